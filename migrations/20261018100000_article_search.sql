@@ -0,0 +1,17 @@
+-- Full-text search over articles.
+--
+-- The language (i.e. the stemming and stop-word rules) is stored per article rather than hardcoded in the generated
+-- column, as the two-argument form of `to_tsvector()` is immutable as long as the config is a column,
+-- and it leaves the door open to articles in different languages.
+alter table article
+    add column search_language regconfig not null default 'english',
+
+    -- A generated column means we never have to remember to keep this up to date in `update_article()`.
+    -- The weights rank matches in the title above the description, and the description above the body.
+    add column search_vector   tsvector generated always as (
+            setweight(to_tsvector(search_language, title), 'A') ||
+            setweight(to_tsvector(search_language, description), 'B') ||
+            setweight(to_tsvector(search_language, body), 'C')
+        ) stored;
+
+create index article_search_gin on article using gin (search_vector);
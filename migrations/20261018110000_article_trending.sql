@@ -0,0 +1,34 @@
+-- Trending scores for `GET /api/articles/trending`.
+--
+-- Each favorite and comment from the past week adds to an article's score, decaying by half every day,
+-- so a burst of activity today outranks a bigger one from last week.
+--
+-- Comments only count once per commenter (their latest one) so a single user can't push an article up the list
+-- just by arguing in its comments.
+--
+-- This is a materialized view so listing trending articles is just an index scan. It's refreshed periodically by the
+-- API server (see `TRENDING_REFRESH_INTERVAL` in `.env.sample`), which means `now()` below is the time of the last
+-- refresh, not the time of the query.
+create materialized view article_trending as
+with activity as (
+    select article_id, created_at, 1.0 weight
+    from article_favorite
+    where created_at > now() - interval '7 days'
+
+    union all
+
+    select article_id, max(created_at), 0.5
+    from article_comment
+    where created_at > now() - interval '7 days'
+    group by article_id, user_id
+)
+select
+    article_id,
+    sum(weight * power(0.5, extract(epoch from now() - created_at) / 86400))::float8 score
+from activity
+group by article_id;
+
+-- `refresh materialized view concurrently` requires a unique index, and lets reads carry on during the refresh.
+create unique index article_trending_article_id on article_trending (article_id);
+
+create index article_trending_score on article_trending (score desc);
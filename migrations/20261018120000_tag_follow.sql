@@ -0,0 +1,31 @@
+-- Lets users follow tags as well as authors, so articles with those tags show up in their feed.
+create table tag_follow
+(
+    user_id    uuid        not null references "user" (user_id) on delete cascade,
+
+    -- This isn't a foreign key as there's no `tag` table; tags only exist in `article.tag_list`.
+    -- That also means a user can follow a tag before anyone has written an article with it.
+    tag        text        not null,
+
+    created_at timestamptz not null default now(),
+
+    -- Same reasoning as `follow.updated_at`.
+    updated_at timestamptz,
+
+    -- `user_id` first so this serves "which tags am I following", which is what the feed needs.
+    primary key (user_id, tag)
+);
+
+select trigger_updated_at('tag_follow');
+
+-- The articles each user has opened, so the feed can leave them out with `?unseen=true`.
+create table article_seen
+(
+    user_id    uuid        not null references "user" (user_id) on delete cascade,
+    article_id uuid        not null references article (article_id) on delete cascade,
+
+    -- Bumped every time the user opens the article again.
+    seen_at    timestamptz not null default now(),
+
+    primary key (user_id, article_id)
+);
@@ -0,0 +1,21 @@
+-- A private "read later" list.
+--
+-- This is deliberately a separate table from `article_favorite` rather than a flag on it: favorites are public and
+-- counted in `favoritesCount`, while bookmarks are only ever visible to the user who made them.
+create table article_bookmark
+(
+    article_id uuid        not null references article (article_id) on delete cascade,
+    user_id    uuid        not null references "user" (user_id) on delete cascade,
+
+    created_at timestamptz not null default now(),
+    updated_at timestamptz,
+
+    -- `user_id` first (unlike `article_favorite`) since the only lookups by a single column are
+    -- "what has this user bookmarked", for `GET /api/user/bookmarks`.
+    primary key (user_id, article_id)
+);
+
+select trigger_updated_at('article_bookmark');
+
+-- For paginating `GET /api/user/bookmarks`, most recently bookmarked first.
+create index article_bookmark_user_created on article_bookmark (user_id, created_at desc, article_id desc);
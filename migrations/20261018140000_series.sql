@@ -0,0 +1,66 @@
+-- Series group an author's articles into an ordered collection, e.g. a multi-part tutorial.
+create table series
+(
+    series_id   uuid primary key     default uuid_generate_v1mc(),
+
+    -- Only this user can modify the series, and only their own articles can be part of it.
+    user_id     uuid        not null references "user" (user_id) on delete cascade,
+
+    -- Generated from the title the same way as article slugs.
+    slug        text unique not null,
+
+    title       text        not null,
+    description text        not null default '',
+
+    created_at  timestamptz not null default now(),
+
+    -- Same as `article.updated_at`.
+    updated_at  timestamptz not null default now()
+);
+
+select trigger_updated_at('series');
+
+create index series_user_id on series (user_id);
+
+create table series_article
+(
+    series_id  uuid not null references series (series_id) on delete cascade,
+
+    -- An article can only be part of one series, otherwise "previous" and "next" would be ambiguous.
+    article_id uuid not null unique references article (article_id) on delete cascade,
+
+    -- 1-based and kept contiguous by the application, which rewrites the whole list on every update.
+    -- That makes looking up the previous and next articles a simple equality check below.
+    position   int  not null check (position > 0),
+
+    primary key (series_id, article_id),
+
+    -- Deferred so the positions can be rewritten in any order within a transaction.
+    constraint series_article_position unique (series_id, position) deferrable initially deferred
+);
+
+-- Everything `Article.series` needs to know about an article's place in its series.
+--
+-- Articles are looked up by `article_id` and this is a plain view (no aggregates or window functions at the top level),
+-- so Postgres can push that condition down instead of computing this for every series.
+create view article_series_position as
+select
+    sa.article_id,
+    series.slug  series_slug,
+    series.title series_title,
+    sa.position,
+    (select count(*) from series_article other where other.series_id = sa.series_id) series_length,
+    (
+        select article.slug
+        from series_article prev
+        inner join article using (article_id)
+        where prev.series_id = sa.series_id and prev.position = sa.position - 1
+    ) previous_slug,
+    (
+        select article.slug
+        from series_article next
+        inner join article using (article_id)
+        where next.series_id = sa.series_id and next.position = sa.position + 1
+    ) next_slug
+from series_article sa
+inner join series using (series_id);
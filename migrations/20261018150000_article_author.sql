@@ -0,0 +1,71 @@
+-- Co-authors.
+--
+-- `article.user_id` stays as the article's owner, which is who the Realworld `author` field shows. Every author,
+-- including the owner, gets a row in here so permission checks only have to look in one place.
+create table article_author
+(
+    article_id  uuid        not null references article (article_id) on delete cascade,
+    user_id     uuid        not null references "user" (user_id) on delete cascade,
+
+    -- `owner` can invite and remove editors, and delete the article. `editor` can only edit it.
+    --
+    -- This could be a Postgres enum, but those are a pain to extend later and SQLx needs a custom type for them.
+    role        text        not null check (role in ('owner', 'editor')),
+
+    -- Co-authors are invited by the owner and have to accept before they count.
+    -- `null` means the invitation is still pending.
+    accepted_at timestamptz,
+
+    -- `set null` rather than `cascade` so an invitation doesn't vanish just because whoever sent it left.
+    invited_by  uuid        references "user" (user_id) on delete set null,
+
+    created_at  timestamptz not null default now(),
+    updated_at  timestamptz,
+
+    primary key (article_id, user_id)
+);
+
+select trigger_updated_at('article_author');
+
+-- Exactly one owner per article. (Well, at most one; `create_article()` is responsible for the "at least".)
+create unique index article_author_owner on article_author (article_id) where role = 'owner';
+
+-- For `GET /api/user/invitations`.
+create index article_author_user_id on article_author (user_id);
+
+insert into article_author (article_id, user_id, role, accepted_at)
+select article_id, user_id, 'owner', created_at
+from article;
+
+-- `Article.authors` as JSON, owner first.
+--
+-- This is needed by every query that returns an `Article`, and SQLx can't decode an array of composite values
+-- into a `Vec` of structs without a lot of ceremony, so it's a function returning JSON instead.
+--
+-- `viewer_id` is the current user, if any, for the `following` flag.
+create function article_authors(article_id uuid, viewer_id uuid) returns jsonb
+    language sql
+    stable
+as
+$$
+select coalesce(
+               jsonb_agg(
+                       jsonb_build_object(
+                               'username', author.username,
+                               'bio', author.bio,
+                               'image', author.image,
+                               'following', exists(select 1
+                                                   from follow
+                                                   where followed_user_id = author.user_id
+                                                     and following_user_id = viewer_id),
+                               'role', article_author.role
+                           )
+                       order by article_author.role = 'owner' desc, article_author.accepted_at
+                   ),
+               '[]'
+           )
+from article_author
+         inner join "user" author using (user_id)
+where article_author.article_id = article_authors.article_id
+  and article_author.accepted_at is not null
+$$;
@@ -0,0 +1,15 @@
+-- Article views, aggregated per day.
+--
+-- Individual views are never stored: the server deduplicates them in memory and adds them up before writing them
+-- here in batches, so this gets one upsert per article per flush instead of one insert per page load. That also
+-- means there's nothing here that could identify a reader.
+create table article_view_daily
+(
+    article_id uuid   not null references article (article_id) on delete cascade,
+    -- UTC
+    day        date   not null,
+    views      bigint not null check (views > 0),
+
+    -- Also covers `viewsCount`, which sums every day for an article.
+    primary key (article_id, day)
+);
@@ -0,0 +1,21 @@
+-- Computed from the Markdown-stripped body by the application whenever an article is created or its body changes.
+-- See `markdown::TextStats`.
+alter table article
+    add column word_count           int not null default 0,
+    add column reading_time_minutes int not null default 0;
+
+-- Existing articles get a rough approximation, since we can't strip Markdown from SQL. `\w+` is Unicode-aware, but it
+-- counts a run of CJK text as one word and counts the words in link URLs. They'll be corrected the next time the
+-- article is edited.
+--
+-- The trigger would bump `updated_at` on every article, which would make them all look like they were just edited.
+alter table article disable trigger set_updated_at;
+
+update article
+set word_count = (select count(*) from regexp_matches(body, '\w+', 'g'));
+
+update article
+set reading_time_minutes = ceil(word_count / 200.0)
+where word_count > 0;
+
+alter table article enable trigger set_updated_at;
@@ -0,0 +1,46 @@
+-- `DELETE /api/articles/:slug` now moves the article to the trash by setting this, rather than deleting it outright,
+-- so its comments, favorites and everything else are still there if it's restored.
+--
+-- Articles in the trash are hidden from every query except `GET /api/user/trash`, and purged for real once they've
+-- been in there longer than `TRASH_RETENTION_DAYS`.
+--
+-- The slug stays taken while an article is in the trash so it can always be restored.
+alter table article
+    add column deleted_at timestamptz;
+
+-- For `GET /api/user/trash` and the purge. Partial, since almost every article isn't in the trash.
+create index article_trash on article (user_id, deleted_at desc, article_id desc) where deleted_at is not null;
+create index article_deleted_at on article (deleted_at) where deleted_at is not null;
+
+-- The length of a series and the previous and next articles skip over articles in the trash. `position` is left alone
+-- so nothing moves around when an article is restored.
+create or replace view article_series_position as
+select
+    sa.article_id,
+    series.slug  series_slug,
+    series.title series_title,
+    sa.position,
+    (
+        select count(*)
+        from series_article other
+        inner join article using (article_id)
+        where other.series_id = sa.series_id and article.deleted_at is null
+    ) series_length,
+    (
+        select article.slug
+        from series_article prev
+        inner join article using (article_id)
+        where prev.series_id = sa.series_id and prev.position < sa.position and article.deleted_at is null
+        order by prev.position desc
+        limit 1
+    ) previous_slug,
+    (
+        select article.slug
+        from series_article next
+        inner join article using (article_id)
+        where next.series_id = sa.series_id and next.position > sa.position and article.deleted_at is null
+        order by next.position
+        limit 1
+    ) next_slug
+from series_article sa
+inner join series using (series_id);
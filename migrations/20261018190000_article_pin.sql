@@ -0,0 +1,15 @@
+-- Articles pinned to the top of their owner's profile.
+--
+-- Only the owner can pin an article, and `GET /api/articles?author=` only matches owners, so the article is enough of
+-- a key. `user_id` is still stored so "what has this user pinned" (for the limit and the profile) doesn't have to
+-- go through `article`.
+create table article_pin
+(
+    article_id uuid primary key references article (article_id) on delete cascade,
+    user_id    uuid        not null references "user" (user_id) on delete cascade,
+
+    created_at timestamptz not null default now()
+);
+
+-- Most recently pinned first, which is the order they're shown in.
+create index article_pin_user_created on article_pin (user_id, created_at desc);
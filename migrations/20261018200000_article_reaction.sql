@@ -0,0 +1,43 @@
+-- Emoji reactions, like on a GitHub issue. Unlike `article_favorite`, a user can leave several different ones.
+--
+-- `kind` is a short name like `heart` rather than the emoji itself so it's easy to put in a URL. Which kinds are
+-- allowed is up to `Config::article_reactions`, not a check constraint, so the set can change without a migration.
+create table article_reaction
+(
+    article_id uuid        not null references article (article_id) on delete cascade,
+    user_id    uuid        not null references "user" (user_id) on delete cascade,
+    kind       text        not null,
+
+    created_at timestamptz not null default now(),
+
+    -- `article_id` first as everything looks these up by article.
+    primary key (article_id, user_id, kind)
+);
+
+-- `Article.reactions` and `Article.userReactions` as JSON, for the same reasons as `article_authors()`:
+-- `{"counts": {"heart": 2}, "mine": ["heart"]}`.
+--
+-- Kinds nobody has used on the article are left out of `counts` rather than showing up as zero.
+create function article_reactions(article_id uuid, viewer_id uuid) returns jsonb
+    language sql
+    stable
+as
+$$
+select jsonb_build_object(
+               'counts', coalesce(
+                       (select jsonb_object_agg(counts.kind, counts.count)
+                        from (select reaction.kind, count(*)
+                              from article_reaction reaction
+                              where reaction.article_id = article_reactions.article_id
+                              group by reaction.kind) counts),
+                       '{}'
+                   ),
+               'mine', coalesce(
+                       (select jsonb_agg(reaction.kind order by reaction.kind)
+                        from article_reaction reaction
+                        where reaction.article_id = article_reactions.article_id
+                          and reaction.user_id = viewer_id),
+                       '[]'
+                   )
+           )
+$$;
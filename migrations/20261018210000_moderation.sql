@@ -0,0 +1,85 @@
+-- Reporting and moderation.
+
+-- There's deliberately no endpoint to make someone a moderator; it's done directly in the database:
+--
+-- update "user" set is_moderator = true where username = '...';
+alter table "user"
+    add column is_moderator boolean not null default false,
+    -- Suspended users can still read, but can't do anything that needs them to be logged in.
+    add column suspended_at timestamptz;
+
+-- Hiding an article also moves it to the trash (`deleted_at`), so it's left out everywhere without every query that
+-- already checks `deleted_at` having to check this as well. Unlike something the owner trashed, it can't be restored
+-- by the owner and isn't purged, so a moderator can change their mind.
+--
+-- If the owner had already trashed the article, `deleted_at` is left alone, and unhiding it leaves it in the trash.
+alter table article
+    add column hidden_at timestamptz;
+
+-- Comments are deleted outright rather than trashed, so hiding them needs its own filter.
+alter table article_comment
+    add column hidden_at timestamptz;
+
+create table report
+(
+    report_id   uuid primary key     default uuid_generate_v1mc(),
+
+    -- `set null` so reports outlive the account that made them.
+    reporter_id uuid        references "user" (user_id) on delete set null,
+
+    target_type text        not null check (target_type in ('article', 'comment', 'profile')),
+    -- Exactly one of these is set for `article` and `comment` reports, neither for `profile` ones.
+    --
+    -- Reports go away with what they're about; `moderation_log.details` keeps a record of them.
+    article_id  uuid references article (article_id) on delete cascade,
+    comment_id  bigint references article_comment (comment_id) on delete cascade,
+    -- Who wrote the reported content, or the reported user themselves. This is who gets suspended.
+    author_id   uuid        not null references "user" (user_id) on delete cascade,
+
+    reason      text        not null,
+
+    -- `open` -> `triaged` (a moderator has picked it up) -> `resolved` or `dismissed`.
+    status      text        not null default 'open'
+        check (status in ('open', 'triaged', 'resolved', 'dismissed')),
+    -- What was done about it: `hide`, `suspend` or `dismiss`.
+    action      text check (action in ('hide', 'suspend', 'dismiss')),
+    note        text,
+
+    triaged_by  uuid        references "user" (user_id) on delete set null,
+    triaged_at  timestamptz,
+    resolved_by uuid        references "user" (user_id) on delete set null,
+    resolved_at timestamptz,
+
+    created_at  timestamptz not null default now(),
+    updated_at  timestamptz,
+
+    check ((target_type = 'article') = (article_id is not null)),
+    check ((target_type = 'comment') = (comment_id is not null))
+);
+
+select trigger_updated_at('report');
+
+-- The queue: oldest first, by status.
+create index report_status_created on report (status, created_at);
+
+-- One report per user per target until it's dealt with.
+create unique index report_pending_unique on report (
+    reporter_id, target_type, coalesce(article_id::text, comment_id::text, author_id::text)
+) where status in ('open', 'triaged');
+
+-- Every action taken by anyone in the moderation system, including reporting something.
+--
+-- Nothing ever updates or deletes these rows, and nothing references anything with `cascade`, so the log outlives
+-- whatever it's about. `details` has enough to make sense of an entry even if the report or target is gone.
+create table moderation_log
+(
+    log_id     uuid primary key     default uuid_generate_v1mc(),
+    actor_id   uuid        references "user" (user_id) on delete set null,
+    -- `report`, `triage`, `hide`, `suspend`, `dismiss` or `reopen`.
+    action     text        not null,
+    report_id  uuid        references report (report_id) on delete set null,
+    details    jsonb       not null default '{}',
+    created_at timestamptz not null default now()
+);
+
+create index moderation_log_created on moderation_log (created_at desc);
@@ -0,0 +1,22 @@
+-- Content filters (see `src/filter.rs`).
+
+-- What `DuplicateFilter` compares bodies by: case and whitespace don't count, so reflowing or shouting the same text
+-- doesn't get it past. `md5()` is plenty for this and keeps the indexes below small.
+create function content_fingerprint(body text) returns text
+    language sql
+    immutable
+    parallel safe
+as
+$$
+select md5(lower(regexp_replace(btrim(body), '\s+', ' ', 'g')))
+$$;
+
+create index article_content_fingerprint on article (content_fingerprint(body));
+create index article_comment_content_fingerprint on article_comment (content_fingerprint(body));
+
+-- Content a filter held for moderation is hidden with a report in the queue, made by nobody (`reporter_id` is null),
+-- with the filter's reason. Dismissing one of these releases the content rather than leaving it hidden.
+alter table report
+    add column held boolean not null default false;
+
+-- Holding something is logged as `hold`, with a null `actor_id` since nobody did it.
@@ -0,0 +1,15 @@
+-- Threaded replies to comments.
+
+alter table article_comment
+    -- Always on the same article; `create_comment()` checks that.
+    add column parent_comment_id bigint references article_comment (comment_id) on delete cascade,
+    -- How many replies deep this is; top-level comments are `0`. Stored so checking `Config::max_comment_depth`
+    -- doesn't have to walk up the thread.
+    add column depth             int not null default 0,
+    -- Deleting a comment that has replies leaves it in place with its `body` blanked out, so the replies
+    -- still have something to hang off. It's shown as `[deleted]` until the last reply goes, when it's
+    -- deleted for real.
+    add column deleted_at        timestamptz;
+
+-- For finding replies when deleting a comment.
+create index on article_comment (parent_comment_id);
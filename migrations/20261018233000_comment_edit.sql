@@ -0,0 +1,22 @@
+-- Editing comments.
+
+-- `updated_at` changes whenever anything about the comment does, including a moderator hiding it,
+-- so it can't tell whether the author edited it.
+alter table article_comment
+    add column edited_at timestamptz;
+
+-- Every version of a comment except the current one, which is still in `article_comment`.
+--
+-- Only moderators can see these. They go when the comment does, including when it's left as a `[deleted]`
+-- placeholder, as the author asked for the text to be gone.
+create table article_comment_edit
+(
+    edit_id    uuid primary key     default uuid_generate_v1mc(),
+    comment_id bigint      not null references article_comment (comment_id) on delete cascade,
+    body       text        not null,
+    -- When this version was posted, and when it was replaced by the next one.
+    written_at timestamptz not null,
+    created_at timestamptz not null default now()
+);
+
+create index on article_comment_edit (comment_id, created_at);
@@ -0,0 +1,18 @@
+-- `Article.commentsCount`: the comments anyone can see, so not hidden ones or `[deleted]` placeholders.
+--
+-- A function for the same reasons as `article_authors()`, and so everything that counts comments agrees.
+create function article_comments_count(article_id uuid) returns int8
+    language sql
+    stable
+as
+$$
+select count(*)
+from article_comment comment
+where comment.article_id = article_comments_count.article_id
+  and comment.deleted_at is null
+  and comment.hidden_at is null
+$$;
+
+-- Top-level comments are listed a page at a time, oldest or newest first; replies are fetched along with them.
+create index article_comment_top_level on article_comment (article_id, created_at, comment_id)
+    where parent_comment_id is null;
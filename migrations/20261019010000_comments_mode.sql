@@ -0,0 +1,42 @@
+-- Per-article comment settings, and letting an article's authors moderate its comments.
+
+-- `open`, `approval_required` (new comments wait for one of the article's authors to approve them), `locked` (no new
+-- comments, but the existing ones are still shown) or `disabled` (no new comments, and the existing ones are hidden).
+--
+-- Text with a check constraint rather than an enum, like `article_author.role`.
+alter table article
+    add column comments_mode text not null default 'open'
+        check (comments_mode in ('open', 'approval_required', 'locked', 'disabled'));
+
+alter table article_comment
+    -- Waiting for approval. Only the commenter and the article's authors can see it until then.
+    add column pending             boolean not null default false,
+    -- Separate from `hidden_at` so moderators and the article's authors can't undo each other's decisions.
+    add column hidden_by_author_at timestamptz;
+
+-- Same as before, except for leaving out pending comments and ones the article's authors have hidden. Split out of
+-- `article_comments_count()` for updates to `comments_mode`, which that can't see until the statement's done.
+create function article_visible_comments_count(article_id uuid) returns int8
+    language sql
+    stable
+as
+$$
+select count(*)
+from article_comment comment
+where comment.article_id = article_visible_comments_count.article_id
+  and comment.deleted_at is null
+  and comment.hidden_at is null
+  and comment.hidden_by_author_at is null
+  and not comment.pending
+$$;
+
+-- Nothing counts on articles with comments disabled.
+create or replace function article_comments_count(article_id uuid) returns int8
+    language sql
+    stable
+as
+$$
+select case when article.comments_mode = 'disabled' then 0 else article_visible_comments_count(article.article_id) end
+from article
+where article.article_id = article_comments_count.article_id
+$$;
@@ -0,0 +1,57 @@
+-- `@username` mentions in articles and comments, and notifications for the users mentioned.
+
+-- Who's mentioned where, as of the latest version of each article and comment. See `record_mentions()`.
+create table mention
+(
+    article_id uuid        not null references article (article_id) on delete cascade,
+    -- `null` for mentions in the article itself.
+    comment_id bigint references article_comment (comment_id) on delete cascade,
+    user_id    uuid        not null references "user" (user_id) on delete cascade,
+    created_at timestamptz not null default now()
+);
+
+-- One of each per user. Two partial indexes rather than one on all three columns, as `null`s are distinct from each
+-- other so every mention in an article itself would be too, and `unique nulls not distinct` needs Postgres 15.
+create unique index mention_article_user on mention (article_id, user_id) where comment_id is null;
+create unique index mention_comment_user on mention (comment_id, user_id) where comment_id is not null;
+
+create index mention_user_created on mention (user_id, created_at desc);
+
+create table notification
+(
+    notification_id bigserial primary key,
+    -- Who it's for.
+    user_id         uuid        not null references "user" (user_id) on delete cascade,
+    -- Only `mention` for now. Text with a check constraint rather than an enum, like `article.comments_mode`.
+    kind            text        not null check (kind in ('mention')),
+    -- Who did whatever it's about.
+    actor_id        uuid        not null references "user" (user_id) on delete cascade,
+    article_id      uuid        not null references article (article_id) on delete cascade,
+    -- `null` if it's about the article itself.
+    comment_id      bigint references article_comment (comment_id) on delete cascade,
+    created_at      timestamptz not null default now(),
+    read_at         timestamptz
+);
+
+create index notification_user on notification (user_id, notification_id desc);
+
+-- Notifications about things that can currently be seen: nothing in the trash, hidden, deleted, waiting for approval
+-- or on an article with comments disabled. Rather than deleting notifications when that happens, they're left out
+-- here, so they come back if it's undone.
+create view visible_notification as
+select notification.*
+from notification
+inner join article on article.article_id = notification.article_id
+left join article_comment comment on comment.comment_id = notification.comment_id
+where article.deleted_at is null
+  and article.hidden_at is null
+  and (
+    notification.comment_id is null
+        or (
+        comment.deleted_at is null
+            and comment.hidden_at is null
+            and comment.hidden_by_author_at is null
+            and not comment.pending
+            and article.comments_mode <> 'disabled'
+        )
+    );
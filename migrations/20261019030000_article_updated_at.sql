@@ -0,0 +1,20 @@
+-- `updated_at` is meant to say when an article was last edited, but the trigger from `trigger_updated_at()` bumps it
+-- on any change at all, including moving it to the trash and back or a moderator hiding it. That made a restored
+-- article jump to the top of `sort=recently_updated`.
+--
+-- So articles get their own trigger that only looks at what their authors can change. Anything added to `article`
+-- later that counts as an edit needs adding here too.
+drop trigger set_updated_at on article;
+
+create trigger set_updated_at
+    before update
+    on article
+    for each row
+    when (
+        (old.user_id, old.slug, old.title, old.description, old.body, old.tag_list, old.search_language,
+         old.word_count, old.reading_time_minutes, old.comments_mode)
+            is distinct from
+        (new.user_id, new.slug, new.title, new.description, new.body, new.tag_list, new.search_language,
+         new.word_count, new.reading_time_minutes, new.comments_mode)
+    )
+execute function set_updated_at();
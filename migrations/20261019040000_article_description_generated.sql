@@ -0,0 +1,6 @@
+-- Whether `description` was generated from the body because the author left it out, in which case it's regenerated
+-- whenever the body changes. See `markdown::excerpt()`.
+--
+-- There's no telling for existing articles, so they keep whatever description they have until it's set to `''`.
+alter table article
+    add column description_generated boolean not null default false;
@@ -0,0 +1,7 @@
+-- `delete_comment()` leaves a tombstone rather than deleting a comment with replies, but anything else that deletes
+-- one, like a cascade from its author's `user` row, shouldn't take everyone else's replies with it. They become
+-- top-level comments instead, keeping their old `depth`.
+alter table article_comment
+    drop constraint article_comment_parent_comment_id_fkey,
+    add constraint article_comment_parent_comment_id_fkey
+        foreign key (parent_comment_id) references article_comment (comment_id) on delete set null;
@@ -0,0 +1,15 @@
+-- Turning comments off or locking them isn't an edit to the article, so it shouldn't bump `updated_at` either.
+drop trigger set_updated_at on article;
+
+create trigger set_updated_at
+    before update
+    on article
+    for each row
+    when (
+        (old.user_id, old.slug, old.title, old.description, old.body, old.tag_list, old.search_language,
+         old.word_count, old.reading_time_minutes)
+            is distinct from
+        (new.user_id, new.slug, new.title, new.description, new.body, new.tag_list, new.search_language,
+         new.word_count, new.reading_time_minutes)
+    )
+execute function set_updated_at();
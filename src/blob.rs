@@ -0,0 +1,83 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Somewhere to put uploaded files.
+///
+/// Keys are relative paths like `ab/abcdef….png`. They're chosen by the caller, and since we
+/// name everything after a hash of its contents, the same key always means the same bytes.
+/// That means implementations don't have to worry about overwrites or cache invalidation.
+///
+/// Only the local filesystem is supported for now, but this is the seam where something
+/// S3-compatible would plug in.
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    /// Store `data` under `key`, unless there's already something there.
+    ///
+    /// `content_type` isn't needed for the local filesystem (`ServeDir` goes by the extension),
+    /// but an object store would want to set it on the object.
+    async fn put(&self, key: &str, content_type: &str, data: Vec<u8>) -> anyhow::Result<()>;
+
+    /// The URL clients should use to fetch `key`.
+    fn url(&self, key: &str) -> String;
+}
+
+pub type DynBlobStore = Arc<dyn BlobStore>;
+
+/// Stores blobs as files under a directory, which `serve()` exposes as a static route.
+pub struct LocalBlobStore {
+    root: PathBuf,
+    base_url: String,
+}
+
+impl LocalBlobStore {
+    pub fn new(root: impl Into<PathBuf>, base_url: &str) -> Self {
+        Self {
+            root: root.into(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+#[async_trait]
+impl BlobStore for LocalBlobStore {
+    async fn put(&self, key: &str, _content_type: &str, data: Vec<u8>) -> anyhow::Result<()> {
+        let path = self.root.join(key);
+
+        // Content-addressed, so if it's already there it's already the right bytes.
+        if tokio::fs::try_exists(&path).await? {
+            return Ok(());
+        }
+
+        let dir = path
+            .parent()
+            .with_context(|| format!("blob key has no parent directory: {key}"))?;
+
+        tokio::fs::create_dir_all(dir)
+            .await
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+
+        // Write somewhere else first and then move it into place, so a request for the file
+        // can never see it half-written. `rename()` is atomic as long as both are on the same
+        // filesystem, which is why the temporary file goes in the same directory.
+        let temp_path = dir.join(format!(".{}.tmp", Uuid::new_v4()));
+
+        tokio::fs::write(&temp_path, data)
+            .await
+            .with_context(|| format!("failed to write {}", temp_path.display()))?;
+
+        tokio::fs::rename(&temp_path, &path)
+            .await
+            .with_context(|| format!("failed to move blob into place at {}", path.display()))
+    }
+
+    fn url(&self, key: &str) -> String {
+        format!("{}/{key}", self.base_url)
+    }
+}
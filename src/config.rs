@@ -1,3 +1,8 @@
+use crate::filter::FilterAction;
+use crate::models::article::SlugFallback;
+use crate::models::listing::CountMode;
+use std::path::PathBuf;
+
 /// The configuration parameters for the application.
 ///
 /// These can either be passed on the command line, or pulled from environment variables.
@@ -28,4 +33,170 @@ pub struct Config {
 
     #[clap(long, env)]
     pub port: u16,
+
+    /// The maximum length of a generated article slug, in characters.
+    ///
+    /// Longer titles are truncated at the last word boundary that fits. Must be at least 8, the
+    /// length of the random slug used for titles with nothing usable in them.
+    #[clap(
+        long,
+        env,
+        default_value_t = 100,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(8..)
+    )]
+    pub slug_max_length: usize,
+
+    /// What to do with characters in an article title that have no ASCII transliteration.
+    #[clap(long, env, value_enum, default_value_t = SlugFallback::Drop)]
+    pub slug_fallback: SlugFallback,
+
+    /// A comma-separated list of words to leave out of article slugs, e.g. `a,an,the`.
+    ///
+    /// Empty by default, so slugs keep every word of the title.
+    #[clap(long, env, value_delimiter = ',')]
+    pub slug_stop_words: Vec<String>,
+
+    /// How many rendered article bodies to keep in memory.
+    #[clap(long, env, default_value_t = 1000)]
+    pub markdown_cache_capacity: usize,
+
+    /// The Postgres text search configuration used to index new articles and parse search queries.
+    ///
+    /// This decides the stemming and stop-word rules, e.g. `english`, `german` or `simple`.
+    /// Run `\dF` in `psql` for the full list.
+    #[clap(long, env, default_value = "english")]
+    pub search_language: String,
+
+    /// How `articlesCount` is calculated in article listings and the feed, unless the request
+    /// overrides it with `?count=`.
+    ///
+    /// `exact` is the most useful for page-number pagination but has to touch every matching row.
+    #[clap(long, env, value_enum, default_value_t = CountMode::Exact)]
+    pub articles_count_mode: CountMode,
+
+    /// How often, in seconds, to recompute the scores for `GET /api/articles/trending`.
+    /// Must be at least 1.
+    #[clap(long, env, default_value_t = 300, value_parser = clap::value_parser!(u64).range(1..))]
+    pub trending_refresh_interval: u64,
+
+    /// The directory uploaded images are stored in. It's created if it doesn't exist.
+    #[clap(long, env, default_value = "uploads")]
+    pub upload_dir: PathBuf,
+
+    /// The URL uploaded images are served from, which `upload_dir` is mapped to.
+    ///
+    /// The server always serves them at `/uploads`, but if there's a CDN or reverse proxy
+    /// in front of it, this can point there instead, e.g. `https://cdn.example.com/uploads`.
+    #[clap(long, env, default_value = "/uploads")]
+    pub upload_base_url: String,
+
+    /// The largest image that can be uploaded, in bytes. Defaults to 5 MiB.
+    #[clap(long, env, default_value_t = 5 * 1024 * 1024)]
+    pub upload_max_bytes: usize,
+
+    /// Repeat views of an article by the same reader within this many seconds are only counted once.
+    #[clap(long, env, default_value_t = 30 * 60)]
+    pub view_dedup_window: u64,
+
+    /// How often, in seconds, buffered article views are written to the database.
+    /// Must be at least 1.
+    #[clap(long, env, default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+    pub view_flush_interval: u64,
+
+    /// Use the first address in `X-Forwarded-For` as the client's IP address.
+    ///
+    /// Only turn this on behind a reverse proxy that sets the header, since otherwise clients
+    /// can put whatever they want in it. It's currently only used to tell anonymous readers
+    /// apart when counting article views.
+    #[clap(long, env)]
+    pub trust_forwarded_for: bool,
+
+    /// How many days deleted articles stay in the trash, where they can be restored,
+    /// before they're deleted for good.
+    #[clap(long, env, default_value_t = 30)]
+    pub trash_retention_days: u32,
+
+    /// How many articles an author can pin to the top of their profile.
+    #[clap(long, env, default_value_t = 3)]
+    pub max_pinned_articles: i64,
+
+    /// A comma-separated list of the reactions users can leave on articles.
+    ///
+    /// These are names rather than the emoji themselves so they're easy to use in URLs;
+    /// it's up to frontends how to draw them. Taking one out of the list stops any new reactions
+    /// of that kind, but the ones already left still show up in `Article.reactions`.
+    #[clap(
+        long,
+        env,
+        value_delimiter = ',',
+        default_value = "thumbsup,heart,tada,thinking"
+    )]
+    pub article_reactions: Vec<String>,
+
+    /// How deeply replies to comments can be nested. Top-level comments are at depth 0,
+    /// replies to them at depth 1, and so on; `0` turns replies off altogether.
+    #[clap(long, env, default_value_t = 5)]
+    pub max_comment_depth: i32,
+
+    /// A list of banned words and patterns for new articles and comments, one per line.
+    ///
+    /// See `WordFilter` for the format. The server won't start if it can't be read.
+    #[clap(long, env)]
+    pub content_filter_file: Option<PathBuf>,
+
+    /// How many days an account counts as new for `content_filter_max_links`.
+    #[clap(long, env, default_value_t = 3)]
+    pub content_filter_new_account_days: u32,
+
+    /// How many links new accounts can put in an article or comment.
+    #[clap(long, env, default_value_t = 2)]
+    pub content_filter_max_links: usize,
+
+    /// What to do when a new account posts too many links.
+    #[clap(long, env, value_enum, default_value_t = FilterAction::Hold)]
+    pub content_filter_link_action: FilterAction,
+
+    /// How many hours the same article or comment body can't be posted again for, by anyone.
+    #[clap(long, env, default_value_t = 24)]
+    pub content_filter_duplicate_window: u32,
+
+    /// What to do when someone posts a duplicate body.
+    #[clap(long, env, value_enum, default_value_t = FilterAction::Reject)]
+    pub content_filter_duplicate_action: FilterAction,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn parse(args: &[&str]) -> Result<Config, clap::Error> {
+        let required = [
+            "realworld",
+            "--database-url=",
+            "--hmac-key=test",
+            "--port=0",
+        ];
+        Config::try_parse_from(required.iter().chain(args))
+    }
+
+    #[test]
+    fn slug_max_length_fits_random_slug() {
+        assert_eq!(parse(&[]).unwrap().slug_max_length, 100);
+        assert_eq!(parse(&["--slug-max-length=8"]).unwrap().slug_max_length, 8);
+        assert!(parse(&["--slug-max-length=7"]).is_err());
+        assert!(parse(&["--slug-max-length=0"]).is_err());
+    }
+
+    #[test]
+    fn trending_refresh_interval_not_zero() {
+        assert_eq!(parse(&[]).unwrap().trending_refresh_interval, 300);
+        assert!(parse(&["--trending-refresh-interval=0"]).is_err());
+    }
+
+    #[test]
+    fn view_flush_interval_not_zero() {
+        assert_eq!(parse(&[]).unwrap().view_flush_interval, 10);
+        assert!(parse(&["--view-flush-interval=0"]).is_err());
+    }
 }
@@ -0,0 +1,561 @@
+use crate::config::Config;
+use crate::http::{Error, Result};
+use anyhow::Context;
+use async_trait::async_trait;
+use axum::http::StatusCode;
+use regex::{Regex, RegexBuilder};
+use sqlx::PgConnection;
+use std::path::Path;
+use uuid::Uuid;
+
+// New articles and comments go through `ContentFilters` before they're stored. Each filter can
+// let the content through, reject it outright with a `422`, or hold it for moderation, in which
+// case it's stored but hidden, with a report in the moderation queue (see `moderation.rs`) that
+// releases it if a moderator dismisses it.
+//
+// Every filter also decides for itself which of the last two it does, which for the built-in ones
+// is up to `Config`.
+
+/// What a filter wants done with a rule violation.
+#[derive(clap::ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum FilterAction {
+    /// Fail the request with a `422` for the offending field.
+    #[default]
+    Reject,
+    /// Store it, but hidden until a moderator looks at it.
+    Hold,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Allow,
+    Reject {
+        field: &'static str,
+        message: String,
+    },
+    /// `reason` ends up in the report in the moderation queue.
+    Hold {
+        reason: String,
+    },
+}
+
+impl Verdict {
+    fn new(action: FilterAction, field: &'static str, message: String) -> Self {
+        match action {
+            FilterAction::Reject => Self::Reject { field, message },
+            FilterAction::Hold => Self::Hold {
+                reason: format!("{field} {message}"),
+            },
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SubmissionKind {
+    Article,
+    Comment,
+}
+
+/// Something a user wants to post.
+pub struct Submission<'a> {
+    pub kind: SubmissionKind,
+    pub author_id: Uuid,
+    /// The article or comment being edited, if this is an edit, so it doesn't count as
+    /// a duplicate of itself.
+    pub article_id: Option<Uuid>,
+    pub comment_id: Option<i64>,
+    /// The text the user sent, by field name for error messages. Fields that aren't being
+    /// changed by an edit are left out.
+    pub fields: Vec<(&'static str, &'a str)>,
+}
+
+impl Submission<'_> {
+    fn body(&self) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| *field == "body")
+            .map(|(_, text)| *text)
+    }
+}
+
+/// Something that went through the filters, and whether it was held for moderation.
+pub struct Filtered<T> {
+    pub value: T,
+    pub held: bool,
+}
+
+impl<T> Filtered<T> {
+    /// `202 Accepted` for held content, since it was stored but nobody else can see it yet.
+    pub fn status(&self) -> StatusCode {
+        if self.held {
+            StatusCode::ACCEPTED
+        } else {
+            StatusCode::OK
+        }
+    }
+}
+
+/// One rule for what can be posted.
+///
+/// The connection is for filters that need to look things up; `ContentFilters::check()` runs
+/// them in the same transaction the content is inserted in.
+#[async_trait]
+pub trait ContentFilter: Send + Sync {
+    async fn check(
+        &self,
+        conn: &mut PgConnection,
+        submission: &Submission<'_>,
+    ) -> anyhow::Result<Verdict>;
+}
+
+/// Every `ContentFilter` that new content has to get through.
+pub struct ContentFilters {
+    filters: Vec<Box<dyn ContentFilter>>,
+}
+
+impl ContentFilters {
+    pub fn new(filters: Vec<Box<dyn ContentFilter>>) -> Self {
+        Self { filters }
+    }
+
+    /// The built-in filters, as configured.
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let mut filters: Vec<Box<dyn ContentFilter>> = Vec::new();
+
+        if let Some(path) = &config.content_filter_file {
+            filters.push(Box::new(WordFilter::load(path)?));
+        }
+
+        filters.push(Box::new(LinkFilter {
+            new_account_days: config.content_filter_new_account_days,
+            max_links: config.content_filter_max_links,
+            action: config.content_filter_link_action,
+        }));
+
+        filters.push(Box::new(DuplicateFilter {
+            window_hours: config.content_filter_duplicate_window,
+            action: config.content_filter_duplicate_action,
+        }));
+
+        Ok(Self::new(filters))
+    }
+
+    /// Run every filter, in order.
+    ///
+    /// Returns the reason to hold the content for moderation, if any filter wants to.
+    /// A rejection stops at the first filter that rejects; holds keep going, in case
+    /// a later filter would reject it outright.
+    pub async fn check(
+        &self,
+        conn: &mut PgConnection,
+        submission: &Submission<'_>,
+    ) -> Result<Option<String>> {
+        let mut hold = None;
+
+        for filter in &self.filters {
+            match filter.check(conn, submission).await? {
+                Verdict::Allow => (),
+                Verdict::Reject { field, message } => {
+                    return Err(Error::unprocessable_entity([(field, message)]));
+                }
+                Verdict::Hold { reason } => {
+                    hold.get_or_insert(reason);
+                }
+            }
+        }
+
+        Ok(hold)
+    }
+}
+
+/// Banned words and patterns, loaded from `Config::content_filter_file`.
+///
+/// One rule per line, matched case-insensitively. A plain word or phrase only matches as whole
+/// words, so `ass` doesn't match `class`, with any amount of whitespace between the words;
+/// anything between slashes is a regular expression.
+/// Rules reject by default, or hold if prefixed with `hold:`. Blank lines and lines starting
+/// with `#` are ignored:
+///
+/// ```text
+/// # Straight to the bin.
+/// cheap pills
+/// /b[i1]tc[o0]in ?d[o0]ubl(er|ing)/
+///
+/// # Probably spam, but let a moderator decide.
+/// hold: crypto
+/// ```
+pub struct WordFilter {
+    rules: Vec<(FilterAction, Regex)>,
+}
+
+impl WordFilter {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let list = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read content filter file {}", path.display()))?;
+
+        Self::parse(&list).with_context(|| format!("error in {}", path.display()))
+    }
+
+    fn parse(list: &str) -> anyhow::Result<Self> {
+        let mut rules = Vec::new();
+
+        for (number, line) in list.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (action, rule) = match line.strip_prefix("hold:") {
+                Some(rule) => (FilterAction::Hold, rule.trim()),
+                None => (FilterAction::Reject, line),
+            };
+
+            let pattern = match rule.strip_prefix('/').and_then(|r| r.strip_suffix('/')) {
+                Some(pattern) => pattern.to_string(),
+                None => {
+                    let words: Vec<_> = rule.split_whitespace().map(regex::escape).collect();
+                    format!(r"\b{}\b", words.join(r"\s+"))
+                }
+            };
+
+            let regex = RegexBuilder::new(&pattern)
+                .case_insensitive(true)
+                .build()
+                .with_context(|| format!("invalid pattern on line {}", number + 1))?;
+
+            rules.push((action, regex));
+        }
+
+        Ok(Self { rules })
+    }
+
+    fn find(&self, submission: &Submission<'_>) -> Verdict {
+        // Rejections go first so a rule that holds can't shadow one that rejects.
+        let rejections = self
+            .rules
+            .iter()
+            .filter(|(action, _)| *action == FilterAction::Reject);
+        let holds = self
+            .rules
+            .iter()
+            .filter(|(action, _)| *action == FilterAction::Hold);
+
+        for (action, regex) in rejections.chain(holds) {
+            for (field, text) in &submission.fields {
+                if let Some(found) = regex.find(text) {
+                    return Verdict::new(
+                        *action,
+                        field,
+                        format!("contains \"{}\", which isn't allowed", found.as_str()),
+                    );
+                }
+            }
+        }
+
+        Verdict::Allow
+    }
+}
+
+#[async_trait]
+impl ContentFilter for WordFilter {
+    async fn check(
+        &self,
+        _conn: &mut PgConnection,
+        submission: &Submission<'_>,
+    ) -> anyhow::Result<Verdict> {
+        Ok(self.find(submission))
+    }
+}
+
+/// Limits how many links accounts can post while they're new, which is most of what spam
+/// accounts are made for.
+pub struct LinkFilter {
+    new_account_days: u32,
+    max_links: usize,
+    action: FilterAction,
+}
+
+fn count_links(text: &str) -> usize {
+    // Bare URLs as well as Markdown links, since the point is what a reader could click on
+    // or copy, and most spam doesn't bother with the Markdown.
+    static LINK: std::sync::OnceLock<Regex> = std::sync::OnceLock::new();
+
+    // The `www.` is part of the scheme alternative so `https://www.` is one link, not two.
+    LINK.get_or_init(|| Regex::new(r"(?i)\b(?:https?://(?:www\.)?|www\.)").expect("valid regex"))
+        .find_iter(text)
+        .count()
+}
+
+#[async_trait]
+impl ContentFilter for LinkFilter {
+    async fn check(
+        &self,
+        conn: &mut PgConnection,
+        submission: &Submission<'_>,
+    ) -> anyhow::Result<Verdict> {
+        let links: usize = submission
+            .fields
+            .iter()
+            .map(|(_, text)| count_links(text))
+            .sum();
+
+        if links <= self.max_links {
+            return Ok(Verdict::Allow);
+        }
+
+        let is_new = sqlx::query_scalar!(
+            r#"
+                select created_at > now() - make_interval(days => $2) "is_new!"
+                from "user"
+                where user_id = $1
+            "#,
+            submission.author_id,
+            self.new_account_days as i32
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+
+        if !is_new {
+            return Ok(Verdict::Allow);
+        }
+
+        // Blame the body if it has any links, as that's where they usually are.
+        let field = submission
+            .fields
+            .iter()
+            .find(|(_, text)| count_links(text) > 0)
+            .map_or("body", |(field, _)| *field);
+
+        Ok(Verdict::new(
+            self.action,
+            field,
+            format!(
+                "can't have more than {} links until your account is {} days old",
+                self.max_links, self.new_account_days
+            ),
+        ))
+    }
+}
+
+/// Anything shorter than this is too likely to be posted more than once innocently,
+/// like "Thanks!" or "Great article".
+const DUPLICATE_MIN_CHARS: usize = 50;
+
+/// How long `body` is for `DUPLICATE_MIN_CHARS`, not counting whitespace.
+///
+/// In characters rather than bytes, or three words of Chinese would be as long as nine in English.
+fn duplicate_check_len(body: &str) -> usize {
+    body.split_whitespace()
+        .map(|word| word.chars().count())
+        .sum()
+}
+
+/// Catches the same body being posted over and over, by anyone, within a window.
+///
+/// Bodies are compared by `content_fingerprint()` (see the migration), which ignores case and
+/// whitespace, so it's no good against anything more determined than copy and paste.
+pub struct DuplicateFilter {
+    window_hours: u32,
+    action: FilterAction,
+}
+
+#[async_trait]
+impl ContentFilter for DuplicateFilter {
+    async fn check(
+        &self,
+        conn: &mut PgConnection,
+        submission: &Submission<'_>,
+    ) -> anyhow::Result<Verdict> {
+        let Some(body) = submission.body() else {
+            return Ok(Verdict::Allow);
+        };
+
+        if duplicate_check_len(body) < DUPLICATE_MIN_CHARS {
+            return Ok(Verdict::Allow);
+        }
+
+        let window_hours = self.window_hours as i32;
+
+        let duplicate = match submission.kind {
+            SubmissionKind::Article => {
+                sqlx::query_scalar!(
+                    r#"
+                    select exists(
+                        select 1
+                        from article
+                        where content_fingerprint(body) = content_fingerprint($1)
+                          and created_at > now() - make_interval(hours => $2)
+                          and article_id is distinct from $3
+                          -- The owner's own trash doesn't count, so they can delete and repost,
+                          -- but held and hidden articles do, or spam could just be posted again.
+                          and not (deleted_at is not null and hidden_at is null)
+                    ) "exists!"
+                "#,
+                    body,
+                    window_hours,
+                    submission.article_id
+                )
+                .fetch_one(&mut *conn)
+                .await?
+            }
+            SubmissionKind::Comment => {
+                sqlx::query_scalar!(
+                    r#"
+                    select exists(
+                        select 1
+                        from article_comment
+                        where content_fingerprint(body) = content_fingerprint($1)
+                          and created_at > now() - make_interval(hours => $2)
+                          and comment_id is distinct from $3
+                    ) "exists!"
+                "#,
+                    body,
+                    window_hours,
+                    submission.comment_id
+                )
+                .fetch_one(&mut *conn)
+                .await?
+            }
+        };
+
+        if !duplicate {
+            return Ok(Verdict::Allow);
+        }
+
+        Ok(Verdict::new(
+            self.action,
+            "body",
+            "has already been posted".to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::article::CreateArticle;
+    use crate::models::testing::{config, create_user, store};
+    use crate::models::StoreTrait;
+    use sqlx::PgPool;
+
+    fn submission(body: &str) -> Submission<'_> {
+        Submission {
+            kind: SubmissionKind::Comment,
+            author_id: Uuid::nil(),
+            article_id: None,
+            comment_id: None,
+            fields: vec![("body", body)],
+        }
+    }
+
+    #[test]
+    fn word_filter() {
+        let filter = WordFilter::parse(
+            "
+            # comment
+            cheap pills
+            /b[i1]tc[o0]in/
+
+            hold: crypto
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(
+            filter.find(&submission("A perfectly nice comment")),
+            Verdict::Allow
+        );
+        // Whole words only.
+        assert_eq!(filter.find(&submission("cheap pillsbury")), Verdict::Allow);
+        assert_eq!(filter.find(&submission("cryptography")), Verdict::Allow);
+
+        assert_eq!(
+            filter.find(&submission("Buy CHEAP\n  PILLS here")),
+            Verdict::Reject {
+                field: "body",
+                message: "contains \"CHEAP\n  PILLS\", which isn't allowed".into()
+            }
+        );
+        assert!(matches!(
+            filter.find(&submission("crypto b1tc0in")),
+            Verdict::Reject { .. }
+        ));
+        assert!(matches!(
+            filter.find(&submission("crypto")),
+            Verdict::Hold { .. }
+        ));
+
+        assert!(WordFilter::parse("/(unclosed/").is_err());
+    }
+
+    #[test]
+    fn counts_links() {
+        assert_eq!(count_links("no links here"), 0);
+        assert_eq!(
+            count_links("[one](https://example.com), HTTP://two.example and www.three.example"),
+            3
+        );
+        assert_eq!(
+            count_links("https://www.example.com and http://www.example.org/page"),
+            2
+        );
+    }
+
+    #[test]
+    fn duplicate_check_len_counts_chars() {
+        assert_eq!(duplicate_check_len("  Great\n article! "), 13);
+        // 20 characters but 60 bytes, so still too short to be checked.
+        let body = "谢谢你写了这篇文章我学到了很多东西真谢谢";
+        assert_eq!(duplicate_check_len(body), 20);
+        assert!(duplicate_check_len(body) < DUPLICATE_MIN_CHARS);
+    }
+
+    #[sqlx::test]
+    async fn duplicates_ignore_trash(pool: PgPool) {
+        let store = store(pool.clone(), config());
+        let alice = create_user(&pool, "alice").await;
+        let body = "The same long article body, posted over and over again by the same person.";
+
+        store
+            .article()
+            .create_article(
+                alice,
+                CreateArticle {
+                    title: "Original".into(),
+                    description: None,
+                    body: body.into(),
+                    tag_list: vec![],
+                    comments_mode: Default::default(),
+                },
+            )
+            .await
+            .unwrap();
+
+        let filter = DuplicateFilter {
+            window_hours: 24,
+            action: FilterAction::Reject,
+        };
+        let check = || async {
+            let mut conn = pool.acquire().await.unwrap();
+            let submission = Submission {
+                kind: SubmissionKind::Article,
+                author_id: alice,
+                article_id: None,
+                comment_id: None,
+                fields: vec![("body", body)],
+            };
+            filter.check(&mut conn, &submission).await.unwrap()
+        };
+
+        assert!(matches!(check().await, Verdict::Reject { .. }));
+
+        // Deleting it and posting it again is fine.
+        store
+            .article()
+            .delete_article(alice, "original")
+            .await
+            .unwrap();
+        assert_eq!(check().await, Verdict::Allow);
+    }
+}
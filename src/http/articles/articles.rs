@@ -1,11 +1,14 @@
-use axum::extract::{Path, State};
-use axum::routing::{get, post};
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::routing::{get, post, put};
 use axum::{Json, Router};
 
 use crate::http::extractor::{AuthUser, MaybeAuthUser};
 use crate::http::{ApiContext, Result};
-use crate::models::article::{Article, CreateArticle, UpdateArticle};
+use crate::models::article::{Article, CreateArticle, Tag, UpdateArticle};
+use crate::models::view::{ArticleStats, ArticleStatsQuery, Viewer};
 
+use crate::http::articles::coauthors::router as coauthors_router;
 use crate::http::articles::comments::router as comments_router;
 use crate::http::articles::listing;
 
@@ -20,6 +23,8 @@ pub fn router() -> Router<ApiContext> {
         )
         // `feed_articles` could be private technically, but meh
         .route("/api/articles/feed", get(listing::feed_articles))
+        .route("/api/articles/search", get(listing::search_articles))
+        .route("/api/articles/trending", get(listing::trending_articles))
         .route(
             "/api/articles/:slug",
             get(get_article).put(update_article).delete(delete_article),
@@ -28,10 +33,36 @@ pub fn router() -> Router<ApiContext> {
             "/api/articles/:slug/favorite",
             post(favorite_article).delete(unfavorite_article),
         )
+        .route(
+            "/api/articles/:slug/bookmark",
+            post(bookmark_article).delete(unbookmark_article),
+        )
+        .route(
+            "/api/articles/:slug/pin",
+            post(pin_article).delete(unpin_article),
+        )
+        .route(
+            "/api/articles/:slug/reactions/:kind",
+            put(add_reaction).delete(remove_reaction),
+        )
+        .route(
+            "/api/articles/:slug/related",
+            get(listing::related_articles),
+        )
+        .route("/api/articles/:slug/stats", get(article_stats))
+        .route("/api/articles/:slug/restore", post(restore_article))
         // This route isn't technically grouped with articles but it makes sense to include it
         // here since it touches the `article` table.
         .route("/api/tags", get(get_tags))
+        // Same goes for this one.
+        .route("/api/user/bookmarks", get(listing::bookmarked_articles))
+        .route("/api/user/trash", get(listing::trashed_articles))
+        .route(
+            "/api/tags/:tag/follow",
+            post(follow_tag).delete(unfollow_tag),
+        )
         .merge(comments_router())
+        .merge(coauthors_router())
 }
 
 #[derive(serde::Deserialize, serde::Serialize)]
@@ -45,18 +76,30 @@ struct TagsBody {
     tags: Vec<String>,
 }
 
+#[derive(serde::Serialize)]
+struct TagBody {
+    tag: Tag,
+}
+
 // https://realworld-docs.netlify.app/docs/specs/backend-specs/endpoints#create-article
+//
+// Returns `202 Accepted` instead if the content filters held the article for moderation.
 async fn create_article(
     auth_user: AuthUser,
     ctx: State<ApiContext>,
     Json(req): Json<ArticleBody<CreateArticle>>,
-) -> Result<Json<ArticleBody>> {
+) -> Result<(StatusCode, Json<ArticleBody>)> {
     let article = ctx
         .store
         .article()
         .create_article(auth_user.user_id, req.article)
         .await?;
-    Ok(Json(ArticleBody { article }))
+    Ok((
+        article.status(),
+        Json(ArticleBody {
+            article: article.value,
+        }),
+    ))
 }
 
 // https://realworld-docs.netlify.app/docs/specs/backend-specs/endpoints#update-article
@@ -65,13 +108,18 @@ async fn update_article(
     ctx: State<ApiContext>,
     Path(slug): Path<String>,
     Json(req): Json<ArticleBody<UpdateArticle>>,
-) -> Result<Json<ArticleBody>> {
+) -> Result<(StatusCode, Json<ArticleBody>)> {
     let article = ctx
         .store
         .article()
         .update_article(auth_user.user_id, &slug, req.article)
         .await?;
-    Ok(Json(ArticleBody { article }))
+    Ok((
+        article.status(),
+        Json(ArticleBody {
+            article: article.value,
+        }),
+    ))
 }
 
 // https://realworld-docs.netlify.app/docs/specs/backend-specs/endpoints#delete-article
@@ -91,13 +139,48 @@ async fn get_article(
     // The spec states "no authentication required" but should probably state
     // "authentication optional" because we still need to check if the user is following the author.
     maybe_auth_user: MaybeAuthUser,
+    viewer: Viewer,
+    ctx: State<ApiContext>,
+    Path(slug): Path<String>,
+) -> Result<Json<ArticleBody>> {
+    let article = ctx
+        .store
+        .article()
+        .get_article(maybe_auth_user.user_id(), &slug, viewer)
+        .await?;
+    Ok(Json(ArticleBody { article }))
+}
+
+#[derive(serde::Serialize)]
+struct StatsBody {
+    stats: ArticleStats,
+}
+
+// Not in the Realworld spec. Only the article's authors can see this.
+async fn article_stats(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Path(slug): Path<String>,
+    Query(query): Query<ArticleStatsQuery>,
+) -> Result<Json<StatsBody>> {
+    let stats = ctx
+        .store
+        .view()
+        .article_stats(auth_user.user_id, &slug, query)
+        .await?;
+    Ok(Json(StatsBody { stats }))
+}
+
+// Not in the Realworld spec. `DELETE /api/articles/:slug` only moves an article to the trash.
+async fn restore_article(
+    auth_user: AuthUser,
     ctx: State<ApiContext>,
     Path(slug): Path<String>,
 ) -> Result<Json<ArticleBody>> {
     let article = ctx
         .store
         .article()
-        .get_article(maybe_auth_user.user_id(), &slug)
+        .restore_article(auth_user.user_id, &slug)
         .await?;
     Ok(Json(ArticleBody { article }))
 }
@@ -130,10 +213,245 @@ async fn unfavorite_article(
     Ok(Json(ArticleBody { article }))
 }
 
+// Not in the Realworld spec. Bookmarks are like favorites, but private.
+async fn bookmark_article(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Path(slug): Path<String>,
+) -> Result<Json<ArticleBody>> {
+    let article = ctx
+        .store
+        .article()
+        .bookmark_article(auth_user.user_id, &slug)
+        .await?;
+    Ok(Json(ArticleBody { article }))
+}
+
+// Not in the Realworld spec.
+async fn unbookmark_article(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Path(slug): Path<String>,
+) -> Result<Json<ArticleBody>> {
+    let article = ctx
+        .store
+        .article()
+        .unbookmark_article(auth_user.user_id, &slug)
+        .await?;
+    Ok(Json(ArticleBody { article }))
+}
+
+// Not in the Realworld spec. Pinned articles come first on the owner's profile.
+async fn pin_article(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Path(slug): Path<String>,
+) -> Result<Json<ArticleBody>> {
+    let article = ctx
+        .store
+        .article()
+        .pin_article(auth_user.user_id, &slug)
+        .await?;
+    Ok(Json(ArticleBody { article }))
+}
+
+// Not in the Realworld spec.
+async fn unpin_article(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Path(slug): Path<String>,
+) -> Result<Json<ArticleBody>> {
+    let article = ctx
+        .store
+        .article()
+        .unpin_article(auth_user.user_id, &slug)
+        .await?;
+    Ok(Json(ArticleBody { article }))
+}
+
+// Not in the Realworld spec. `PUT` rather than `POST` like favorites, since it's keyed by `kind`.
+async fn add_reaction(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Path((slug, kind)): Path<(String, String)>,
+) -> Result<Json<ArticleBody>> {
+    let article = ctx
+        .store
+        .article()
+        .add_reaction(auth_user.user_id, &slug, &kind)
+        .await?;
+    Ok(Json(ArticleBody { article }))
+}
+
+// Not in the Realworld spec.
+async fn remove_reaction(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Path((slug, kind)): Path<(String, String)>,
+) -> Result<Json<ArticleBody>> {
+    let article = ctx
+        .store
+        .article()
+        .remove_reaction(auth_user.user_id, &slug, &kind)
+        .await?;
+    Ok(Json(ArticleBody { article }))
+}
+
 // https://realworld-docs.netlify.app/docs/specs/backend-specs/endpoints#get-tags
 async fn get_tags(ctx: State<ApiContext>) -> Result<Json<TagsBody>> {
     let tags = ctx.store.article().get_tags().await?;
     Ok(Json(TagsBody { tags }))
 }
 
+// Not in the Realworld spec. Articles with the tag will show up in the user's feed.
+async fn follow_tag(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Path(tag): Path<String>,
+) -> Result<Json<TagBody>> {
+    let tag = ctx
+        .store
+        .article()
+        .follow_tag(auth_user.user_id, &tag)
+        .await?;
+    Ok(Json(TagBody { tag }))
+}
+
+// Not in the Realworld spec.
+async fn unfollow_tag(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Path(tag): Path<String>,
+) -> Result<Json<TagBody>> {
+    let tag = ctx
+        .store
+        .article()
+        .unfollow_tag(auth_user.user_id, &tag)
+        .await?;
+    Ok(Json(TagBody { tag }))
+}
+
 // End handler functions.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::moderation::{ModerationAction, NewReport, ReportTarget, Resolution};
+    use crate::models::testing::{config, create_article, create_moderator, create_user, store};
+    use crate::models::{Store, StoreTrait};
+    use axum::body::Body;
+    use axum::http::{Method, Request};
+    use serde_json::{json, Value};
+    use sqlx::PgPool;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    /// Send a request as `user_id` and return the status and the body, if it's JSON.
+    async fn send(store: &Store, method: Method, uri: &str, user_id: Uuid) -> (StatusCode, Value) {
+        let app = router().with_state(ApiContext {
+            store: Arc::new(store.clone()),
+            config: store.config.clone(),
+        });
+        let jwt = AuthUser { user_id }.to_jwt(&store.config.hmac_key);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .header("Authorization", format!("Token {jwt}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[sqlx::test]
+    async fn restore_hidden_article(pool: PgPool) {
+        let store = store(pool.clone(), config());
+        let alice = create_user(&pool, "alice").await;
+        let bob = create_user(&pool, "bob").await;
+        let moderator = create_moderator(&pool, "moderator").await;
+        create_article(&store, alice, "Trashed").await;
+        create_article(&store, alice, "Hidden").await;
+
+        store
+            .article()
+            .delete_article(alice, "trashed")
+            .await
+            .unwrap();
+
+        let report = store
+            .moderation()
+            .create_report(
+                bob,
+                NewReport {
+                    target: ReportTarget::Article("hidden".into()),
+                    reason: "spam".into(),
+                },
+            )
+            .await
+            .unwrap();
+        store
+            .moderation()
+            .resolve_report(
+                moderator,
+                report.id,
+                Resolution {
+                    action: ModerationAction::Hide,
+                    note: None,
+                },
+            )
+            .await
+            .unwrap();
+
+        let restore = |slug: &str| {
+            let uri = format!("/api/articles/{slug}/restore");
+            let store = store.clone();
+            async move { send(&store, Method::POST, &uri, alice).await.0 }
+        };
+
+        assert_eq!(restore("trashed").await, StatusCode::OK);
+        // Only a moderator can bring it back, by reopening the report.
+        assert_eq!(restore("hidden").await, StatusCode::FORBIDDEN);
+        assert_eq!(restore("never-existed").await, StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn reactions(pool: PgPool) {
+        let store = store(pool.clone(), config());
+        let alice = create_user(&pool, "alice").await;
+        let bob = create_user(&pool, "bob").await;
+        create_article(&store, alice, "Reactive").await;
+
+        let uri = "/api/articles/reactive/reactions/tada";
+        send(&store, Method::PUT, uri, alice).await;
+        // Twice is the same as once.
+        send(&store, Method::PUT, uri, bob).await;
+        let (status, body) = send(&store, Method::PUT, uri, bob).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["article"]["reactions"], json!({ "tada": 2 }));
+        assert_eq!(body["article"]["userReactions"], json!(["tada"]));
+
+        let (status, body) = send(
+            &store,
+            Method::PUT,
+            "/api/articles/reactive/reactions/angry",
+            bob,
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(body["errors"]["kind"].is_array());
+
+        let (status, body) = send(&store, Method::DELETE, uri, bob).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["article"]["reactions"], json!({ "tada": 1 }));
+        assert_eq!(body["article"]["userReactions"], json!([]));
+    }
+}
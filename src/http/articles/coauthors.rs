@@ -0,0 +1,109 @@
+use crate::http::extractor::AuthUser;
+use crate::http::ApiContext;
+use crate::http::Result;
+use crate::models::coauthor::{Coauthor, Invitation};
+use axum::extract::{Path, State};
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+
+// None of these are in the Realworld spec.
+pub(crate) fn router() -> Router<ApiContext> {
+    Router::new()
+        .route(
+            "/api/articles/:slug/authors",
+            get(list_authors).post(invite_author),
+        )
+        .route(
+            "/api/articles/:slug/authors/accept",
+            post(accept_invitation),
+        )
+        .route(
+            "/api/articles/:slug/authors/:username",
+            delete(remove_author),
+        )
+        // Same deal as `/api/tags`: not technically an article route, but it's about articles.
+        .route("/api/user/invitations", get(pending_invitations))
+}
+
+#[derive(serde::Serialize)]
+struct AuthorsBody {
+    authors: Vec<Coauthor>,
+}
+
+#[derive(serde::Deserialize)]
+struct InviteBody {
+    author: InviteAuthor,
+}
+
+#[derive(serde::Deserialize)]
+struct InviteAuthor {
+    username: String,
+}
+
+#[derive(serde::Serialize)]
+struct InvitationsBody {
+    invitations: Vec<Invitation>,
+}
+
+async fn list_authors(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Path(slug): Path<String>,
+) -> Result<Json<AuthorsBody>> {
+    let authors = ctx
+        .store
+        .coauthor()
+        .list_authors(auth_user.user_id, &slug)
+        .await?;
+    Ok(Json(AuthorsBody { authors }))
+}
+
+async fn invite_author(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Path(slug): Path<String>,
+    Json(req): Json<InviteBody>,
+) -> Result<Json<AuthorsBody>> {
+    let authors = ctx
+        .store
+        .coauthor()
+        .invite_author(auth_user.user_id, &slug, &req.author.username)
+        .await?;
+    Ok(Json(AuthorsBody { authors }))
+}
+
+async fn accept_invitation(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Path(slug): Path<String>,
+) -> Result<Json<AuthorsBody>> {
+    let authors = ctx
+        .store
+        .coauthor()
+        .accept_invitation(auth_user.user_id, &slug)
+        .await?;
+    Ok(Json(AuthorsBody { authors }))
+}
+
+async fn remove_author(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Path((slug, username)): Path<(String, String)>,
+) -> Result<()> {
+    ctx.store
+        .coauthor()
+        .remove_author(auth_user.user_id, &slug, &username)
+        .await
+}
+
+async fn pending_invitations(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+) -> Result<Json<InvitationsBody>> {
+    let invitations = ctx
+        .store
+        .coauthor()
+        .pending_invitations(auth_user.user_id)
+        .await?;
+    Ok(Json(InvitationsBody { invitations }))
+}
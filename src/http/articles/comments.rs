@@ -1,9 +1,10 @@
 use crate::http::extractor::{AuthUser, MaybeAuthUser};
 use crate::http::ApiContext;
 use crate::http::Result;
-use crate::models::comment::Comment;
-use axum::extract::{Path, State};
-use axum::routing::{delete, get};
+use crate::models::comment::{Comment, CommentsQuery, RepliesQuery};
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::routing::{get, post, put};
 use axum::{Json, Router};
 
 pub(crate) fn router() -> Router<ApiContext> {
@@ -15,7 +16,21 @@ pub(crate) fn router() -> Router<ApiContext> {
         )
         .route(
             "/api/articles/:slug/comments/:comment_id",
-            delete(delete_comment),
+            put(update_comment).delete(delete_comment),
+        )
+        // Not in the Realworld spec.
+        .route(
+            "/api/articles/:slug/comments/:comment_id/replies",
+            get(get_replies).post(add_reply),
+        )
+        // Only for the article's authors.
+        .route(
+            "/api/articles/:slug/comments/:comment_id/approve",
+            post(approve_comment),
+        )
+        .route(
+            "/api/articles/:slug/comments/:comment_id/hide",
+            post(hide_comment).delete(unhide_comment),
         )
 }
 
@@ -25,10 +40,26 @@ struct CommentBody<T = Comment> {
 }
 
 #[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
 struct MultipleCommentsBody {
     comments: Vec<Comment>,
+    // Not in the Realworld spec, like the rest of these. This is every comment on the article,
+    // not just the ones in this page.
+    comments_count: i64,
+    // Pass this back as `?cursor=` for the next page. `null` if there isn't one, or if
+    // the comments are sorted by `top`, which only supports `offset`.
+    next_cursor: Option<String>,
 }
 
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RepliesBody {
+    comments: Vec<Comment>,
+    // How many are left after this page; pass a higher `?offset=` to get them.
+    more_replies: i64,
+}
+
+// Also used for `update_comment()`, as the body is the only thing that can be changed.
 #[derive(serde::Deserialize)]
 struct AddComment {
     body: String,
@@ -39,30 +70,106 @@ async fn get_article_comments(
     maybe_auth_user: MaybeAuthUser,
     ctx: State<ApiContext>,
     Path(slug): Path<String>,
+    Query(query): Query<CommentsQuery>,
 ) -> Result<Json<MultipleCommentsBody>> {
     // With this, we can return 404 if the article slug was not found.
-    let comments = ctx
+    let page = ctx
+        .store
+        .comment()
+        .get_article_comments(maybe_auth_user.user_id(), &slug, query)
+        .await?;
+
+    Ok(Json(MultipleCommentsBody {
+        comments: page.comments,
+        comments_count: page.total_count,
+        next_cursor: page.next_cursor,
+    }))
+}
+
+// Not in the Realworld spec. The rest of a thread, when `get_article_comments()` cut it short.
+async fn get_replies(
+    maybe_auth_user: MaybeAuthUser,
+    ctx: State<ApiContext>,
+    Path((slug, comment_id)): Path<(String, i64)>,
+    Query(query): Query<RepliesQuery>,
+) -> Result<Json<RepliesBody>> {
+    let page = ctx
         .store
         .comment()
-        .get_article_comments(maybe_auth_user.user_id(), &slug)
+        .get_replies(maybe_auth_user.user_id(), &slug, comment_id, query)
         .await?;
 
-    Ok(Json(MultipleCommentsBody { comments }))
+    Ok(Json(RepliesBody {
+        comments: page.comments,
+        more_replies: page.more_replies,
+    }))
 }
 
 // https://realworld-docs.netlify.app/docs/specs/backend-specs/endpoints#add-comments-to-an-article
+//
+// Returns `202 Accepted` instead if the content filters held the comment for moderation.
 async fn add_comment(
     auth_user: AuthUser,
     ctx: State<ApiContext>,
     Path(slug): Path<String>,
     req: Json<CommentBody<AddComment>>,
-) -> Result<Json<CommentBody>> {
+) -> Result<(StatusCode, Json<CommentBody>)> {
     let comment = ctx
         .store
         .comment()
-        .create_comment(auth_user.user_id, &slug, &req.comment.body)
+        .create_comment(auth_user.user_id, &slug, None, &req.comment.body)
         .await?;
-    Ok(Json(CommentBody { comment }))
+    Ok((
+        comment.status(),
+        Json(CommentBody {
+            comment: comment.value,
+        }),
+    ))
+}
+
+// Same as `add_comment()`, but as a reply to another comment on the article.
+async fn add_reply(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Path((slug, comment_id)): Path<(String, i64)>,
+    req: Json<CommentBody<AddComment>>,
+) -> Result<(StatusCode, Json<CommentBody>)> {
+    let comment = ctx
+        .store
+        .comment()
+        .create_comment(
+            auth_user.user_id,
+            &slug,
+            Some(comment_id),
+            &req.comment.body,
+        )
+        .await?;
+    Ok((
+        comment.status(),
+        Json(CommentBody {
+            comment: comment.value,
+        }),
+    ))
+}
+
+// Not in the Realworld spec. Only the author can edit a comment.
+async fn update_comment(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Path((slug, comment_id)): Path<(String, i64)>,
+    req: Json<CommentBody<AddComment>>,
+) -> Result<(StatusCode, Json<CommentBody>)> {
+    let comment = ctx
+        .store
+        .comment()
+        .update_comment(auth_user.user_id, &slug, comment_id, &req.comment.body)
+        .await?;
+    Ok((
+        comment.status(),
+        Json(CommentBody {
+            comment: comment.value,
+        }),
+    ))
 }
 
 // https://realworld-docs.netlify.app/docs/specs/backend-specs/endpoints#delete-comment
@@ -76,3 +183,36 @@ async fn delete_comment(
         .delete_comment(auth_user.user_id, &slug, comment_id)
         .await
 }
+
+async fn approve_comment(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Path((slug, comment_id)): Path<(String, i64)>,
+) -> Result<()> {
+    ctx.store
+        .comment()
+        .approve_comment(auth_user.user_id, &slug, comment_id)
+        .await
+}
+
+async fn hide_comment(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Path((slug, comment_id)): Path<(String, i64)>,
+) -> Result<()> {
+    ctx.store
+        .comment()
+        .set_comment_hidden(auth_user.user_id, &slug, comment_id, true)
+        .await
+}
+
+async fn unhide_comment(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Path((slug, comment_id)): Path<(String, i64)>,
+) -> Result<()> {
+    ctx.store
+        .comment()
+        .set_comment_hidden(auth_user.user_id, &slug, comment_id, false)
+        .await
+}
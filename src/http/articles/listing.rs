@@ -1,11 +1,14 @@
-use axum::extract::{Query, State};
+use axum::extract::{Path, Query, State};
 use axum::Json;
 
 use crate::http;
-use crate::http::extractor::{AuthUser, MaybeAuthUser};
+use crate::http::extractor::{AuthUser, MaybeAuthUser, MultiQuery};
 use crate::http::ApiContext;
 use crate::models::article::Article;
-use crate::models::listing::{FeedArticlesQuery, ListArticlesQuery};
+use crate::models::listing::{
+    ArticleSearchHit, BookmarkedArticlesQuery, FeedArticlesQuery, ListArticlesQuery,
+    RelatedArticlesQuery, SearchArticlesQuery, TrashQuery, TrashedArticle, TrendingArticlesQuery,
+};
 
 #[derive(serde::Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -33,8 +36,25 @@ pub struct MultipleArticlesBody {
     // don't usually care where they are in the total ordering of things, or if they do
     // then the scrollbar is already an intuitive indication of where they're at.
     //
-    // The Postman collection doesn't test pagination, so as a cop-out I've decided to just
-    // return the count of articles currently being returned, which satisfies the happy-path tests.
+    // The Postman collection doesn't test pagination, so originally I just returned the count of
+    // articles in the page as a cop-out. However, the reference frontend really does use this to
+    // calculate page numbers, so we now return the real total by default.
+    //
+    // If that turns out to be too expensive, it can be switched to an estimate or back to the
+    // count of the current page; see `CountMode`.
+    articles_count: i64,
+
+    // Not in the Realworld spec. Pass either of these back as `?cursor=` to fetch
+    // the page after or before this one. `null` if there isn't one.
+    next_cursor: Option<String>,
+    prev_cursor: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResultsBody {
+    articles: Vec<ArticleSearchHit>,
+    // Unlike `MultipleArticlesBody::articles_count`, this is just the number of hits in the page.
     articles_count: usize,
 }
 
@@ -43,20 +63,21 @@ pub(in crate::http) async fn list_articles(
     // authentication is optional
     maybe_auth_user: MaybeAuthUser,
     ctx: State<ApiContext>,
-    Query(query): Query<ListArticlesQuery>,
+    // `MultiQuery` so `?tag=rust&tag=sql` works.
+    MultiQuery(query): MultiQuery<ListArticlesQuery>,
 ) -> http::Result<Json<MultipleArticlesBody>> {
-    let articles = ctx
+    let page = ctx
         .store
         .listing()
         .article_list(maybe_auth_user.user_id(), query)
         .await?;
 
     Ok(Json(MultipleArticlesBody {
-        // This is probably incorrect but is deliberate and the Postman collection allows it.
-        //
         // See the comment on the field definition for details.
-        articles_count: articles.len(),
-        articles,
+        articles_count: page.total_count.unwrap_or(page.articles.len() as i64),
+        articles: page.articles,
+        next_cursor: page.next_cursor,
+        prev_cursor: page.prev_cursor,
     }))
 }
 
@@ -67,15 +88,126 @@ pub(in crate::http) async fn feed_articles(
     Query(query): Query<FeedArticlesQuery>,
 ) -> http::Result<Json<MultipleArticlesBody>> {
     println!("feed_articles for : {:?}", auth_user.user_id);
-    let articles = ctx
+    let page = ctx
         .store
         .listing()
         .get_feed_articles(auth_user.user_id, query)
         .await?;
     Ok(Json(MultipleArticlesBody {
-        // This is probably incorrect but is deliberate and the Postman collection allows it.
-        //
         // See the comment on the field definition for details.
+        articles_count: page.total_count.unwrap_or(page.articles.len() as i64),
+        articles: page.articles,
+        next_cursor: page.next_cursor,
+        prev_cursor: page.prev_cursor,
+    }))
+}
+
+// Not in the Realworld spec. Ranked by recent favorites and comments instead of recency,
+// see `ListingController::trending_articles()`.
+pub(in crate::http) async fn trending_articles(
+    // authentication is optional
+    maybe_auth_user: MaybeAuthUser,
+    ctx: State<ApiContext>,
+    Query(query): Query<TrendingArticlesQuery>,
+) -> http::Result<Json<MultipleArticlesBody>> {
+    let page = ctx
+        .store
+        .listing()
+        .trending_articles(maybe_auth_user.user_id(), query)
+        .await?;
+
+    Ok(Json(MultipleArticlesBody {
+        // See the comment on the field definition for details.
+        articles_count: page.total_count.unwrap_or(page.articles.len() as i64),
+        articles: page.articles,
+        next_cursor: page.next_cursor,
+        prev_cursor: page.prev_cursor,
+    }))
+}
+
+// Not in the Realworld spec. The current user's private reading list.
+pub(in crate::http) async fn bookmarked_articles(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Query(query): Query<BookmarkedArticlesQuery>,
+) -> http::Result<Json<MultipleArticlesBody>> {
+    let page = ctx
+        .store
+        .listing()
+        .bookmarked_articles(auth_user.user_id, query)
+        .await?;
+
+    Ok(Json(MultipleArticlesBody {
+        // See the comment on the field definition for details.
+        articles_count: page.total_count.unwrap_or(page.articles.len() as i64),
+        articles: page.articles,
+        next_cursor: page.next_cursor,
+        prev_cursor: page.prev_cursor,
+    }))
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashBody {
+    articles: Vec<TrashedArticle>,
+    articles_count: i64,
+}
+
+// Not in the Realworld spec. The current user's deleted articles, which can be restored with
+// `POST /api/articles/:slug/restore` until they're purged.
+pub(in crate::http) async fn trashed_articles(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Query(query): Query<TrashQuery>,
+) -> http::Result<Json<TrashBody>> {
+    let page = ctx
+        .store
+        .listing()
+        .trashed_articles(auth_user.user_id, query)
+        .await?;
+
+    Ok(Json(TrashBody {
+        articles: page.articles,
+        articles_count: page.total_count,
+    }))
+}
+
+// Not in the Realworld spec. See `ListingController::related_articles()` for how these are picked.
+pub(in crate::http) async fn related_articles(
+    // authentication is optional
+    maybe_auth_user: MaybeAuthUser,
+    ctx: State<ApiContext>,
+    Path(slug): Path<String>,
+    Query(query): Query<RelatedArticlesQuery>,
+) -> http::Result<Json<MultipleArticlesBody>> {
+    let articles = ctx
+        .store
+        .listing()
+        .related_articles(maybe_auth_user.user_id(), &slug, query)
+        .await?;
+
+    Ok(Json(MultipleArticlesBody {
+        articles_count: articles.len() as i64,
+        articles,
+        next_cursor: None,
+        prev_cursor: None,
+    }))
+}
+
+// Not in the Realworld spec. Accepts the same filters as `list_articles()`, plus `q`.
+pub(in crate::http) async fn search_articles(
+    // authentication is optional
+    maybe_auth_user: MaybeAuthUser,
+    ctx: State<ApiContext>,
+    MultiQuery(query): MultiQuery<SearchArticlesQuery>,
+) -> http::Result<Json<SearchResultsBody>> {
+    let articles = ctx
+        .store
+        .listing()
+        .search_articles(maybe_auth_user.user_id(), query)
+        .await?;
+
+    Ok(Json(SearchResultsBody {
         articles_count: articles.len(),
         articles,
     }))
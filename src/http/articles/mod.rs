@@ -1,4 +1,7 @@
+// Named after the route prefix rather than `mod.rs`; see the README for why.
+#[allow(clippy::module_inception)]
 mod articles;
+mod coauthors;
 mod comments;
 mod listing;
 pub use articles::router;
@@ -1,14 +1,18 @@
 use crate::http::error::Error;
-use axum::extract::{FromRef, FromRequestParts};
+use axum::extract::{ConnectInfo, FromRef, FromRequestParts};
 use axum::http::request::Parts;
 
 use crate::http::ApiContext;
+use crate::models::view::Viewer;
 use async_trait::async_trait;
-use axum::http::header::AUTHORIZATION;
+use axum::http::header::{AUTHORIZATION, USER_AGENT};
 use axum::http::HeaderValue;
 use hmac::{Hmac, Mac};
 use jwt::{SignWithKey, VerifyWithKey};
 use sha2::Sha384;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::net::{IpAddr, SocketAddr};
 use time::OffsetDateTime;
 use uuid::Uuid;
 
@@ -106,7 +110,8 @@ impl AuthUser {
 
         // Because JWTs are stateless, we don't really have any mechanism here to invalidate them
         // besides expiration. You probably want to add more checks, like ensuring the user ID
-        // exists and has not been deleted/banned/deactivated.
+        // exists and has not been deleted/banned/deactivated. (We now check for suspended users,
+        // but in the `AuthUser` extractor below, since that needs the database.)
         //
         // You could also use the user's password hash as part of the keying material for the HMAC,
         // so changing their password invalidates their existing sessions.
@@ -170,7 +175,20 @@ where
             .get(AUTHORIZATION)
             .ok_or(Error::Unauthorized)?;
 
-        Self::from_authorization(&ctx.config.hmac_key, auth_header)
+        let auth_user = Self::from_authorization(&ctx.config.hmac_key, auth_header)?;
+
+        // Suspended users can still browse, since that doesn't need a token,
+        // but nothing that requires logging in will work for them.
+        //
+        // This is an extra query for every authenticated request, but it's a primary key lookup,
+        // and it's the only way to make a suspension take effect before their token expires.
+        // `MaybeAuthUser` doesn't check, which is why it's only used for reads.
+        ctx.store
+            .moderation()
+            .check_active(auth_user.user_id)
+            .await?;
+
+        Ok(auth_user)
     }
 }
 
@@ -195,3 +213,72 @@ where
         ))
     }
 }
+
+/// Identifies the reader for deduplicating article views.
+///
+/// Logged-in users are identified by their user ID. Everyone else gets a hash of their IP address
+/// and `User-Agent`, which is never stored anywhere; it's only good enough to tell readers apart,
+/// and anyone determined to inflate a view count can always just change them.
+#[async_trait]
+impl<S> FromRequestParts<S> for Viewer
+where
+    S: Send + Sync,
+    ApiContext: FromRef<S>,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(user_id) = MaybeAuthUser::from_request_parts(parts, state)
+            .await?
+            .user_id()
+        {
+            return Ok(Self::User(user_id));
+        }
+
+        let ctx: ApiContext = ApiContext::from_ref(state);
+
+        let forwarded_for = ctx
+            .config
+            .trust_forwarded_for
+            .then(|| parts.headers.get("x-forwarded-for")?.to_str().ok())
+            .flatten()
+            .and_then(|forwarded_for| forwarded_for.split(',').next())
+            .and_then(|addr| addr.trim().parse::<IpAddr>().ok());
+
+        // This is only missing if the router is run without `into_make_service_with_connect_info()`,
+        // like in tests.
+        let peer_addr = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+
+        let mut hasher = DefaultHasher::new();
+        forwarded_for.or(peer_addr).hash(&mut hasher);
+        parts.headers.get(USER_AGENT).hash(&mut hasher);
+
+        Ok(Self::Anonymous(hasher.finish()))
+    }
+}
+
+/// Like `axum::extract::Query` but allows repeated keys, e.g. `?tag=rust&tag=sql`, to be
+/// deserialized into a `Vec`.
+///
+/// `Query` uses `serde_urlencoded`, which only keeps the last value of a repeated key.
+pub struct MultiQuery<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for MultiQuery<T>
+where
+    T: serde::de::DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let query = parts.uri.query().unwrap_or_default();
+
+        serde_html_form::from_str(query)
+            .map(Self)
+            .map_err(|e| Error::unprocessable_entity([("query", e.to_string())]))
+    }
+}
@@ -21,7 +21,11 @@ pub mod types;
 //
 // See `api_router()` below for the recommended order.
 mod articles;
+mod moderation;
+mod notifications;
 mod profiles;
+mod series;
+mod uploads;
 mod users;
 
 pub mod server;
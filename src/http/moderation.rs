@@ -0,0 +1,161 @@
+use axum::extract::{Path, Query, State};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use uuid::Uuid;
+
+use crate::http::extractor::AuthUser;
+use crate::http::{ApiContext, Result};
+use crate::models::moderation::{
+    CommentVersion, LogEntry, LogQuery, ModerationReport, NewReport, Report, ReportsQuery,
+    Resolution,
+};
+
+// None of this is in the Realworld spec.
+//
+// `POST /api/reports` is for everyone; the rest are only for moderators, and return `403` for
+// anyone else. See `ModerationController` for how it all fits together.
+pub(crate) fn router() -> Router<ApiContext> {
+    Router::new()
+        .route("/api/reports", post(create_report))
+        .route("/api/moderation/reports", get(list_reports))
+        .route(
+            "/api/moderation/reports/:report_id/triage",
+            post(triage_report),
+        )
+        .route(
+            "/api/moderation/reports/:report_id/resolve",
+            post(resolve_report),
+        )
+        .route(
+            "/api/moderation/reports/:report_id/reopen",
+            post(reopen_report),
+        )
+        .route("/api/moderation/log", get(moderation_log))
+        .route(
+            "/api/moderation/comments/:comment_id/history",
+            get(comment_history),
+        )
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct ReportBody<T = ModerationReport> {
+    report: T,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MultipleReportsBody {
+    reports: Vec<ModerationReport>,
+    reports_count: i64,
+}
+
+#[derive(serde::Deserialize)]
+struct ResolutionBody {
+    resolution: Resolution,
+}
+
+#[derive(serde::Serialize)]
+struct LogBody {
+    entries: Vec<LogEntry>,
+}
+
+#[derive(serde::Serialize)]
+struct CommentHistoryBody {
+    versions: Vec<CommentVersion>,
+}
+
+async fn create_report(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Json(req): Json<ReportBody<NewReport>>,
+) -> Result<Json<ReportBody<Report>>> {
+    let report = ctx
+        .store
+        .moderation()
+        .create_report(auth_user.user_id, req.report)
+        .await?;
+    Ok(Json(ReportBody { report }))
+}
+
+async fn list_reports(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Query(query): Query<ReportsQuery>,
+) -> Result<Json<MultipleReportsBody>> {
+    let page = ctx
+        .store
+        .moderation()
+        .list_reports(auth_user.user_id, query)
+        .await?;
+
+    Ok(Json(MultipleReportsBody {
+        reports: page.reports,
+        reports_count: page.total_count,
+    }))
+}
+
+async fn triage_report(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Path(report_id): Path<Uuid>,
+) -> Result<Json<ReportBody>> {
+    let report = ctx
+        .store
+        .moderation()
+        .triage_report(auth_user.user_id, report_id)
+        .await?;
+    Ok(Json(ReportBody { report }))
+}
+
+async fn resolve_report(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Path(report_id): Path<Uuid>,
+    Json(req): Json<ResolutionBody>,
+) -> Result<Json<ReportBody>> {
+    let report = ctx
+        .store
+        .moderation()
+        .resolve_report(auth_user.user_id, report_id, req.resolution)
+        .await?;
+    Ok(Json(ReportBody { report }))
+}
+
+async fn reopen_report(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Path(report_id): Path<Uuid>,
+) -> Result<Json<ReportBody>> {
+    let report = ctx
+        .store
+        .moderation()
+        .reopen_report(auth_user.user_id, report_id)
+        .await?;
+    Ok(Json(ReportBody { report }))
+}
+
+async fn moderation_log(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Query(query): Query<LogQuery>,
+) -> Result<Json<LogBody>> {
+    let entries = ctx
+        .store
+        .moderation()
+        .moderation_log(auth_user.user_id, query)
+        .await?;
+    Ok(Json(LogBody { entries }))
+}
+
+async fn comment_history(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Path(comment_id): Path<i64>,
+) -> Result<Json<CommentHistoryBody>> {
+    let versions = ctx
+        .store
+        .moderation()
+        .comment_history(auth_user.user_id, comment_id)
+        .await?;
+    Ok(Json(CommentHistoryBody { versions }))
+}
@@ -0,0 +1,57 @@
+use axum::extract::{Path, Query, State};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+
+use crate::http::extractor::AuthUser;
+use crate::http::{ApiContext, Result};
+use crate::models::notification::{Notification, NotificationsQuery};
+
+// None of this is in the Realworld spec. See `notification.rs` for where notifications come from.
+pub(crate) fn router() -> Router<ApiContext> {
+    Router::new()
+        .route("/api/notifications", get(list_notifications))
+        .route("/api/notifications/read", post(mark_all_read))
+        .route("/api/notifications/:notification_id/read", post(mark_read))
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MultipleNotificationsBody {
+    notifications: Vec<Notification>,
+    unread_count: i64,
+}
+
+async fn list_notifications(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Query(query): Query<NotificationsQuery>,
+) -> Result<Json<MultipleNotificationsBody>> {
+    let page = ctx
+        .store
+        .notification()
+        .list_notifications(auth_user.user_id, query)
+        .await?;
+
+    Ok(Json(MultipleNotificationsBody {
+        notifications: page.notifications,
+        unread_count: page.unread_count,
+    }))
+}
+
+async fn mark_read(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Path(notification_id): Path<i64>,
+) -> Result<()> {
+    ctx.store
+        .notification()
+        .mark_read(auth_user.user_id, notification_id)
+        .await
+}
+
+async fn mark_all_read(auth_user: AuthUser, ctx: State<ApiContext>) -> Result<()> {
+    ctx.store
+        .notification()
+        .mark_all_read(auth_user.user_id)
+        .await
+}
@@ -1,7 +1,7 @@
 use crate::http::extractor::{AuthUser, MaybeAuthUser};
 use crate::http::ApiContext;
 use crate::http::Result;
-use crate::models::profile::{DynProfileCtrl, Profile};
+use crate::models::profile::{DynProfileCtrl, PinnedArticle, Profile};
 use axum::{
     extract::{FromRef, Path, State},
     routing::{get, post},
@@ -21,8 +21,18 @@ pub(crate) fn router() -> Router<ApiContext> {
 // https://realworld-docs.netlify.app/docs/specs/backend-specs/api-response-format#profile
 #[derive(serde::Serialize)]
 #[serde(rename_all = "camelCase")]
-struct ProfileBody {
+struct ProfileBody<T = Profile> {
+    profile: T,
+}
+
+// Not in the Realworld spec. Only `GET /api/profiles/:username` includes these, since following
+// or unfollowing someone doesn't change them.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProfileWithPinnedArticles {
+    #[serde(flatten)]
     profile: Profile,
+    pinned_articles: Vec<PinnedArticle>,
 }
 
 impl FromRef<ApiContext> for DynProfileCtrl {
@@ -41,13 +51,19 @@ async fn get_user_profile(
     maybe_auth_user: MaybeAuthUser, 
     profile_controller: State<DynProfileCtrl>,
     Path(username): Path<String>,
-) -> Result<Json<ProfileBody>> {
+) -> Result<Json<ProfileBody<ProfileWithPinnedArticles>>> {
     let user_id: Option<Uuid> = maybe_auth_user.0.map(|auth_user| auth_user.user_id);
     let profile = profile_controller
         .get_profile_by_id(user_id, &username)
         .await?;
-
-    Ok(Json(ProfileBody { profile }))
+    let pinned_articles = profile_controller.pinned_articles(&username).await?;
+
+    Ok(Json(ProfileBody {
+        profile: ProfileWithPinnedArticles {
+            profile,
+            pinned_articles,
+        },
+    }))
 }
 
 // https://realworld-docs.netlify.app/docs/specs/backend-specs/endpoints#follow-user
@@ -118,6 +134,10 @@ mod tests {
                 .expect_get_profile_by_id()
                 .with(eq(Some(uuid)), eq(username.clone()))
                 .return_once(move |_, _| result);
+            mock_profile_ctrl
+                .expect_pinned_articles()
+                .with(eq(username.clone()))
+                .return_once(|_| Ok(vec![]));
 
             Arc::new(mock_profile_ctrl)
         });
@@ -165,6 +185,7 @@ mod tests {
         // check if {profile: {"username"} } is username.
         let json: Value = serde_json::from_slice(&body).unwrap();
         assert_eq!(json["profile"]["username"], username);
+        assert_eq!(json["profile"]["pinnedArticles"], serde_json::json!([]));
 
         assert_eq!(status, StatusCode::OK);
     }
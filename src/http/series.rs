@@ -0,0 +1,96 @@
+use axum::extract::{Path, Query, State};
+use axum::routing::get;
+use axum::{Json, Router};
+
+use crate::http::extractor::{AuthUser, MaybeAuthUser};
+use crate::http::{ApiContext, Result};
+use crate::models::series::{CreateSeries, ListSeriesQuery, Series, UpdateSeries};
+
+// None of these routes are in the Realworld spec. They're modeled on the article routes.
+pub(crate) fn router() -> Router<ApiContext> {
+    Router::new()
+        .route("/api/series", get(list_series).post(create_series))
+        .route(
+            "/api/series/:slug",
+            get(get_series).put(update_series).delete(delete_series),
+        )
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+// Same trick as `ArticleBody`.
+struct SeriesBody<T = Series> {
+    series: T,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MultipleSeriesBody {
+    // `series` is its own plural, so this is the best I could come up with.
+    series_list: Vec<Series>,
+}
+
+async fn create_series(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Json(req): Json<SeriesBody<CreateSeries>>,
+) -> Result<Json<SeriesBody>> {
+    let series = ctx
+        .store
+        .series()
+        .create_series(auth_user.user_id, req.series)
+        .await?;
+    Ok(Json(SeriesBody { series }))
+}
+
+async fn list_series(
+    // authentication is optional
+    maybe_auth_user: MaybeAuthUser,
+    ctx: State<ApiContext>,
+    Query(query): Query<ListSeriesQuery>,
+) -> Result<Json<MultipleSeriesBody>> {
+    let series_list = ctx
+        .store
+        .series()
+        .list_series(maybe_auth_user.user_id(), query)
+        .await?;
+    Ok(Json(MultipleSeriesBody { series_list }))
+}
+
+async fn get_series(
+    // authentication is optional
+    maybe_auth_user: MaybeAuthUser,
+    ctx: State<ApiContext>,
+    Path(slug): Path<String>,
+) -> Result<Json<SeriesBody>> {
+    let series = ctx
+        .store
+        .series()
+        .get_series(maybe_auth_user.user_id(), &slug)
+        .await?;
+    Ok(Json(SeriesBody { series }))
+}
+
+async fn update_series(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Path(slug): Path<String>,
+    Json(req): Json<SeriesBody<UpdateSeries>>,
+) -> Result<Json<SeriesBody>> {
+    let series = ctx
+        .store
+        .series()
+        .update_series(auth_user.user_id, &slug, req.series)
+        .await?;
+    Ok(Json(SeriesBody { series }))
+}
+
+async fn delete_series(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    Path(slug): Path<String>,
+) -> Result<()> {
+    ctx.store
+        .series()
+        .delete_series(auth_user.user_id, &slug)
+        .await
+}
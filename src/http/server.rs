@@ -1,20 +1,48 @@
 use crate::config::Config;
+use crate::filter::ContentFilters;
 use crate::http::*;
-use crate::models::{DynStore, Store};
+use crate::models::{article, listing, view, DynStore, Store};
 use anyhow::Context;
+use axum::http::header::CACHE_CONTROL;
+use axum::http::HeaderValue;
 use axum::Router;
 use sqlx::PgPool;
 use std::{
     net::{Ipv4Addr, SocketAddr},
     sync::Arc,
+    time::Duration,
 };
+use tower::Layer;
+use tower_http::services::ServeDir;
+use tower_http::set_header::SetResponseHeaderLayer;
 
 pub async fn serve(config: Config, db: PgPool) -> anyhow::Result<()> {
     let port = config.port;
+    let config = Arc::new(config);
+
+    tokio::spawn(listing::refresh_trending(
+        db.clone(),
+        Duration::from_secs(config.trending_refresh_interval),
+    ));
+
+    tokio::spawn(article::purge_trash(
+        db.clone(),
+        config.trash_retention_days,
+    ));
+
+    let filters = ContentFilters::from_config(&config).context("error loading content filters")?;
+
+    let store = Store::new(db.clone(), config.clone(), Arc::new(filters));
+
+    tokio::spawn(view::flush_views(
+        db.clone(),
+        store.views.clone(),
+        Duration::from_secs(config.view_flush_interval),
+    ));
 
     let api_context = ApiContext {
-        config: Arc::new(config),
-        store: Arc::new(Store::new(db.clone())) as DynStore,
+        config,
+        store: Arc::new(store) as DynStore,
     };
 
     let app = api_router(api_context);
@@ -22,7 +50,8 @@ pub async fn serve(config: Config, db: PgPool) -> anyhow::Result<()> {
     // Port is configured in .env
     let addr = SocketAddr::from((Ipv4Addr::UNSPECIFIED, port));
     axum::Server::bind(&addr)
-        .serve(app.into_make_service())
+        // The `Viewer` extractor needs the client's address.
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .context("error running HTTP server")
 }
@@ -33,6 +62,20 @@ fn api_router(api_context: ApiContext) -> Router {
         .merge(users::router())
         .merge(profiles::router())
         .merge(articles::router())
+        .merge(moderation::router())
+        .merge(notifications::router())
+        .merge(series::router())
+        .merge(uploads::router())
+        .merge(users::avatar_router())
+        // Uploaded files are named after their contents so they can be cached forever.
+        .nest_service(
+            "/uploads",
+            SetResponseHeaderLayer::overriding(
+                CACHE_CONTROL,
+                HeaderValue::from_static("public, max-age=31536000, immutable"),
+            )
+            .layer(ServeDir::new(&api_context.config.upload_dir)),
+        )
         // Enables logging. Use `RUST_LOG=tower_http=debug`
         .layer(TraceLayer::new_for_http())
         .with_state(api_context)
@@ -0,0 +1,74 @@
+use axum::extract::{DefaultBodyLimit, Multipart, State};
+use axum::routing::post;
+use axum::{Json, Router};
+
+use crate::http::extractor::AuthUser;
+use crate::http::{ApiContext, Error, Result};
+use crate::models::upload::{Upload, UploadKind};
+
+// Not in the Realworld spec. Articles are still plain Markdown, so the way to put an image in one
+// is to upload it here and then link to the returned `url` from the body.
+//
+// Avatars are uploaded with `POST /api/user/image` instead, which also sets `"user".image`.
+pub(crate) fn router() -> Router<ApiContext> {
+    Router::new()
+        .route("/api/uploads", post(upload_image))
+        // The default limit is 2 MB for the whole body; `read_image()` enforces
+        // `upload_max_bytes` on the image itself instead.
+        .layer(DefaultBodyLimit::disable())
+}
+
+#[derive(serde::Serialize)]
+struct UploadBody {
+    upload: Upload,
+}
+
+async fn upload_image(
+    // Only so random passers-by can't use us as free image hosting.
+    _auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    multipart: Multipart,
+) -> Result<Json<UploadBody>> {
+    let data = read_image(multipart, ctx.config.upload_max_bytes).await?;
+
+    let upload = ctx
+        .store
+        .upload()
+        .upload_image(UploadKind::ArticleImage, data)
+        .await?;
+
+    Ok(Json(UploadBody { upload }))
+}
+
+/// Read the `image` field of a `multipart/form-data` body, ignoring any other fields.
+///
+/// The field's `Content-Type` and filename are ignored too; `UploadController` sniffs the type
+/// from the data itself.
+pub(crate) async fn read_image(mut multipart: Multipart, max_bytes: usize) -> Result<Vec<u8>> {
+    let malformed = |_| Error::unprocessable_entity([("image", "malformed multipart body")]);
+
+    while let Some(mut field) = multipart.next_field().await.map_err(malformed)? {
+        if field.name() != Some("image") {
+            continue;
+        }
+
+        // Read it a chunk at a time so we can bail out as soon as it's too big,
+        // rather than buffering however much the client feels like sending.
+        let mut data = Vec::new();
+
+        while let Some(chunk) = field.chunk().await.map_err(malformed)? {
+            if data.len() + chunk.len() > max_bytes {
+                return Err(Error::unprocessable_entity([(
+                    "image",
+                    format!("can't be larger than {max_bytes} bytes"),
+                )]));
+            }
+
+            data.extend_from_slice(&chunk);
+        }
+
+        return Ok(data);
+    }
+
+    Err(Error::unprocessable_entity([("image", "is required")]))
+}
@@ -5,12 +5,13 @@ use crate::models::StoreTrait;
 use anyhow::Context;
 use argon2::password_hash::SaltString;
 use argon2::{Argon2, PasswordHash};
-use axum::extract::State;
+use axum::extract::{DefaultBodyLimit, Multipart, State};
 use axum::routing::{get, post};
 use axum::{Json, Router};
 
 use crate::http::error::{Error, ResultExt};
 use crate::http::extractor::AuthUser;
+use crate::http::uploads::read_image;
 
 pub(crate) fn router() -> Router<ApiContext> {
     Router::new()
@@ -19,6 +20,14 @@ pub(crate) fn router() -> Router<ApiContext> {
         .route("/api/user", get(get_current_user).put(update_user))
 }
 
+// Not in the Realworld spec, so it's kept out of `router()` to keep the tests below honest.
+pub(crate) fn avatar_router() -> Router<ApiContext> {
+    Router::new()
+        .route("/api/user/image", post(upload_avatar))
+        // See `uploads::router()`.
+        .layer(DefaultBodyLimit::disable())
+}
+
 /// A wrapper type for all requests/responses from these routes.
 #[derive(serde::Serialize, serde::Deserialize)]
 struct UserBody<T> {
@@ -171,6 +180,24 @@ async fn update_user(
     }))
 }
 
+/// Takes a `multipart/form-data` body with the image in an `image` field.
+///
+/// Returns the same thing as `GET /api/user`, with `image` set to the new avatar.
+async fn upload_avatar(
+    auth_user: AuthUser,
+    ctx: State<ApiContext>,
+    multipart: Multipart,
+) -> Result<Json<UserBody<UserWithToken>>> {
+    let data = read_image(multipart, ctx.config.upload_max_bytes).await?;
+
+    ctx.store
+        .upload()
+        .set_avatar(auth_user.user_id, data)
+        .await?;
+
+    get_current_user(auth_user, ctx).await
+}
+
 async fn hash_password(password: String) -> Result<String> {
     // Argon2 hashing is designed to be computationally intensive,
     // so we need to do this on a blocking thread.
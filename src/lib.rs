@@ -9,9 +9,18 @@
 /// [`clap`]: https://github.com/clap-rs/clap/
 pub mod config;
 
+/// Where uploaded images are stored, behind the `BlobStore` trait.
+pub mod blob;
+
+/// The `ContentFilter` pipeline new articles and comments go through before they're stored.
+pub mod filter;
+
 /// Contains the setup code for the API build with Axum.
 ///
 /// The Realworld API routes exist in child modules of this.
 pub mod http;
 
+/// Renders the Markdown in article and comment bodies to sanitized HTML.
+pub mod markdown;
+
 pub mod models;
@@ -0,0 +1,451 @@
+use pulldown_cmark::{Event, LinkType, Options, Parser, Tag, TagEnd, TextMergeStream};
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::OnceLock;
+use time::OffsetDateTime;
+use unicode_segmentation::UnicodeSegmentation;
+
+// Roughly what GitHub Flavored Markdown supports on top of CommonMark.
+const OPTIONS: Options = Options::ENABLE_TABLES
+    .union(Options::ENABLE_FOOTNOTES)
+    .union(Options::ENABLE_STRIKETHROUGH)
+    .union(Options::ENABLE_TASKLISTS)
+    .union(Options::ENABLE_GFM);
+
+/// Render `markdown` to HTML that is safe to drop straight into a page.
+///
+/// Raw HTML is allowed in the input, same as CommonMark, but everything goes through an
+/// allowlist afterwards so `<script>`, event handler attributes, `javascript:` URLs and the like
+/// are stripped out.
+///
+/// Headings get an `id` derived from their text so they can be linked to. These are prefixed
+/// with `user-content-` so user content can't clobber IDs the frontend relies on.
+///
+/// `@username` mentions link to `/api/profiles/:username`, whether or not there's such a user;
+/// the cache in `MarkdownCache` would go stale otherwise.
+pub fn render(markdown: &str) -> String {
+    // Adjacent text is merged first, or a mention could be split across events.
+    let events = with_mention_links(TextMergeStream::new(Parser::new_ext(markdown, OPTIONS)));
+    let events = with_heading_anchors(events.into_iter());
+
+    let mut html = String::with_capacity(markdown.len() * 3 / 2);
+    pulldown_cmark::html::push_html(&mut html, events.into_iter());
+
+    sanitizer().clean(&html).to_string()
+}
+
+/// Roughly how fast an adult reads English prose. It's an overestimate for CJK text, where every
+/// character counts as a word, but it's only meant to be ballpark.
+const WORDS_PER_MINUTE: i32 = 200;
+
+/// Computed from an article body when it's written, and stored alongside it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct TextStats {
+    pub word_count: i32,
+    /// Rounded up, so anything with at least one word takes at least a minute.
+    pub reading_time_minutes: i32,
+}
+
+impl TextStats {
+    pub fn of(markdown: &str) -> Self {
+        // Unicode word boundaries (UAX #29), which handles things like apostrophes and
+        // languages that don't put spaces between words. Punctuation isn't counted.
+        let word_count = plain_text(markdown)
+            .unicode_words()
+            .count()
+            .try_into()
+            .unwrap_or(i32::MAX);
+
+        Self {
+            word_count,
+            reading_time_minutes: (word_count + WORDS_PER_MINUTE - 1) / WORDS_PER_MINUTE,
+        }
+    }
+}
+
+/// The start of the text of `markdown`, cut off at a word boundary so it's at most `max_chars`
+/// characters long, including the `…` that's added if anything was cut off.
+pub fn excerpt(markdown: &str, max_chars: usize) -> String {
+    let text = plain_text(markdown);
+    // Newlines between blocks are just noise in a one-line summary.
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    if text.chars().count() <= max_chars {
+        return text;
+    }
+
+    let budget = max_chars.saturating_sub(1);
+    let mut end = 0;
+    let mut chars = 0;
+
+    for (i, word) in text.split_word_bound_indices() {
+        chars += word.chars().count();
+
+        if chars > budget {
+            break;
+        }
+
+        end = i + word.len();
+    }
+
+    // A single word longer than the whole excerpt, like a URL. Cut it wherever we can,
+    // just not in the middle of a grapheme.
+    if end == 0 {
+        let mut chars = 0;
+
+        for (i, grapheme) in text.grapheme_indices(true) {
+            chars += grapheme.chars().count();
+
+            if chars > budget {
+                break;
+            }
+
+            end = i + grapheme.len();
+        }
+    }
+
+    let mut excerpt = text[..end]
+        .trim_end_matches(|c: char| c.is_whitespace() || c.is_ascii_punctuation())
+        .to_string();
+    excerpt.push('…');
+    excerpt
+}
+
+/// The text of `markdown`, without any of the syntax, HTML or image alt text.
+///
+/// Blocks are separated by newlines.
+pub fn plain_text(markdown: &str) -> String {
+    let mut text = String::with_capacity(markdown.len());
+    let mut image_depth = 0;
+
+    for event in Parser::new_ext(markdown, OPTIONS) {
+        match event {
+            Event::Start(Tag::Image { .. }) => image_depth += 1,
+            Event::End(TagEnd::Image) => image_depth -= 1,
+            _ if image_depth > 0 => (),
+            Event::Text(t) | Event::Code(t) => text.push_str(&t),
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            Event::End(
+                TagEnd::Paragraph
+                | TagEnd::Heading(_)
+                | TagEnd::Item
+                | TagEnd::CodeBlock
+                | TagEnd::TableCell
+                | TagEnd::BlockQuote(_),
+            ) => text.push('\n'),
+            _ => (),
+        }
+    }
+
+    text
+}
+
+/// The usernames mentioned in `markdown` as `@username`, in order and possibly repeated.
+///
+/// Mentions in code, links and raw HTML don't count, same as in `render()`.
+pub fn mentions(markdown: &str) -> Vec<String> {
+    mentionable_text(TextMergeStream::new(Parser::new_ext(markdown, OPTIONS)))
+        .filter_map(|(event, mentionable)| match event {
+            Event::Text(text) if mentionable => Some(
+                find_mentions(&text)
+                    .map(|(_, username)| username.to_string())
+                    .collect::<Vec<_>>(),
+            ),
+            _ => None,
+        })
+        .flatten()
+        .collect()
+}
+
+/// Caches rendered article bodies so `get_article()` and friends don't re-render on every request.
+///
+/// Entries are keyed by slug and `updated_at`: the `set_updated_at` trigger bumps the latter
+/// whenever the row changes, so an edited article simply misses the cache and the stale entry
+/// ages out on its own.
+pub struct MarkdownCache {
+    cache: quick_cache::sync::Cache<(String, OffsetDateTime), String>,
+}
+
+impl MarkdownCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            cache: quick_cache::sync::Cache::new(capacity),
+        }
+    }
+
+    pub fn render(&self, slug: &str, updated_at: OffsetDateTime, markdown: &str) -> String {
+        let key = (slug.to_owned(), updated_at);
+
+        if let Some(html) = self.cache.get(&key) {
+            return html;
+        }
+
+        let html = render(markdown);
+        self.cache.insert(key, html.clone());
+        html
+    }
+}
+
+fn with_mention_links<'a>(events: impl Iterator<Item = Event<'a>>) -> Vec<Event<'a>> {
+    let mut out = Vec::new();
+
+    for (event, mentionable) in mentionable_text(events) {
+        let Event::Text(text) = &event else {
+            out.push(event);
+            continue;
+        };
+
+        let mut rest = 0;
+
+        for (range, username) in find_mentions(text).filter(|_| mentionable) {
+            if range.start > rest {
+                out.push(Event::Text(text[rest..range.start].to_string().into()));
+            }
+
+            out.extend([
+                Event::Start(Tag::Link {
+                    link_type: LinkType::Inline,
+                    dest_url: format!("/api/profiles/{username}").into(),
+                    title: "".into(),
+                    id: "".into(),
+                }),
+                Event::Text(text[range.clone()].to_string().into()),
+                Event::End(TagEnd::Link),
+            ]);
+
+            rest = range.end;
+        }
+
+        match rest {
+            0 => out.push(event),
+            rest if rest < text.len() => out.push(Event::Text(text[rest..].to_string().into())),
+            _ => (),
+        }
+    }
+
+    out
+}
+
+/// Pairs each event with whether it's somewhere a mention could be, i.e. not inside a link,
+/// image or code block. Inline code and raw HTML are separate events, so they're never `Text`.
+fn mentionable_text<'a>(
+    events: impl Iterator<Item = Event<'a>>,
+) -> impl Iterator<Item = (Event<'a>, bool)> {
+    events.scan(0, |depth, event| {
+        match event {
+            Event::Start(Tag::Link { .. } | Tag::Image { .. } | Tag::CodeBlock(_)) => *depth += 1,
+            Event::End(TagEnd::Link | TagEnd::Image | TagEnd::CodeBlock) => *depth -= 1,
+            _ => (),
+        }
+
+        Some((event, *depth == 0))
+    })
+}
+
+/// Where the `@username` mentions are in `text`, and the usernames without the `@`.
+///
+/// Usernames aren't restricted, but mentions are: letters, digits, `_` and `-` only.
+fn find_mentions(text: &str) -> impl Iterator<Item = (Range<usize>, &str)> {
+    static MENTION: OnceLock<Regex> = OnceLock::new();
+
+    MENTION
+        .get_or_init(|| Regex::new(r"@([\w-]+)").unwrap())
+        .captures_iter(text)
+        .filter_map(move |captures| {
+            let mention = captures.get(0)?;
+
+            // Not an email address, or a path like `example.com/@someone`.
+            let preceding = text[..mention.start()].chars().next_back();
+            if preceding.is_some_and(|c| c.is_alphanumeric() || "_-@./".contains(c)) {
+                return None;
+            }
+
+            Some((mention.range(), captures.get(1)?.as_str()))
+        })
+}
+
+fn with_heading_anchors<'a>(events: impl Iterator<Item = Event<'a>>) -> Vec<Event<'a>> {
+    let mut out = Vec::new();
+    let mut seen = HashMap::new();
+    // The index of the `Start(Heading)` event we're inside, and its text so far.
+    let mut heading: Option<(usize, String)> = None;
+
+    for event in events {
+        match (&event, &mut heading) {
+            (Event::Start(Tag::Heading { .. }), _) => heading = Some((out.len(), String::new())),
+            (Event::Text(text) | Event::Code(text), Some((_, heading_text))) => {
+                heading_text.push_str(text)
+            }
+            (Event::End(TagEnd::Heading(_)), Some((start, text))) => {
+                if let Event::Start(Tag::Heading { id, .. }) = &mut out[*start] {
+                    *id = Some(unique_anchor(text, &mut seen).into());
+                }
+                heading = None;
+            }
+            _ => (),
+        }
+
+        out.push(event);
+    }
+
+    out
+}
+
+/// Same scheme as GitHub: lowercase, spaces become hyphens, other punctuation is dropped,
+/// and repeated headings get a `-1`, `-2`, ... suffix.
+fn unique_anchor(text: &str, seen: &mut HashMap<String, usize>) -> String {
+    let anchor: String = text
+        .trim()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' | '-' => Some('-'),
+            '_' => Some('_'),
+            c if c.is_alphanumeric() => Some(c),
+            _ => None,
+        })
+        .flat_map(char::to_lowercase)
+        .collect();
+
+    let count = seen.entry(anchor.clone()).or_insert(0);
+    let unique = match *count {
+        0 => anchor,
+        n => format!("{anchor}-{n}"),
+    };
+    *count += 1;
+
+    unique
+}
+
+fn sanitizer() -> &'static ammonia::Builder<'static> {
+    static SANITIZER: OnceLock<ammonia::Builder<'static>> = OnceLock::new();
+
+    SANITIZER.get_or_init(|| {
+        let mut builder = ammonia::Builder::default();
+
+        builder
+            .add_tags(["input"])
+            .add_tag_attributes("input", ["type", "checked", "disabled"])
+            // `pulldown-cmark` puts the fence info string here, e.g. `language-rust`,
+            // which frontends use for syntax highlighting.
+            .add_tag_attributes("code", ["class"])
+            .id_prefix(Some("user-content-"))
+            // Links are user-generated content, so don't lend them our search ranking.
+            .link_rel(Some("noopener noreferrer nofollow ugc"))
+            .attribute_filter(|element, attribute, value| match (element, attribute) {
+                // Task list items are the only reason to allow `<input>` at all.
+                ("input", "type") if value != "checkbox" => None,
+                ("code", "class") if !value.starts_with("language-") => None,
+                _ => Some(Cow::Borrowed(value)),
+            });
+
+        for heading in ["h1", "h2", "h3", "h4", "h5", "h6"] {
+            builder.add_tag_attributes(heading, ["id"]);
+        }
+
+        builder
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_scripts_and_handlers() {
+        let html = render(
+            "hello <script>alert(1)</script><img src=x onerror=alert(1)> [x](javascript:alert(1))",
+        );
+
+        assert!(!html.contains("<script"), "{html}");
+        assert!(!html.contains("onerror"), "{html}");
+        assert!(!html.contains("javascript:"), "{html}");
+    }
+
+    #[test]
+    fn heading_anchors() {
+        let html = render("# Getting Started\n\n## `async` & You\n\n# Getting Started");
+
+        assert!(
+            html.contains(r#"<h1 id="user-content-getting-started">"#),
+            "{html}"
+        );
+        assert!(
+            html.contains(r#"<h2 id="user-content-async--you">"#),
+            "{html}"
+        );
+        assert!(
+            html.contains(r#"<h1 id="user-content-getting-started-1">"#),
+            "{html}"
+        );
+    }
+
+    #[test]
+    fn gfm_extensions() {
+        let html = render(
+            "| a | b |\n|---|---|\n| 1 | 2 |\n\n~~gone~~\n\n- [x] done\n- [ ] todo\n\n```rust\nfn main() {}\n```",
+        );
+
+        assert!(html.contains("<table>"), "{html}");
+        assert!(html.contains("<del>gone</del>"), "{html}");
+        assert!(html.contains(r#"type="checkbox""#), "{html}");
+        assert!(html.contains(r#"<code class="language-rust">"#), "{html}");
+    }
+
+    #[test]
+    fn text_stats_ignore_markup() {
+        let stats = TextStats::of(
+            "# Title\n\nIt's **bold** and [a link](https://example.com/some/long/path).\n\n![alt text](x.png)",
+        );
+        assert_eq!(stats.word_count, 6);
+        assert_eq!(stats.reading_time_minutes, 1);
+
+        // No spaces between words, but each ideograph is its own word.
+        assert_eq!(TextStats::of("日本語").word_count, 3);
+
+        assert_eq!(TextStats::of("").reading_time_minutes, 0);
+        assert_eq!(TextStats::of(&"word ".repeat(401)).reading_time_minutes, 3);
+    }
+
+    #[test]
+    fn excerpts() {
+        assert_eq!(excerpt("Short *and* sweet.", 100), "Short and sweet.");
+        assert_eq!(
+            excerpt("The quick brown fox jumps over the lazy dog.", 20),
+            "The quick brown fox…"
+        );
+        assert_eq!(excerpt("# Heading\n\nbody text", 100), "Heading body text");
+        // Never cuts inside a multi-byte character.
+        assert_eq!(excerpt("ééééééééééé", 5), "éééé…");
+    }
+
+    #[test]
+    fn mentions_link_to_profiles() {
+        let markdown = "Thanks @alice and (@Bob_2)! Not bob@example.com, `@carol` or [@dave](x).";
+
+        assert_eq!(mentions(markdown), ["alice", "Bob_2"]);
+
+        let html = render(markdown);
+        assert!(
+            html.contains(
+                r#"<a href="/api/profiles/alice" rel="noopener noreferrer nofollow ugc">@alice</a>"#
+            ),
+            "{html}"
+        );
+        assert!(html.contains(r#"href="/api/profiles/Bob_2""#), "{html}");
+        assert!(html.contains("bob@example.com"), "{html}");
+        assert!(html.contains("<code>@carol</code>"), "{html}");
+        assert!(!html.contains("/api/profiles/dave"), "{html}");
+    }
+
+    #[test]
+    fn links_are_nofollow() {
+        let html = render("[home](https://example.com)");
+
+        assert!(
+            html.contains(r#"rel="noopener noreferrer nofollow ugc""#),
+            "{html}"
+        );
+    }
+}
@@ -1,18 +1,50 @@
+use crate::config::Config;
+use crate::filter::{ContentFilters, Filtered, Submission, SubmissionKind};
 use crate::http::types::Timestamptz;
 use crate::http::{Error, Result, ResultExt};
+use crate::markdown::{excerpt, MarkdownCache, TextStats};
+use crate::models::coauthor::ArticleAuthor;
+use crate::models::comment::CommentsMode;
+use crate::models::moderation::{hold_content, HeldContent};
+use crate::models::notification::record_mentions;
 use crate::models::profile::Profile;
-use itertools::Itertools;
+use crate::models::series::ArticleSeries;
+use crate::models::view::{ViewBuffer, Viewer};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use sqlx::types::Json;
 use sqlx::PgPool;
+use std::collections::BTreeMap;
+use std::sync::Arc;
 use uuid::Uuid;
 
+/// The length of a generated `description`, about as much as a search engine shows of a page.
+const EXCERPT_MAX_CHARS: usize = 160;
+
 #[derive(Clone)]
 pub struct ArticleController {
     pool: PgPool,
+    config: Arc<Config>,
+    markdown: Arc<MarkdownCache>,
+    views: Arc<ViewBuffer>,
+    filters: Arc<ContentFilters>,
 }
 
 impl ArticleController {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+    pub fn new(
+        pool: PgPool,
+        config: Arc<Config>,
+        markdown: Arc<MarkdownCache>,
+        views: Arc<ViewBuffer>,
+        filters: Arc<ContentFilters>,
+    ) -> Self {
+        Self {
+            pool,
+            config,
+            markdown,
+            views,
+            filters,
+        }
     }
 }
 
@@ -23,12 +55,45 @@ pub struct Article {
     pub title: String,
     pub description: String,
     pub body: String,
+    /// `body` rendered from Markdown and sanitized. Not in the Realworld spec.
+    pub body_html: String,
     pub tag_list: Vec<String>,
     pub created_at: Timestamptz,
     pub updated_at: Timestamptz,
     pub favorited: bool,
+    /// Whether the current user has this in their reading list. Not in the Realworld spec.
+    ///
+    /// Unlike favorites, bookmarks are private, so there's no `bookmarksCount`.
+    pub bookmarked: bool,
+    /// Whether the owner has pinned this to the top of their profile. Not in the Realworld spec.
+    pub pinned: bool,
     pub favorites_count: i64,
+    /// Of the body with the Markdown stripped out. Not in the Realworld spec.
+    pub word_count: i32,
+    /// Not in the Realworld spec.
+    pub reading_time_minutes: i32,
+    /// How many times the article has been read, give or take. Not in the Realworld spec.
+    ///
+    /// Repeat views by the same reader are only counted once in a while, and new views take a
+    /// few seconds to show up here.
+    pub views_count: i64,
+    /// Not counting hidden, deleted or pending comments, and always `0` if comments are
+    /// disabled. Not in the Realworld spec.
+    pub comments_count: i64,
+    /// Not in the Realworld spec.
+    pub comments_mode: CommentsMode,
+    /// The owner of the article, as the Realworld spec only allows for one author.
     pub author: Profile,
+    /// Everyone who can edit the article, owner first. Not in the Realworld spec.
+    pub authors: Vec<ArticleAuthor>,
+    /// The series this article is part of, if any. Not in the Realworld spec.
+    pub series: Option<ArticleSeries>,
+    /// How many of each kind of reaction the article has. Not in the Realworld spec.
+    ///
+    /// Kinds without any reactions are left out.
+    pub reactions: BTreeMap<String, i64>,
+    /// The kinds of reaction the current user has left, if any. Not in the Realworld spec.
+    pub user_reactions: Vec<String>,
 }
 
 #[derive(serde::Deserialize)]
@@ -37,17 +102,43 @@ pub struct Article {
 #[serde(rename_all = "camelCase")]
 pub struct CreateArticle {
     pub title: String,
-    pub description: String,
+    /// Not optional in the Realworld spec, but if it's left out we generate one from the body.
+    #[serde(default)]
+    pub description: Option<String>,
     pub body: String,
     pub tag_list: Vec<String>,
+    /// Not in the Realworld spec.
+    #[serde(default)]
+    pub comments_mode: CommentsMode,
+}
+
+/// What comes out of the `article_reactions()` SQL function, for `Article.reactions`
+/// and `Article.userReactions`.
+#[derive(serde::Deserialize)]
+pub struct ArticleReactions {
+    counts: BTreeMap<String, i64>,
+    mine: Vec<String>,
+}
+
+/// Not in the Realworld spec. Modeled on `Profile`, which has a `following` flag the same way.
+#[derive(serde::Serialize)]
+pub struct Tag {
+    pub name: String,
+    pub following: bool,
 }
 
 #[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct UpdateArticle {
     pub title: Option<String>,
+    /// Setting this to an empty string regenerates it from the body, like leaving it out of
+    /// `CreateArticle`, and keeps regenerating it whenever the body changes.
     pub description: Option<String>,
     pub body: Option<String>,
     // Interestingly, the spec omits `tagList` from this route.
+    /// Not in the Realworld spec. Comments that are already waiting for approval still need
+    /// approving if this changes from `approval_required`.
+    pub comments_mode: Option<CommentsMode>,
 }
 
 // One place that SQLx could still improve upon is when a query wants to return a nested
@@ -58,6 +149,8 @@ pub struct UpdateArticle {
 // It's a good chunk of boilerplate but thankfully you usually only have to write it a few
 // times across a whole project.
 pub struct ArticleFromQuery {
+    // Not returned in `Article`, but listings need it for pagination cursors.
+    pub article_id: Uuid,
     pub slug: String,
     pub title: String,
     pub description: String,
@@ -66,16 +159,43 @@ pub struct ArticleFromQuery {
     pub created_at: Timestamptz,
     pub updated_at: Timestamptz,
     pub favorited: bool,
+    pub bookmarked: bool,
+    pub pinned: bool,
     pub favorites_count: i64,
+    pub views_count: i64,
+    pub comments_count: i64,
+    pub comments_mode: CommentsMode,
+    pub word_count: i32,
+    pub reading_time_minutes: i32,
     pub author_username: String,
     pub author_bio: String,
     pub author_image: Option<String>,
     pub following_author: bool,
+    pub authors: Json<Vec<ArticleAuthor>>,
+    pub reactions: Json<ArticleReactions>,
+    // Same story for `series`, except every column is `null` if the article isn't in one.
+    pub series_slug: Option<String>,
+    pub series_title: Option<String>,
+    pub series_position: Option<i32>,
+    pub series_length: Option<i64>,
+    pub series_previous: Option<String>,
+    pub series_next: Option<String>,
 }
 
 impl ArticleFromQuery {
-    pub fn into_article(self) -> Article {
+    pub fn into_article(self, markdown: &MarkdownCache) -> Article {
+        // The rest can only be `null` if this is, but SQLx can't tell that through the view.
+        let series = self.series_slug.map(|slug| ArticleSeries {
+            slug,
+            title: self.series_title.unwrap_or_default(),
+            position: self.series_position.unwrap_or_default(),
+            length: self.series_length.unwrap_or_default(),
+            previous: self.series_previous,
+            next: self.series_next,
+        });
+
         Article {
+            body_html: markdown.render(&self.slug, self.updated_at.0, &self.body),
             slug: self.slug,
             title: self.title,
             description: self.description,
@@ -84,48 +204,119 @@ impl ArticleFromQuery {
             created_at: self.created_at,
             updated_at: self.updated_at,
             favorited: self.favorited,
+            bookmarked: self.bookmarked,
+            pinned: self.pinned,
             favorites_count: self.favorites_count,
+            views_count: self.views_count,
+            comments_count: self.comments_count,
+            comments_mode: self.comments_mode,
+            word_count: self.word_count,
+            reading_time_minutes: self.reading_time_minutes,
             author: Profile {
                 username: self.author_username,
                 bio: self.author_bio,
                 image: self.author_image,
                 following: self.following_author,
             },
+            authors: self.authors.0,
+            series,
+            reactions: self.reactions.0.counts,
+            user_reactions: self.reactions.0.mine,
         }
     }
 }
 
 impl ArticleController {
     /// https://realworld-docs.netlify.app/docs/specs/backend-specs/endpoints#create-article
+    ///
+    /// The article goes through the content filters first, which may hold it for moderation.
     pub async fn create_article(
         &self,
         author_id: Uuid,
         mut article: CreateArticle,
-    ) -> Result<Article> {
-        let slug = slugify(&article.title);
+    ) -> Result<Filtered<Article>> {
+        let slug = slugify(&article.title, &SlugOptions::from(&*self.config));
         article.tag_list.sort();
 
+        let stats = TextStats::of(&article.body);
+        let given_description = article
+            .description
+            .filter(|description| !description.trim().is_empty());
+        let description = given_description
+            .clone()
+            .unwrap_or_else(|| excerpt(&article.body, EXCERPT_MAX_CHARS));
+
+        let mut tx = self.pool.begin().await?;
+
+        // A generated description is left out, since it's made of the body anyway.
+        let mut fields = vec![("title", &*article.title), ("body", &*article.body)];
+        if let Some(description) = &given_description {
+            fields.push(("description", description));
+        }
+
+        let hold = self
+            .filters
+            .check(
+                &mut tx,
+                &Submission {
+                    kind: SubmissionKind::Article,
+                    author_id,
+                    article_id: None,
+                    comment_id: None,
+                    fields,
+                },
+            )
+            .await?;
+
         let article = sqlx::query_as!(
             ArticleFromQuery,
             // language=PostgreSQL
             r#"
                 with inserted_article as (
-                    insert into article (user_id, slug, title, description, body, tag_list)
-                    values ($1, $2, $3, $4, $5, $6)
+                    insert into article (
+                        user_id, slug, title, description, body, tag_list, search_language,
+                        word_count, reading_time_minutes, comments_mode, description_generated
+                    )
+                    values ($1, $2, $3, $4, $5, $6, $7::text::regconfig, $8, $9, $10, $11)
                     returning 
+                        article_id,
                         slug, 
                         title, 
                         description, 
                         body, 
                         tag_list, 
+                        word_count,
+                        reading_time_minutes,
+                        comments_mode "comments_mode: CommentsMode",
                         -- This is how you can override the inferred type of a column.
                         created_at "created_at: Timestamptz", 
                         updated_at "updated_at: Timestamptz"
+                ),
+                -- Every article has an owner in `article_author`; see `coauthor.rs`.
+                inserted_owner as (
+                    insert into article_author (article_id, user_id, role, accepted_at)
+                    select article_id, $1, 'owner', now()
+                    from inserted_article
                 )
                 select 
                     inserted_article.*,
                     false "favorited!",
+                    false "bookmarked!",
+                    false "pinned!",
+                    -- The owner row is inserted by the CTE above, which the rest of the query can't see yet.
+                    jsonb_build_array(jsonb_build_object(
+                        'username', username, 'bio', bio, 'image', image, 'following', false, 'role', 'owner'
+                    )) "authors!: Json<Vec<ArticleAuthor>>",
+                    '{"counts": {}, "mine": []}'::jsonb "reactions!: Json<ArticleReactions>",
+                    null::text series_slug,
+                    null::text series_title,
+                    null::int4 series_position,
+                    null::int8 series_length,
+                    null::text series_previous,
+                    null::text series_next,
                     0::int8 "favorites_count!",
+                    0::int8 "views_count!",
+                    0::int8 "comments_count!",
                     username author_username,
                     bio author_bio,
                     image author_image,
@@ -137,43 +328,127 @@ impl ArticleController {
             author_id,
             slug,
             article.title,
-            article.description,
+            description,
             article.body,
             // The typechecking code that SQLx emits for parameters sometimes chokes on vectors.
             // This slicing operation shouldn't be required, but it took a mess of type-system
             // hacks just to get the codegen this far.
-            &article.tag_list[..]
+            &article.tag_list[..],
+            self.config.search_language,
+            stats.word_count,
+            stats.reading_time_minutes,
+            article.comments_mode as CommentsMode,
+            given_description.is_none()
         )
-        .fetch_one(&self.pool)
+        .fetch_one(&mut tx)
         .await
         .on_constraint("article_slug_key", |_| {
             Error::unprocessable_entity([("slug", format!("duplicate article slug: {}", slug))])
         })?;
 
-        Ok(article.into_article())
+        record_mentions(&mut tx, author_id, article.article_id, None, &article.body).await?;
+
+        if let Some(reason) = &hold {
+            hold_content(
+                &mut tx,
+                HeldContent::Article(article.article_id),
+                author_id,
+                reason,
+            )
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(Filtered {
+            value: article.into_article(&self.markdown),
+            held: hold.is_some(),
+        })
     }
 
     /// https://realworld-docs.netlify.app/docs/specs/backend-specs/endpoints#update-article
+    ///
+    /// Like `create_article()`, the changed fields go through the content filters.
     pub async fn update_article(
         &self,
         user_id: Uuid,
         slug: &str,
         article: UpdateArticle,
-    ) -> Result<Article> {
+    ) -> Result<Filtered<Article>> {
         let mut tx = self.pool.begin().await?;
-        let new_slug = article.title.as_deref().map(slugify);
+        let slug_options = SlugOptions::from(&*self.config);
+        let new_slug = article
+            .title
+            .as_deref()
+            .map(|title| slugify(title, &slug_options));
         let article_meta = sqlx::query!(
-            "select article_id, user_id from article where slug = $1 for update",
-            slug
+            r#"
+                select
+                    article_id,
+                    user_id,
+                    body,
+                    description_generated,
+                    -- The owner and any co-authors who have accepted their invitation.
+                    exists(
+                        select 1
+                        from article_author
+                        where article_author.article_id = article.article_id
+                          and article_author.user_id = $2
+                          and accepted_at is not null
+                    ) "can_edit!"
+                from article
+                where slug = $1 and deleted_at is null
+                for update
+            "#,
+            slug,
+            user_id
         )
         .fetch_optional(&mut tx)
         .await?
         .ok_or(Error::NotFound)?;
 
-        if article_meta.user_id != user_id {
+        if !article_meta.can_edit {
             return Err(Error::Forbidden);
         }
 
+        let fields = [
+            ("title", &article.title),
+            ("description", &article.description),
+            ("body", &article.body),
+        ]
+        .into_iter()
+        .filter_map(|(field, text)| Some((field, text.as_deref()?)))
+        .collect();
+
+        // Held edits are blamed on whoever made them, not the owner.
+        let hold = self
+            .filters
+            .check(
+                &mut tx,
+                &Submission {
+                    kind: SubmissionKind::Article,
+                    author_id: user_id,
+                    article_id: Some(article_meta.article_id),
+                    comment_id: None,
+                    fields,
+                },
+            )
+            .await?;
+
+        let body = article.body.as_deref().unwrap_or(&article_meta.body);
+        let stats = article.body.as_deref().map(TextStats::of);
+        // A generated description is kept up to date with the body, until someone writes one.
+        let (description, description_generated) = match article.description {
+            Some(description) if description.trim().is_empty() => {
+                (Some(excerpt(body, EXCERPT_MAX_CHARS)), Some(true))
+            }
+            Some(description) => (Some(description), Some(false)),
+            None if article_meta.description_generated && article.body.is_some() => {
+                (Some(excerpt(body, EXCERPT_MAX_CHARS)), None)
+            }
+            None => (None, None),
+        };
+
         let article = sqlx::query_as!(
             ArticleFromQuery,
             // language=PostgreSQL
@@ -184,39 +459,86 @@ impl ArticleController {
                     slug = coalesce($1, slug),
                     title = coalesce($2, title),
                     description = coalesce($3, description),
-                    body = coalesce($4, body)
+                    body = coalesce($4, body),
+                    word_count = coalesce($8, word_count),
+                    reading_time_minutes = coalesce($9, reading_time_minutes),
+                    comments_mode = coalesce($10, comments_mode),
+                    description_generated = coalesce($11, description_generated)
                 where article_id = $5
                 returning
+                    article_id,
                     slug,
                     title,
                     description,
                     body,
                     tag_list,
-                    article.created_at "created_at: Timestamptz",
-                    article.updated_at "updated_at: Timestamptz"
+                    word_count,
+                    reading_time_minutes,
+                    comments_mode,
+                    created_at,
+                    updated_at
             )
             select
-                updated_article.*,
-                exists(select 1 from article_favorite where user_id = $6) "favorited!",
+                -- The type overrides go out here rather than in the CTE, so its columns keep
+                -- their real names for the rest of the query.
+                updated_article.article_id,
+                updated_article.slug,
+                updated_article.title,
+                updated_article.description,
+                updated_article.body,
+                updated_article.tag_list,
+                updated_article.word_count,
+                updated_article.reading_time_minutes,
+                updated_article.comments_mode "comments_mode: CommentsMode",
+                updated_article.created_at "created_at: Timestamptz",
+                updated_article.updated_at "updated_at: Timestamptz",
+                exists(select 1 from article_favorite fav where fav.article_id = $5 and fav.user_id = $6) "favorited!",
+                exists(select 1 from article_bookmark bm where bm.article_id = $5 and bm.user_id = $6) "bookmarked!",
+                exists(select 1 from article_pin pin where pin.article_id = $5) "pinned!",
+                article_authors(updated_article.article_id, $6) "authors!: Json<Vec<ArticleAuthor>>",
+                article_reactions(updated_article.article_id, $6) "reactions!: Json<ArticleReactions>",
+                article_series.series_slug,
+                article_series.series_title,
+                article_series.position series_position,
+                article_series.series_length,
+                article_series.previous_slug series_previous,
+                article_series.next_slug series_next,
                 coalesce(
                     (select count(*) from article_favorite fav where fav.article_id = $5),
                     0
                 ) "favorites_count!",
+                coalesce(
+                    (select sum(daily.views) from article_view_daily daily where daily.article_id = $5),
+                    0
+                )::int8 "views_count!",
+                -- `article_comments_count()` would see the article as it was before this
+                -- statement, so not whether comments were just disabled or enabled again.
+                case
+                    when updated_article.comments_mode = 'disabled' then 0
+                    else article_visible_comments_count($5)
+                end "comments_count!",
                 author.username author_username,
                 author.bio author_bio,
                 author.image author_image,
-                -- user not allowed to follow themselves
-                false "following_author!"
+                -- This used to be `false` as only the author could edit an article,
+                -- but co-authors can follow the owner.
+                exists(select 1 from follow where followed_user_id = author.user_id and following_user_id = $6) "following_author!"
             from updated_article
-            -- we've ensured the current user is the article's author so we can assume it here
-            inner join "user" author on author.user_id = $6
+            -- `author` is always the owner, even if a co-author made the edit.
+            inner join "user" author on author.user_id = $7
+            left join article_series_position article_series on article_series.article_id = updated_article.article_id
         "#,
             new_slug,
             article.title,
-            article.description,
+            description,
             article.body,
             article_meta.article_id,
-            user_id
+            user_id,
+            article_meta.user_id,
+            stats.as_ref().map(|stats| stats.word_count),
+            stats.as_ref().map(|stats| stats.reading_time_minutes),
+            article.comments_mode as Option<CommentsMode>,
+            description_generated
         )
         .fetch_one(&mut tx)
         .await
@@ -226,14 +548,42 @@ impl ArticleController {
                 format!("duplicate article slug: {}", new_slug.unwrap()),
             )])
         })?
-        .into_article();
+        .into_article(&self.markdown);
+
+        // Only if the body changed, which is when `stats` is set. Mentions are credited to whoever
+        // made the edit, like held edits.
+        if stats.is_some() {
+            record_mentions(
+                &mut tx,
+                user_id,
+                article_meta.article_id,
+                None,
+                &article.body,
+            )
+            .await?;
+        }
+
+        if let Some(reason) = &hold {
+            hold_content(
+                &mut tx,
+                HeldContent::Article(article_meta.article_id),
+                user_id,
+                reason,
+            )
+            .await?;
+        }
 
         tx.commit().await?;
 
-        Ok(article)
+        Ok(Filtered {
+            value: article,
+            held: hold.is_some(),
+        })
     }
 
     /// https://realworld-docs.netlify.app/docs/specs/backend-specs/endpoints#delete-article
+    ///
+    /// This only moves the article to the trash; see `restore_article()` and `purge_trash()`.
     pub async fn delete_article(&self, user_id: Uuid, slug: &str) -> Result<()> {
         let result = sqlx::query!(
             // I like to use raw strings for most queries mainly because CLion doesn't try
@@ -248,15 +598,21 @@ impl ArticleController {
             -- permissible here as we're not pairing this together with a huge select, so it
             -- should be relatively easy to understand the intended effect here.
             with deleted_article as (
-                delete from article 
+                update article
+                set deleted_at = now()
                 -- Important: we only delete the article if the user actually authored it.
-                where slug = $1 and user_id = $2
-                -- We just need to return *something* for `exists()` below.
-                returning 1
+                where slug = $1 and user_id = $2 and deleted_at is null
+                returning article_id
+            ),
+            -- Otherwise it'd quietly count against `max_pinned_articles` while it's in the trash,
+            -- and could push the profile over the limit when it's restored.
+            unpinned_article as (
+                delete from article_pin
+                where article_id in (select article_id from deleted_article)
             )
             select
                 -- This will be `true` if the article existed before we deleted it.
-                exists(select 1 from article where slug = $1) "existed!",
+                exists(select 1 from article where slug = $1 and deleted_at is null) "existed!",
                 -- This will only be `true` if we actually deleted the article.
                 exists(select 1 from deleted_article) "deleted!"
         "#,
@@ -278,48 +634,110 @@ impl ArticleController {
         }
     }
 
+    /// Take an article back out of the trash. Only the owner can do this, same as deleting it.
+    pub async fn restore_article(&self, user_id: Uuid, slug: &str) -> Result<Article> {
+        // Same trick as `delete_article()`.
+        let result = sqlx::query!(
+            r#"
+            with restored_article as (
+                update article
+                set deleted_at = null
+                -- Articles hidden by a moderator are in the trash too, but only they can bring them back.
+                where slug = $1 and user_id = $2 and deleted_at is not null and hidden_at is null
+                returning article_id
+            )
+            select
+                exists(select 1 from article where slug = $1 and deleted_at is not null) "existed!",
+                (select article_id from restored_article) "restored_id?"
+        "#,
+            slug,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        match (result.restored_id, result.existed) {
+            (Some(article_id), _) => self.article_by_id(user_id, article_id).await,
+            (None, true) => Err(Error::Forbidden),
+            // Either there's no such article, it's already been purged, or it was never deleted.
+            (None, false) => Err(Error::NotFound),
+        }
+    }
+
     /// https://realworld-docs.netlify.app/docs/specs/backend-specs/endpoints#get-article
-    pub async fn get_article(&self, user_id: Option<Uuid>, slug: &str) -> Result<Article> {
+    /// Also counts a view of the article by `viewer`, which marks it as seen if they're logged in.
+    pub async fn get_article(
+        &self,
+        user_id: Option<Uuid>,
+        slug: &str,
+        viewer: Viewer,
+    ) -> Result<Article> {
         let article = sqlx::query_as!(
         ArticleFromQuery,
         // language=PostgreSQL
         r#"
             select
+                article.article_id,
                 slug,
                 title,
                 description,
                 body,
                 tag_list,
+                word_count,
+                reading_time_minutes,
                 article.created_at "created_at: Timestamptz",
                 article.updated_at "updated_at: Timestamptz",
-                exists(select 1 from article_favorite where user_id = $1) "favorited!",
+                exists(select 1 from article_favorite fav where fav.article_id = article.article_id and fav.user_id = $1) "favorited!",
+                exists(select 1 from article_bookmark bm where bm.article_id = article.article_id and bm.user_id = $1) "bookmarked!",
+                exists(select 1 from article_pin pin where pin.article_id = article.article_id) "pinned!",
+                article_authors(article.article_id, $1) "authors!: Json<Vec<ArticleAuthor>>",
+                article_reactions(article.article_id, $1) "reactions!: Json<ArticleReactions>",
+                article_series.series_slug,
+                article_series.series_title,
+                article_series.position series_position,
+                article_series.series_length,
+                article_series.previous_slug series_previous,
+                article_series.next_slug series_next,
                 coalesce(
                     -- `count(*)` returns `NULL` if the query returned zero columns
                     -- not exactly a fan of that design choice but whatever
                     (select count(*) from article_favorite fav where fav.article_id = article.article_id),
                     0
                 ) "favorites_count!",
+                coalesce(
+                    (select sum(daily.views) from article_view_daily daily where daily.article_id = article.article_id),
+                    0
+                )::int8 "views_count!",
+                article_comments_count(article.article_id) "comments_count!",
+                article.comments_mode "comments_mode: CommentsMode",
                 author.username author_username,
                 author.bio author_bio,
                 author.image author_image,
                 exists(select 1 from follow where followed_user_id = author.user_id and following_user_id = $1) "following_author!"
             from article
             inner join "user" author using (user_id)
-            where slug = $2
+            left join article_series_position article_series on article_series.article_id = article.article_id
+            where slug = $2 and article.deleted_at is null
         "#,
         user_id,
         slug
     )
         .fetch_optional(&self.pool)
         .await?
-        .ok_or(Error::NotFound)?
-        .into_article();
+        .ok_or(Error::NotFound)?;
+
+        self.views.record(article.article_id, viewer);
+
+        let article = article.into_article(&self.markdown);
 
         Ok(article)
     }
 
     // This is used in a few different places so it makes sense to extract into its own function.
     //
+    // Unlike everything else, this doesn't hide articles in the trash, since `restore_article()`
+    // uses it and all the other callers have already checked.
+    //
     // I usually throw stuff like this at the bottom of the file but other engineers like
     // to put these kinds of functions in their own modules. Po-tay-to po-tah-to.
     pub async fn article_by_id(&self, user_id: Uuid, article_id: Uuid) -> Result<Article> {
@@ -328,27 +746,47 @@ impl ArticleController {
         // language=PostgreSQL
         r#"
             select
+                article.article_id,
                 slug,
                 title,
                 description,
                 body,
                 tag_list,
+                word_count,
+                reading_time_minutes,
                 article.created_at "created_at: Timestamptz",
                 article.updated_at "updated_at: Timestamptz",
-                exists(select 1 from article_favorite where user_id = $1) "favorited!",
+                exists(select 1 from article_favorite fav where fav.article_id = article.article_id and fav.user_id = $1) "favorited!",
+                exists(select 1 from article_bookmark bm where bm.article_id = article.article_id and bm.user_id = $1) "bookmarked!",
+                exists(select 1 from article_pin pin where pin.article_id = article.article_id) "pinned!",
+                article_authors(article.article_id, $1) "authors!: Json<Vec<ArticleAuthor>>",
+                article_reactions(article.article_id, $1) "reactions!: Json<ArticleReactions>",
+                article_series.series_slug,
+                article_series.series_title,
+                article_series.position series_position,
+                article_series.series_length,
+                article_series.previous_slug series_previous,
+                article_series.next_slug series_next,
                 coalesce(
                     -- `count(*)` returns `NULL` if the query returned zero columns
                     -- not exactly a fan of that design choice but whatever
                     (select count(*) from article_favorite fav where fav.article_id = article.article_id),
                     0
                 ) "favorites_count!",
+                coalesce(
+                    (select sum(daily.views) from article_view_daily daily where daily.article_id = article.article_id),
+                    0
+                )::int8 "views_count!",
+                article_comments_count(article.article_id) "comments_count!",
+                article.comments_mode "comments_mode: CommentsMode",
                 author.username author_username,
                 author.bio author_bio,
                 author.image author_image,
                 exists(select 1 from follow where followed_user_id = author.user_id and following_user_id = $1) "following_author!"
             from article
             inner join "user" author using (user_id)
-            where article_id = $2
+            left join article_series_position article_series on article_series.article_id = article.article_id
+            where article.article_id = $2
         "#,
         user_id,
         article_id
@@ -356,7 +794,7 @@ impl ArticleController {
         .fetch_optional(&self.pool)
         .await?
         .ok_or(Error::NotFound)?
-        .into_article();
+        .into_article(&self.markdown);
 
         Ok(article)
     }
@@ -374,7 +812,7 @@ impl ArticleController {
         let article_id = sqlx::query_scalar!(
             r#"
             with selected_article as (
-                select article_id from article where slug = $1
+                select article_id from article where slug = $1 and deleted_at is null
             ),
             inserted_favorite as (
                 insert into article_favorite(article_id, user_id)
@@ -401,7 +839,7 @@ impl ArticleController {
         let article_id = sqlx::query_scalar!(
             r#"
             with selected_article as (
-                select article_id from article where slug = $1
+                select article_id from article where slug = $1 and deleted_at is null
             ),
             deleted_favorite as (
                 delete from article_favorite
@@ -422,6 +860,228 @@ impl ArticleController {
         Ok(article)
     }
 
+    /// Add the article to the user's reading list. Not in the Realworld spec.
+    ///
+    /// Same shape as `favorite_article()`, just against `article_bookmark`.
+    pub async fn bookmark_article(&self, user_id: Uuid, slug: &str) -> Result<Article> {
+        let article_id = sqlx::query_scalar!(
+            r#"
+            with selected_article as (
+                select article_id from article where slug = $1 and deleted_at is null
+            ),
+            inserted_bookmark as (
+                insert into article_bookmark(article_id, user_id)
+                select article_id, $2
+                from selected_article
+                -- if the article is already bookmarked
+                on conflict do nothing
+            )
+            select article_id from selected_article
+        "#,
+            slug,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        let article = self.article_by_id(user_id, article_id).await?;
+        Ok(article)
+    }
+
+    pub async fn unbookmark_article(&self, user_id: Uuid, slug: &str) -> Result<Article> {
+        let article_id = sqlx::query_scalar!(
+            r#"
+            with selected_article as (
+                select article_id from article where slug = $1 and deleted_at is null
+            ),
+            deleted_bookmark as (
+                delete from article_bookmark
+                where article_id = (select article_id from selected_article)
+                and user_id = $2
+            )
+            select article_id from selected_article
+        "#,
+            slug,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        let article = self.article_by_id(user_id, article_id).await?;
+
+        Ok(article)
+    }
+
+    /// Pin the article to the top of the owner's profile. Not in the Realworld spec.
+    ///
+    /// Only the owner can do this, since `?author=` only matches owners and that's where
+    /// pinned articles show up. Pinning an article that's already pinned does nothing.
+    pub async fn pin_article(&self, user_id: Uuid, slug: &str) -> Result<Article> {
+        let mut tx = self.pool.begin().await?;
+
+        let article = sqlx::query!(
+            r#"
+                select
+                    article_id,
+                    user_id = $2 "is_owner!",
+                    exists(select 1 from article_pin pin where pin.article_id = article.article_id) "pinned!"
+                from article
+                where slug = $1 and deleted_at is null
+            "#,
+            slug,
+            user_id
+        )
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        if !article.is_owner {
+            return Err(Error::Forbidden);
+        }
+
+        if !article.pinned {
+            // Two concurrent pins could both see room for one more pin and both go in,
+            // so they take turns on the user's row.
+            sqlx::query!(
+                r#"select 1 "locked" from "user" where user_id = $1 for update"#,
+                user_id
+            )
+            .fetch_one(&mut tx)
+            .await?;
+
+            let pinned_count = sqlx::query_scalar!(
+                r#"select count(*) "count!" from article_pin where user_id = $1"#,
+                user_id
+            )
+            .fetch_one(&mut tx)
+            .await?;
+
+            if pinned_count >= self.config.max_pinned_articles {
+                return Err(Error::unprocessable_entity([(
+                    "pinned",
+                    format!(
+                        "can't pin more than {} articles",
+                        self.config.max_pinned_articles
+                    ),
+                )]));
+            }
+
+            sqlx::query!(
+                r#"
+                    insert into article_pin (article_id, user_id)
+                    values ($1, $2)
+                    on conflict (article_id) do nothing
+                "#,
+                article.article_id,
+                user_id
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        self.article_by_id(user_id, article.article_id).await
+    }
+
+    /// Not in the Realworld spec. Unpinning an article that isn't pinned does nothing.
+    pub async fn unpin_article(&self, user_id: Uuid, slug: &str) -> Result<Article> {
+        let article = sqlx::query!(
+            r#"
+                select article_id, user_id = $2 "is_owner!"
+                from article
+                where slug = $1 and deleted_at is null
+            "#,
+            slug,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        if !article.is_owner {
+            return Err(Error::Forbidden);
+        }
+
+        sqlx::query!(
+            "delete from article_pin where article_id = $1",
+            article.article_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        self.article_by_id(user_id, article.article_id).await
+    }
+
+    /// Leave a reaction on the article. Not in the Realworld spec.
+    ///
+    /// Same shape as `favorite_article()`, but users can leave more than one kind.
+    pub async fn add_reaction(&self, user_id: Uuid, slug: &str, kind: &str) -> Result<Article> {
+        let allowed = &self.config.article_reactions;
+
+        if !allowed.iter().any(|allowed| allowed == kind) {
+            return Err(Error::unprocessable_entity([(
+                "kind",
+                format!("must be one of: {}", allowed.join(", ")),
+            )]));
+        }
+
+        let article_id = sqlx::query_scalar!(
+            r#"
+            with selected_article as (
+                select article_id from article where slug = $1 and deleted_at is null
+            ),
+            inserted_reaction as (
+                insert into article_reaction(article_id, user_id, kind)
+                select article_id, $2, $3
+                from selected_article
+                -- if the user already left this reaction
+                on conflict do nothing
+            )
+            select article_id from selected_article
+        "#,
+            slug,
+            user_id,
+            kind
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        self.article_by_id(user_id, article_id).await
+    }
+
+    /// Not in the Realworld spec.
+    ///
+    /// `kind` isn't checked against `Config::article_reactions`, so reactions of a kind that's
+    /// since been taken out of the list can still be removed.
+    pub async fn remove_reaction(&self, user_id: Uuid, slug: &str, kind: &str) -> Result<Article> {
+        let article_id = sqlx::query_scalar!(
+            r#"
+            with selected_article as (
+                select article_id from article where slug = $1 and deleted_at is null
+            ),
+            deleted_reaction as (
+                delete from article_reaction
+                where article_id = (select article_id from selected_article)
+                and user_id = $2
+                and kind = $3
+            )
+            select article_id from selected_article
+        "#,
+            slug,
+            user_id,
+            kind
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        self.article_by_id(user_id, article_id).await
+    }
+
     /// https://realworld-docs.netlify.app/docs/specs/backend-specs/endpoints#get-tags
     pub async fn get_tags(&self) -> Result<Vec<String>> {
         // Note: this query requires a full table scan and is a likely point for a DoS attack.
@@ -438,6 +1098,7 @@ impl ArticleController {
             r#"
                 select distinct tag "tag!"
                 from article, unnest (article.tag_list) tags(tag)
+                where deleted_at is null
                 order by tag
             "#
         )
@@ -446,30 +1107,232 @@ impl ArticleController {
 
         Ok(tags)
     }
+
+    /// Articles with this tag will show up in the user's feed.
+    pub async fn follow_tag(&self, user_id: Uuid, tag: &str) -> Result<Tag> {
+        let tag = validate_tag(tag)?;
+
+        sqlx::query!(
+            "insert into tag_follow (user_id, tag) values ($1, $2) on conflict do nothing",
+            user_id,
+            tag
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(Tag {
+            name: tag.to_string(),
+            following: true,
+        })
+    }
+
+    pub async fn unfollow_tag(&self, user_id: Uuid, tag: &str) -> Result<Tag> {
+        let tag = validate_tag(tag)?;
+
+        sqlx::query!(
+            "delete from tag_follow where user_id = $1 and tag = $2",
+            user_id,
+            tag
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(Tag {
+            name: tag.to_string(),
+            following: false,
+        })
+    }
+}
+
+/// How often `purge_trash()` looks for articles to purge. The retention period is in days,
+/// so there's no point doing it more often than this.
+const PURGE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Permanently delete articles that have been in the trash for longer than `retention_days`, forever.
+///
+/// Spawned by `http::serve()`, same as `listing::refresh_trending()`. This is the only place
+/// articles actually get deleted, which finally cascades to their comments, favorites and so on.
+pub async fn purge_trash(pool: PgPool, retention_days: u32) {
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        let result = sqlx::query!(
+            r#"
+                delete from article
+                -- Hidden articles stay until a moderator decides what to do with them.
+                where deleted_at < now() - make_interval(days => $1) and hidden_at is null
+            "#,
+            retention_days as i32
+        )
+        .execute(&pool)
+        .await;
+
+        match result {
+            Ok(result) if result.rows_affected() > 0 => {
+                log::info!("purged {} articles from the trash", result.rows_affected())
+            }
+            Ok(_) => (),
+            // Not fatal; they'll be purged next time.
+            Err(e) => log::error!("failed to purge the trash: {e}"),
+        }
+    }
+}
+
+// Tags aren't validated when creating an article, but there's no point letting a user
+// follow a tag that could never match anything.
+fn validate_tag(tag: &str) -> Result<&str> {
+    let tag = tag.trim();
+
+    if tag.is_empty() {
+        return Err(Error::unprocessable_entity([("tag", "can't be blank")]));
+    }
+
+    Ok(tag)
+}
+
+/// What `slugify()` does with characters that have no ASCII transliteration.
+///
+/// That's surprisingly rare: [`deunicode`] covers most scripts and even spells out emoji by name,
+/// so this mostly comes up for private-use and unassigned codepoints.
+#[derive(clap::ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SlugFallback {
+    /// Leave the character out of the slug.
+    #[default]
+    Drop,
+    /// Keep the character as-is. Clients are expected to percent-encode it in the URL.
+    Keep,
+    /// Spell out the codepoint in hex, e.g. `ue000`, so the slug stays ASCII but lossless.
+    Hex,
+}
+
+/// Tunes how `slugify()` turns a title into a slug. See the `slug_*` fields on `Config`.
+#[derive(Clone, Debug)]
+pub struct SlugOptions {
+    pub max_length: usize,
+    pub fallback: SlugFallback,
+    pub stop_words: Vec<String>,
+}
+
+impl Default for SlugOptions {
+    fn default() -> Self {
+        Self {
+            max_length: 100,
+            fallback: SlugFallback::Drop,
+            stop_words: vec![],
+        }
+    }
+}
+
+impl From<&Config> for SlugOptions {
+    fn from(config: &Config) -> Self {
+        Self {
+            max_length: config.slug_max_length,
+            fallback: config.slug_fallback,
+            stop_words: config.slug_stop_words.clone(),
+        }
+    }
 }
 
 // (Sadly, doctests are not run on private functions it seems.)
-fn slugify(string: &str) -> String {
+pub(crate) fn slugify(string: &str, options: &SlugOptions) -> String {
     const QUOTE_CHARS: &[char] = &['\'', '"'];
 
-    string
+    let transliterated = transliterate(string, options.fallback);
+
+    let words: Vec<String> = transliterated
         // Split on anything that isn't a word character or quotation mark.
         // This has the effect of keeping contractions and possessives together.
         .split(|c: char| !(QUOTE_CHARS.contains(&c) || c.is_alphanumeric()))
-        // If multiple non-word characters follow each other then we'll get empty substrings
-        // so we'll filter those out.
-        .filter(|s| !s.is_empty())
         .map(|s| {
             // Remove quotes from the substring.
             //
             // This allocation is probably avoidable with some more iterator hackery but
             // at that point we'd be micro-optimizing. This function isn't called all that often.
-            let mut s = s.replace(QUOTE_CHARS, "");
-            // Make the substring lowercase (in-place operation)
-            s.make_ascii_lowercase();
-            s
+            //
+            // `to_lowercase()` rather than `make_ascii_lowercase()` because `SlugFallback::Keep`
+            // can leave non-ASCII letters behind.
+            s.replace(QUOTE_CHARS, "").to_lowercase()
         })
-        .join("-")
+        // If multiple non-word characters follow each other then we'll get empty substrings
+        // so we'll filter those out. A lone quote also ends up empty after the above.
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    let mut kept: Vec<&str> = words
+        .iter()
+        .map(String::as_str)
+        .filter(|word| {
+            !options
+                .stop_words
+                .iter()
+                .any(|stop_word| stop_word.trim().eq_ignore_ascii_case(word))
+        })
+        .collect();
+
+    // A title made up entirely of stop words ("The Who") would otherwise have no slug at all.
+    if kept.is_empty() {
+        kept = words.iter().map(String::as_str).collect();
+    }
+
+    let mut slug = String::new();
+    let mut slug_len = 0;
+
+    for word in kept {
+        let word_len = word.chars().count();
+
+        if slug.is_empty() {
+            if word_len > options.max_length {
+                // No word boundary to cut at, so just cut the word.
+                slug.extend(word.chars().take(options.max_length));
+                break;
+            }
+        } else if slug_len + 1 + word_len > options.max_length {
+            break;
+        } else {
+            slug.push('-');
+            slug_len += 1;
+        }
+
+        slug.push_str(word);
+        slug_len += word_len;
+    }
+
+    // Nothing usable in the title at all (e.g. it was all punctuation), but `article.slug` is
+    // unique and not null so we still need *something*.
+    if slug.is_empty() {
+        slug = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(options.max_length.min(8))
+            .map(|b| char::from(b).to_ascii_lowercase())
+            .collect();
+    }
+
+    slug
+}
+
+fn transliterate(string: &str, fallback: SlugFallback) -> String {
+    let mut out = String::with_capacity(string.len());
+
+    for c in string.chars() {
+        match deunicode::deunicode_char(c) {
+            Some(ascii) => out.push_str(ascii),
+            None => match fallback {
+                // Treat it as a word break so we don't glue its neighbors together.
+                SlugFallback::Drop => out.push(' '),
+                SlugFallback::Keep => out.push(c),
+                SlugFallback::Hex => {
+                    use std::fmt::Write;
+                    // Writing to a `String` can't fail.
+                    let _ = write!(out, " u{:x} ", c as u32);
+                }
+            },
+        }
+    }
+
+    out
 }
 
 // This fulfills the "at least one unit test" requirement of the Realworld spec.
@@ -500,18 +1363,446 @@ fn slugify(string: &str) -> String {
 // and energy to help fill that out.
 #[test]
 fn test_slugify() {
+    let options = SlugOptions::default();
+
     assert_eq!(
-        slugify("Segfaults and You: When Raw Pointers Go Wrong"),
+        slugify("Segfaults and You: When Raw Pointers Go Wrong", &options),
         "segfaults-and-you-when-raw-pointers-go-wrong"
     );
 
     assert_eq!(
-        slugify("Why are DB Admins Always Shouting?"),
+        slugify("Why are DB Admins Always Shouting?", &options),
         "why-are-db-admins-always-shouting"
     );
 
     assert_eq!(
-        slugify("Converting to Rust from C: It's as Easy as 1, 2, 3!"),
+        slugify(
+            "Converting to Rust from C: It's as Easy as 1, 2, 3!",
+            &options
+        ),
         "converting-to-rust-from-c-its-as-easy-as-1-2-3"
     )
 }
+
+#[test]
+fn test_slugify_unicode() {
+    let options = SlugOptions::default();
+
+    assert_eq!(
+        slugify("Größenänderung für Anfänger", &options),
+        "grossenanderung-fur-anfanger"
+    );
+    assert_eq!(
+        slugify("Học Rust bằng tiếng Việt", &options),
+        "hoc-rust-bang-tieng-viet"
+    );
+    assert_eq!(slugify("Привет, мир!", &options), "privet-mir");
+    assert_eq!(slugify("北京欢迎你", &options), "bei-jing-huan-ying-ni");
+    // Typographic apostrophes are contractions too.
+    assert_eq!(slugify("It’s Fine", &options), "its-fine");
+
+    assert_eq!(
+        slugify(
+            "Private \u{e000} Use",
+            &SlugOptions {
+                fallback: SlugFallback::Hex,
+                ..SlugOptions::default()
+            }
+        ),
+        "private-ue000-use"
+    );
+    assert_eq!(
+        slugify(
+            "Private\u{e000}Use",
+            &SlugOptions {
+                fallback: SlugFallback::Drop,
+                ..SlugOptions::default()
+            }
+        ),
+        "private-use"
+    );
+}
+
+#[test]
+fn test_slugify_options() {
+    let options = SlugOptions {
+        max_length: 20,
+        stop_words: vec!["a".into(), "the".into(), "of".into()],
+        ..SlugOptions::default()
+    };
+
+    assert_eq!(
+        slugify("The Rise and Fall of a Borrow Checker", &options),
+        "rise-and-fall-borrow"
+    );
+    // Stop words only, so they're kept.
+    assert_eq!(slugify("The A", &options), "the-a");
+    // A single word longer than the limit gets cut mid-word.
+    assert_eq!(
+        slugify("Supercalifragilisticexpialidocious", &options),
+        "supercalifragilistic"
+    );
+}
+
+#[cfg(test)]
+mod slugify_props {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn options() -> impl Strategy<Value = SlugOptions> {
+        (
+            1usize..120,
+            prop_oneof![Just(SlugFallback::Drop), Just(SlugFallback::Hex)],
+            prop::collection::vec("[a-z]{1,4}", 0..4),
+        )
+            .prop_map(|(max_length, fallback, stop_words)| SlugOptions {
+                max_length,
+                fallback,
+                stop_words,
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn slug_is_url_safe(title in "\\PC*", options in options()) {
+            let slug = slugify(&title, &options);
+
+            prop_assert!(!slug.is_empty());
+            prop_assert!(
+                slug.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-'),
+                "{:?}",
+                slug
+            );
+            prop_assert!(!slug.starts_with('-') && !slug.ends_with('-'), "{:?}", slug);
+            prop_assert!(!slug.contains("--"), "{:?}", slug);
+        }
+
+        #[test]
+        fn slug_respects_max_length(title in "\\PC*", options in options()) {
+            prop_assert!(slugify(&title, &options).chars().count() <= options.max_length);
+        }
+
+        #[test]
+        fn slug_truncates_at_word_boundary(
+            words in prop::collection::vec("[a-z0-9]{1,10}", 1..20),
+            max_length in 10usize..60,
+        ) {
+            let options = SlugOptions { max_length, ..SlugOptions::default() };
+            let full = words.join("-");
+            let slug = slugify(&words.join(" "), &options);
+
+            if full.len() <= max_length {
+                prop_assert_eq!(slug, full);
+            } else if words[0].len() <= max_length {
+                // Some prefix of whole words.
+                prop_assert!(full.starts_with(&slug));
+                prop_assert_eq!(full.as_bytes()[slug.len()], b'-');
+            }
+        }
+
+        #[test]
+        fn slugify_is_idempotent(title in "\\PC*", options in options()) {
+            let slug = slugify(&title, &options);
+            let stop_words_only = slug
+                .split('-')
+                .all(|word| options.stop_words.iter().any(|sw| sw == word));
+
+            // Re-slugifying would strip stop words that were only kept as a last resort.
+            prop_assume!(!stop_words_only);
+            prop_assert_eq!(slugify(&slug, &options), slug);
+        }
+
+        #[test]
+        fn slug_omits_stop_words(
+            words in prop::collection::vec("[a-z]{1,6}", 1..10),
+            stop_words in prop::collection::vec("[a-z]{1,6}", 1..4),
+        ) {
+            let options = SlugOptions { max_length: 1000, stop_words: stop_words.clone(), ..SlugOptions::default() };
+            let slug = slugify(&words.join(" "), &options);
+
+            if words.iter().any(|word| !stop_words.contains(word)) {
+                prop_assert!(slug.split('-').all(|word| !stop_words.iter().any(|sw| sw == word)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::listing::ListArticlesQuery;
+    use crate::models::testing::{config, create_article, create_user, store};
+    use crate::models::StoreTrait;
+
+    #[sqlx::test]
+    async fn favorited_is_per_article(pool: PgPool) {
+        let store = store(pool.clone(), config());
+        let alice = create_user(&pool, "alice").await;
+        create_article(&store, alice, "Liked").await;
+        let other = create_article(&store, alice, "Not Liked").await;
+
+        store
+            .article()
+            .favorite_article(alice, "liked")
+            .await
+            .unwrap();
+
+        // Favoriting one article doesn't make every other article look favorited too.
+        let article = store
+            .article()
+            .get_article(Some(alice), &other.slug, Viewer::User(alice))
+            .await
+            .unwrap();
+        assert!(!article.favorited);
+
+        let article = store
+            .article()
+            .update_article(
+                alice,
+                &other.slug,
+                UpdateArticle {
+                    title: None,
+                    description: None,
+                    body: Some("Still not liked.".into()),
+                    comments_mode: None,
+                },
+            )
+            .await
+            .unwrap()
+            .value;
+        assert!(!article.favorited);
+
+        let page = store
+            .listing()
+            .article_list(Some(alice), ListArticlesQuery::default())
+            .await
+            .unwrap();
+        let favorited: Vec<_> = page
+            .articles
+            .iter()
+            .map(|article| (&*article.slug, article.favorited))
+            .collect();
+        assert_eq!(favorited, [("not-liked", false), ("liked", true)]);
+    }
+
+    #[sqlx::test]
+    async fn bookmarks_are_private(pool: PgPool) {
+        let store = store(pool.clone(), config());
+        let alice = create_user(&pool, "alice").await;
+        let bob = create_user(&pool, "bob").await;
+        create_article(&store, alice, "Read Later").await;
+        create_article(&store, alice, "Read Never").await;
+
+        let article = store
+            .article()
+            .bookmark_article(bob, "read-later")
+            .await
+            .unwrap();
+        assert!(article.bookmarked);
+
+        let article = store
+            .article()
+            .get_article(Some(alice), "read-later", Viewer::User(alice))
+            .await
+            .unwrap();
+        assert!(!article.bookmarked);
+
+        let reading_list = |user_id| {
+            let store = store.clone();
+            async move {
+                store
+                    .listing()
+                    .bookmarked_articles(user_id, Default::default())
+                    .await
+                    .unwrap()
+                    .articles
+                    .into_iter()
+                    .map(|article| article.slug)
+                    .collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(reading_list(bob).await, ["read-later"]);
+        assert!(reading_list(alice).await.is_empty());
+
+        store
+            .article()
+            .unbookmark_article(bob, "read-later")
+            .await
+            .unwrap();
+        assert!(reading_list(bob).await.is_empty());
+    }
+
+    #[sqlx::test]
+    async fn editors_can_update_but_not_delete(pool: PgPool) {
+        let store = store(pool.clone(), config());
+        let alice = create_user(&pool, "alice").await;
+        let bob = create_user(&pool, "bob").await;
+        create_article(&store, alice, "Joint Effort").await;
+
+        let edit = |user_id| {
+            let store = store.clone();
+            async move {
+                store
+                    .article()
+                    .update_article(
+                        user_id,
+                        "joint-effort",
+                        UpdateArticle {
+                            title: None,
+                            description: None,
+                            body: Some("Rewritten together.".into()),
+                            comments_mode: None,
+                        },
+                    )
+                    .await
+            }
+        };
+
+        // Not until they've accepted the invitation.
+        store
+            .coauthor()
+            .invite_author(alice, "joint-effort", "bob")
+            .await
+            .unwrap();
+        assert!(matches!(edit(bob).await, Err(Error::Forbidden)));
+
+        store
+            .coauthor()
+            .accept_invitation(bob, "joint-effort")
+            .await
+            .unwrap();
+        let article = edit(bob).await.unwrap().value;
+        assert_eq!(article.body, "Rewritten together.");
+        // Still the owner's article.
+        assert_eq!(article.author.username, "alice");
+
+        assert!(matches!(
+            store.article().delete_article(bob, "joint-effort").await,
+            Err(Error::Forbidden)
+        ));
+        store
+            .article()
+            .delete_article(alice, "joint-effort")
+            .await
+            .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn generated_description_follows_body(pool: PgPool) {
+        let store = store(pool.clone(), config());
+        let alice = create_user(&pool, "alice").await;
+        let article = create_article(&store, alice, "Summaries").await;
+        assert_eq!(article.description, "All about Summaries.");
+
+        let update = |description: Option<&str>, body: &str| {
+            let store = store.clone();
+            let article = UpdateArticle {
+                title: None,
+                description: description.map(Into::into),
+                body: Some(body.into()),
+                comments_mode: None,
+            };
+
+            async move {
+                store
+                    .article()
+                    .update_article(alice, "summaries", article)
+                    .await
+                    .unwrap()
+                    .value
+                    .description
+            }
+        };
+
+        assert_eq!(update(None, "Second draft.").await, "Second draft.");
+        assert_eq!(
+            update(Some("Written by hand."), "Third draft.").await,
+            "Written by hand."
+        );
+        // Not generated anymore, so it's left alone.
+        assert_eq!(update(None, "Fourth draft.").await, "Written by hand.");
+        // Until it's cleared.
+        assert_eq!(update(Some(""), "Fifth draft.").await, "Fifth draft.");
+        assert_eq!(update(None, "Sixth draft.").await, "Sixth draft.");
+    }
+
+    #[sqlx::test]
+    async fn updated_at_only_for_edits(pool: PgPool) {
+        let store = store(pool.clone(), config());
+        let alice = create_user(&pool, "alice").await;
+        let created = create_article(&store, alice, "Edited").await.updated_at.0;
+
+        let update = |body: Option<&str>, comments_mode| {
+            let store = store.clone();
+            let article = UpdateArticle {
+                title: None,
+                description: None,
+                body: body.map(Into::into),
+                comments_mode,
+            };
+
+            async move {
+                store
+                    .article()
+                    .update_article(alice, "edited", article)
+                    .await
+                    .unwrap()
+                    .value
+                    .updated_at
+                    .0
+            }
+        };
+
+        store
+            .article()
+            .delete_article(alice, "edited")
+            .await
+            .unwrap();
+        let restored = store
+            .article()
+            .restore_article(alice, "edited")
+            .await
+            .unwrap();
+        assert_eq!(restored.updated_at.0, created);
+
+        assert_eq!(update(None, Some(CommentsMode::Locked)).await, created);
+        assert!(update(Some("Actually edited."), None).await > created);
+    }
+
+    #[sqlx::test]
+    async fn max_pins(pool: PgPool) {
+        let store = store(
+            pool.clone(),
+            Config {
+                max_pinned_articles: 2,
+                ..config()
+            },
+        );
+        let alice = create_user(&pool, "alice").await;
+        let bob = create_user(&pool, "bob").await;
+
+        for title in ["One", "Two", "Three"] {
+            create_article(&store, alice, title).await;
+        }
+
+        let pin = |user_id, slug| {
+            let store = store.clone();
+            async move { store.article().pin_article(user_id, slug).await }
+        };
+
+        assert!(matches!(pin(bob, "one").await, Err(Error::Forbidden)));
+
+        assert!(pin(alice, "one").await.unwrap().pinned);
+        assert!(pin(alice, "two").await.unwrap().pinned);
+        assert!(matches!(
+            pin(alice, "three").await,
+            Err(Error::UnprocessableEntity { errors }) if errors.contains_key("pinned")
+        ));
+        // Pinning one that's already pinned is fine.
+        assert!(pin(alice, "two").await.unwrap().pinned);
+
+        // Trashing a pinned article makes room.
+        store.article().delete_article(alice, "two").await.unwrap();
+        assert!(pin(alice, "three").await.unwrap().pinned);
+    }
+}
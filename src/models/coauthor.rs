@@ -0,0 +1,292 @@
+use crate::http::types::Timestamptz;
+use crate::http::{Error, Result};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// Every author of an article has a row in `article_author`, including the owner
+// (`article.user_id`) so permission checks only have to look in one place.
+//
+// Only the owner can invite or remove co-authors, and co-authors have to accept
+// an invitation before they can edit the article or show up in `Article.authors`.
+
+#[derive(Clone)]
+pub struct CoauthorController {
+    pool: PgPool,
+}
+
+impl CoauthorController {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, sqlx::Type, Copy, Clone, Debug, PartialEq, Eq)]
+// Stored as `text` rather than a Postgres enum; see the migration.
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum AuthorRole {
+    Owner,
+    Editor,
+}
+
+/// An entry in `Article.authors`.
+///
+/// Deserializable because it comes out of the `article_authors()` SQL function as JSON.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ArticleAuthor {
+    pub username: String,
+    pub bio: String,
+    pub image: Option<String>,
+    pub following: bool,
+    pub role: AuthorRole,
+}
+
+/// An author as seen by the article's other authors, including pending invitations.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Coauthor {
+    pub username: String,
+    pub bio: String,
+    pub image: Option<String>,
+    pub role: AuthorRole,
+    /// `true` if they haven't accepted their invitation yet.
+    pub pending: bool,
+    /// `null` for the owner, or if the inviting user was deleted.
+    pub invited_by: Option<String>,
+    pub created_at: Timestamptz,
+}
+
+/// An invitation to co-author an article, for `GET /api/user/invitations`.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Invitation {
+    pub slug: String,
+    pub title: String,
+    pub role: AuthorRole,
+    pub invited_by: Option<String>,
+    pub created_at: Timestamptz,
+}
+
+/// The article with the given slug and the current user's relationship to it.
+struct ArticleAccess {
+    article_id: Uuid,
+    /// `None` if the user isn't an author and hasn't been invited.
+    role: Option<AuthorRole>,
+    accepted: bool,
+}
+
+impl ArticleAccess {
+    fn is_author(&self) -> bool {
+        self.role.is_some() && self.accepted
+    }
+
+    fn is_owner(&self) -> bool {
+        self.role == Some(AuthorRole::Owner)
+    }
+}
+
+impl CoauthorController {
+    /// Everyone who is, or has been invited to be, an author of the article.
+    ///
+    /// Only visible to the article's authors; everyone else can see the accepted ones in
+    /// `Article.authors`.
+    pub async fn list_authors(&self, user_id: Uuid, slug: &str) -> Result<Vec<Coauthor>> {
+        let access = self.article_access(user_id, slug).await?;
+
+        if !access.is_author() {
+            return Err(Error::Forbidden);
+        }
+
+        self.authors_of(access.article_id).await
+    }
+
+    /// Invite `username` to co-author the article. Only the owner can do this.
+    pub async fn invite_author(
+        &self,
+        user_id: Uuid,
+        slug: &str,
+        username: &str,
+    ) -> Result<Vec<Coauthor>> {
+        let access = self.article_access(user_id, slug).await?;
+
+        if !access.is_owner() {
+            return Err(Error::Forbidden);
+        }
+
+        let invitee_id = sqlx::query_scalar!(
+            r#"select user_id from "user" where username = $1"#,
+            username
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| Error::unprocessable_entity([("username", "user not found")]))?;
+
+        let inserted = sqlx::query!(
+            r#"
+                insert into article_author (article_id, user_id, role, invited_by)
+                values ($1, $2, 'editor', $3)
+                on conflict (article_id, user_id) do nothing
+            "#,
+            access.article_id,
+            invitee_id,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if inserted.rows_affected() == 0 {
+            return Err(Error::unprocessable_entity([(
+                "username",
+                "already an author or invited",
+            )]));
+        }
+
+        self.authors_of(access.article_id).await
+    }
+
+    /// Accept the current user's pending invitation to co-author the article.
+    pub async fn accept_invitation(&self, user_id: Uuid, slug: &str) -> Result<Vec<Coauthor>> {
+        let access = self.article_access(user_id, slug).await?;
+
+        match (access.role, access.accepted) {
+            (None, _) => return Err(Error::NotFound),
+            // Accepting twice is harmless.
+            (Some(_), true) => (),
+            (Some(_), false) => {
+                sqlx::query!(
+                    r#"
+                        update article_author
+                        set accepted_at = now()
+                        where article_id = $1 and user_id = $2 and accepted_at is null
+                    "#,
+                    access.article_id,
+                    user_id
+                )
+                .execute(&self.pool)
+                .await?;
+            }
+        }
+
+        self.authors_of(access.article_id).await
+    }
+
+    /// Remove a co-author or cancel their invitation.
+    ///
+    /// The owner can remove anyone but themselves, and co-authors can remove themselves,
+    /// which is also how an invitation is declined.
+    pub async fn remove_author(&self, user_id: Uuid, slug: &str, username: &str) -> Result<()> {
+        let access = self.article_access(user_id, slug).await?;
+
+        let target = sqlx::query!(
+            r#"
+                select user_id, role "role: AuthorRole"
+                from article_author
+                inner join "user" using (user_id)
+                where article_id = $1 and username = $2
+            "#,
+            access.article_id,
+            username
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        if target.role == AuthorRole::Owner {
+            return Err(Error::unprocessable_entity([(
+                "username",
+                "the owner of an article can't be removed",
+            )]));
+        }
+
+        if target.user_id != user_id && !access.is_owner() {
+            return Err(Error::Forbidden);
+        }
+
+        sqlx::query!(
+            "delete from article_author where article_id = $1 and user_id = $2",
+            access.article_id,
+            target.user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// The current user's pending invitations, newest first.
+    pub async fn pending_invitations(&self, user_id: Uuid) -> Result<Vec<Invitation>> {
+        let invitations = sqlx::query_as!(
+            Invitation,
+            r#"
+                select
+                    article.slug,
+                    article.title,
+                    article_author.role "role: AuthorRole",
+                    inviter.username "invited_by?",
+                    article_author.created_at "created_at: Timestamptz"
+                from article_author
+                inner join article using (article_id)
+                left join "user" inviter on inviter.user_id = article_author.invited_by
+                where article_author.user_id = $1 and accepted_at is null and article.deleted_at is null
+                order by article_author.created_at desc
+            "#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(invitations)
+    }
+
+    async fn article_access(&self, user_id: Uuid, slug: &str) -> Result<ArticleAccess> {
+        let access = sqlx::query!(
+            r#"
+                select
+                    article.article_id,
+                    article_author.role "role?: AuthorRole",
+                    article_author.accepted_at is not null "accepted!"
+                from article
+                left join article_author
+                    on article_author.article_id = article.article_id and article_author.user_id = $2
+                where slug = $1 and article.deleted_at is null
+            "#,
+            slug,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        Ok(ArticleAccess {
+            article_id: access.article_id,
+            role: access.role,
+            accepted: access.accepted,
+        })
+    }
+
+    async fn authors_of(&self, article_id: Uuid) -> Result<Vec<Coauthor>> {
+        let authors = sqlx::query_as!(
+            Coauthor,
+            r#"
+                select
+                    author.username,
+                    author.bio,
+                    author.image,
+                    article_author.role "role: AuthorRole",
+                    article_author.accepted_at is null "pending!",
+                    inviter.username "invited_by?",
+                    article_author.created_at "created_at: Timestamptz"
+                from article_author
+                inner join "user" author using (user_id)
+                left join "user" inviter on inviter.user_id = article_author.invited_by
+                where article_author.article_id = $1
+                order by article_author.role = 'owner' desc, article_author.created_at
+            "#,
+            article_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(authors)
+    }
+}
@@ -1,27 +1,70 @@
+use crate::config::Config;
+use crate::filter::{ContentFilters, Filtered, Submission, SubmissionKind};
 use crate::http::types::Timestamptz;
 use crate::http::{Error, Result};
+use crate::markdown;
+use crate::models::listing::{limit_and_offset, MAX_LIMIT};
+use crate::models::moderation::{hold_content, HeldContent};
+use crate::models::notification::record_mentions;
 use crate::models::profile::Profile;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
 use futures::TryStreamExt;
-use sqlx::PgPool;
+use sqlx::{PgConnection, PgPool};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use time::OffsetDateTime;
 use uuid::Uuid;
 
+/// What's shown instead of a deleted comment that still has replies.
+const TOMBSTONE: &str = "[deleted]";
+
+/// How many replies each thread in a page of comments gets by default. See `CommentsQuery`.
+const DEFAULT_REPLIES: i64 = 10;
+
 #[derive(serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Comment {
     pub id: i64,
+    /// The comment this is a reply to, if any. Not in the Realworld spec.
+    pub parent_id: Option<i64>,
     pub created_at: Timestamptz,
     pub updated_at: Timestamptz,
     pub body: String,
-    pub author: Profile,
+    /// `body` rendered from Markdown and sanitized. Not in the Realworld spec.
+    pub body_html: String,
+    /// `null` if `deleted`.
+    pub author: Option<Profile>,
+    /// Whether this is all that's left of a deleted comment, which is kept around while it
+    /// still has replies. The `body` is just `[deleted]`. Not in the Realworld spec.
+    pub deleted: bool,
+    /// Whether the author has changed `body` since posting it. Not in the Realworld spec.
+    pub edited: bool,
+    /// Whether it's waiting for one of the article's authors to approve it, in which case only
+    /// they and the commenter can see it. Not in the Realworld spec.
+    pub pending: bool,
+    /// Only set for `?format=tree`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replies: Option<Vec<Comment>>,
+    /// How many more replies there are to this thread, however deep, than made it onto the
+    /// page; see `CommentsQuery::replies`. Only set on top-level comments, and only if any
+    /// were left out. Not in the Realworld spec.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub more_replies: Option<i64>,
 }
 
 // Same thing as `ArticleFromQuery`
 pub struct CommentFromQuery {
     pub comment_id: i64,
+    pub parent_comment_id: Option<i64>,
     pub created_at: OffsetDateTime,
     pub updated_at: OffsetDateTime,
     pub body: String,
+    /// Deleted by its author or hidden by a moderator or the article's authors,
+    /// but there are replies to it.
+    pub removed: bool,
+    pub edited: bool,
+    pub pending: bool,
     pub author_username: String,
     pub author_bio: String,
     pub author_image: Option<String>,
@@ -30,30 +73,205 @@ pub struct CommentFromQuery {
 
 impl CommentFromQuery {
     pub fn into_comment(self) -> Comment {
+        // Hidden comments are shown the same as deleted ones, so as not to give away who
+        // was involved, or what it said.
+        let (body, author) = if self.removed {
+            (TOMBSTONE.to_string(), None)
+        } else {
+            (
+                self.body,
+                Some(Profile {
+                    username: self.author_username,
+                    bio: self.author_bio,
+                    image: self.author_image,
+                    following: self.following_author,
+                }),
+            )
+        };
+
         Comment {
             id: self.comment_id,
+            parent_id: self.parent_comment_id,
             // doing this conversion in-code does save having to use the type overrides in query
             created_at: Timestamptz(self.created_at),
             updated_at: Timestamptz(self.updated_at),
-            body: self.body,
-            author: Profile {
-                username: self.author_username,
-                bio: self.author_bio,
-                image: self.author_image,
-                following: self.following_author,
-            },
+            // Comments are short enough that caching them isn't worth the memory.
+            body_html: markdown::render(&body),
+            body,
+            author,
+            deleted: self.removed,
+            edited: self.edited,
+            pending: self.pending,
+            replies: None,
+            more_replies: None,
         }
     }
 }
 
+/// Who can comment on an article, and who can see the comments. Set by the article's authors.
+#[derive(
+    serde::Serialize, serde::Deserialize, sqlx::Type, Copy, Clone, Debug, Default, PartialEq, Eq,
+)]
+// Stored as `text` like `AuthorRole`.
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum CommentsMode {
+    #[default]
+    Open,
+    /// New comments are only shown to the commenter and the article's authors until one of
+    /// the authors approves them. The authors' own comments don't need approving.
+    ApprovalRequired,
+    /// No new comments, but the existing ones are still shown.
+    Locked,
+    /// No new comments, and the existing ones are hidden until comments are turned back on.
+    Disabled,
+}
+
+impl CommentsMode {
+    /// Fail unless comments can be posted or edited.
+    fn require_writable(self) -> Result<()> {
+        match self {
+            CommentsMode::Open | CommentsMode::ApprovalRequired => Ok(()),
+            CommentsMode::Locked => Err(Error::unprocessable_entity([(
+                "comments",
+                "are locked on this article",
+            )])),
+            CommentsMode::Disabled => Err(Error::unprocessable_entity([(
+                "comments",
+                "are turned off for this article",
+            )])),
+        }
+    }
+}
+
+/// How `GET /api/articles/:slug/comments` lays out replies.
+#[derive(serde::Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CommentFormat {
+    /// Every comment in one list, oldest first, with `parentId` to tell what's a reply to what.
+    /// This is what the Realworld spec expects.
+    #[default]
+    Flat,
+    /// Only top-level comments, each with its `replies`, and so on.
+    Tree,
+}
+
+#[derive(serde::Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CommentSort {
+    /// What the Realworld spec expects.
+    #[default]
+    Oldest,
+    Newest,
+    /// Most replies first, counting only direct replies. Doesn't support cursors.
+    Top,
+}
+
+impl CommentSort {
+    /// What the listing query expects in its `sort` parameter.
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Oldest => "oldest",
+            Self::Newest => "newest",
+            Self::Top => "top",
+        }
+    }
+}
+
+// Pages are of top-level comments, each with the first few of its replies, and `limit` only
+// counts the top-level ones. The rest of a thread's replies can be fetched with `get_replies()`.
+//
+// Same as `ListArticlesQuery`, `offset` is ignored if `cursor` is set. Unlike article listings,
+// `limit` defaults to the maximum, as the Realworld spec expects every comment in one response
+// and most articles don't have anywhere near that many.
+#[derive(serde::Deserialize, Default)]
+#[serde(default)]
+pub struct CommentsQuery {
+    pub format: CommentFormat,
+    pub sort: CommentSort,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    pub cursor: Option<String>,
+    /// How many replies to include in each thread, oldest first, from 0 up to `MAX_LIMIT`.
+    /// Defaults to `DEFAULT_REPLIES`.
+    pub replies: Option<i64>,
+}
+
+/// For `get_replies()`. `limit` defaults to the maximum, like `CommentsQuery`.
+#[derive(serde::Deserialize, Default)]
+#[serde(default)]
+pub struct RepliesQuery {
+    pub format: CommentFormat,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+pub struct RepliesPage {
+    pub comments: Vec<Comment>,
+    /// How many more replies there are after this page.
+    pub more_replies: i64,
+}
+
+/// A page of comments, along with a cursor for the next one.
+pub struct CommentPage {
+    pub comments: Vec<Comment>,
+    /// Only for `oldest` and `newest`.
+    pub next_cursor: Option<String>,
+    /// Every comment on the article, same as `Article.commentsCount`.
+    pub total_count: i64,
+}
+
+/// The last top-level comment of a page, for `CommentPage::next_cursor`.
+///
+/// Like `ArticleCursor`, this is opaque to clients, though there's no need to go backwards.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct CommentCursor {
+    created_at: OffsetDateTime,
+    comment_id: i64,
+}
+
+impl CommentCursor {
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!(
+            "{}:{}",
+            self.created_at.unix_timestamp_nanos(),
+            self.comment_id
+        ))
+    }
+
+    fn decode(cursor: &str) -> Result<Self> {
+        let invalid = || Error::unprocessable_entity([("cursor", "invalid cursor")]);
+
+        let decoded = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?;
+        let decoded = std::str::from_utf8(&decoded).map_err(|_| invalid())?;
+
+        let (created_at, comment_id) = decoded.split_once(':').ok_or_else(invalid)?;
+
+        Ok(Self {
+            created_at: created_at
+                .parse()
+                .ok()
+                .and_then(|nanos| OffsetDateTime::from_unix_timestamp_nanos(nanos).ok())
+                .ok_or_else(invalid)?,
+            comment_id: comment_id.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
 #[derive(Clone)]
 pub struct CommentController {
     pool: PgPool,
+    config: Arc<Config>,
+    filters: Arc<ContentFilters>,
 }
 
 impl CommentController {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+    pub fn new(pool: PgPool, config: Arc<Config>, filters: Arc<ContentFilters>) -> Self {
+        Self {
+            pool,
+            config,
+            filters,
+        }
     }
 }
 
@@ -62,57 +280,355 @@ impl CommentController {
         &self,
         maybe_auth_user: Option<Uuid>,
         slug: &str,
-    ) -> Result<Vec<Comment>> {
-        let article_id =
-            sqlx::query_scalar!("select article_id from article where slug = $1", slug)
-                .fetch_optional(&self.pool)
-                .await?
-                .ok_or(Error::NotFound)?;
+        query: CommentsQuery,
+    ) -> Result<CommentPage> {
+        let (limit, offset) = limit_and_offset(query.limit.or(Some(MAX_LIMIT)), query.offset)?;
+        let cursor = query
+            .cursor
+            .as_deref()
+            .map(CommentCursor::decode)
+            .transpose()?;
+        let offset = if cursor.is_some() { 0 } else { offset };
+        let replies = query.replies.unwrap_or(DEFAULT_REPLIES);
 
-        let comments = sqlx::query_as!(
-            CommentFromQuery,
+        if !(0..=MAX_LIMIT).contains(&replies) {
+            return Err(Error::unprocessable_entity([(
+                "replies",
+                format!("must be between 0 and {MAX_LIMIT}"),
+            )]));
+        }
+
+        if cursor.is_some() && query.sort == CommentSort::Top {
+            return Err(Error::unprocessable_entity([(
+                "cursor",
+                "only supported when sorting by newest or oldest",
+            )]));
+        }
+
+        let article = sqlx::query!(
             r#"
                 select
-                    comment_id,
-                    comment.created_at,
-                    comment.updated_at,
-                    comment.body,
-                    author.username author_username,
-                    author.bio author_bio,
-                    author.image author_image,
-                    exists(select 1 from follow where followed_user_id = author.user_id and following_user_id = $1) "following_author!"
+                    article_id,
+                    comments_mode "comments_mode: CommentsMode",
+                    article_comments_count(article_id) "comments_count!",
+                    exists(
+                        select 1
+                        from article_author
+                        where article_author.article_id = article.article_id
+                          and user_id = $2
+                          and accepted_at is not null
+                    ) "can_moderate!"
+                from article
+                where slug = $1 and deleted_at is null
+            "#,
+            slug,
+            maybe_auth_user
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        if article.comments_mode == CommentsMode::Disabled {
+            return Ok(CommentPage {
+                comments: vec![],
+                next_cursor: None,
+                total_count: 0,
+            });
+        }
+
+        // Deleted and hidden comments with replies are still listed, as `[deleted]`. This can let
+        // through one whose replies are all deleted or hidden too, which `prune_removed()` then
+        // drops, leaving the page a bit short.
+        //
+        // Pending comments are left out altogether for everyone but the commenter and the
+        // article's authors; nobody else can reply to them, so they can't have replies to keep.
+        //
+        // One more than `limit` is fetched to tell whether there's a next page.
+        let mut top_level = sqlx::query!(
+            r#"
+                select comment_id, created_at
                 from article_comment comment
-                inner join "user" author using (user_id)
-                where article_id = $2
-                order by created_at
+                where article_id = $1
+                  and parent_comment_id is null
+                  and (
+                      (deleted_at is null and hidden_at is null and hidden_by_author_at is null)
+                      or exists(select 1 from article_comment reply where reply.parent_comment_id = comment.comment_id)
+                  )
+                  and (not pending or user_id = $7 or $8)
+                  and (
+                      $3::timestamptz is null
+                      or (
+                          case when $2 = 'newest'
+                              then (created_at, comment_id) < ($3, $4)
+                              else (created_at, comment_id) > ($3, $4)
+                          end
+                      )
+                  )
+                order by
+                    case when $2 = 'top' then (
+                        select count(*)
+                        from article_comment reply
+                        where reply.parent_comment_id = comment.comment_id
+                          and reply.deleted_at is null
+                          and reply.hidden_at is null
+                          and reply.hidden_by_author_at is null
+                          and not reply.pending
+                    ) end desc,
+                    case when $2 = 'newest' then created_at end desc,
+                    case when $2 = 'newest' then comment_id end desc,
+                    created_at,
+                    comment_id
+                limit $5
+                offset $6
             "#,
+            article.article_id,
+            query.sort.as_str(),
+            cursor.map(|cursor| cursor.created_at),
+            cursor.map(|cursor| cursor.comment_id),
+            limit + 1,
+            offset,
             maybe_auth_user,
-            article_id
+            article.can_moderate
         )
-        .fetch(&self.pool)
-        .map_ok(CommentFromQuery::into_comment)
-        .try_collect()
+        .fetch_all(&self.pool)
         .await?;
 
-        Ok(comments)
+        let has_more = top_level.len() as i64 > limit;
+        top_level.truncate(limit as usize);
+
+        let next_cursor = top_level
+            .last()
+            .filter(|_| has_more && query.sort != CommentSort::Top)
+            .map(|last| {
+                CommentCursor {
+                    created_at: last.created_at,
+                    comment_id: last.comment_id,
+                }
+                .encode()
+            });
+
+        let thread_ids: Vec<i64> = top_level.iter().map(|row| row.comment_id).collect();
+
+        let (mut comments, more_replies) = self
+            .fetch_threads(
+                maybe_auth_user,
+                article.can_moderate,
+                &thread_ids,
+                true,
+                0,
+                replies,
+            )
+            .await?;
+
+        for comment in &mut comments {
+            comment.more_replies = more_replies.get(&comment.id).copied().filter(|&n| n > 0);
+        }
+
+        let comments = prune_removed(comments);
+
+        Ok(CommentPage {
+            comments: match query.format {
+                CommentFormat::Flat => comments,
+                CommentFormat::Tree => into_tree(comments),
+            },
+            next_cursor,
+            total_count: article.comments_count,
+        })
     }
 
-    pub async fn create_comment(&self, user_id: Uuid, slug: &str, body: &str) -> Result<Comment> {
+    /// Replies to a comment, however deep, oldest first, for when `get_article_comments()`
+    /// didn't include all of them.
+    ///
+    /// In `?format=tree`, any whose parent is on an earlier page are at the top level.
+    pub async fn get_replies(
+        &self,
+        maybe_auth_user: Option<Uuid>,
+        slug: &str,
+        comment_id: i64,
+        query: RepliesQuery,
+    ) -> Result<RepliesPage> {
+        let (limit, offset) = limit_and_offset(query.limit.or(Some(MAX_LIMIT)), query.offset)?;
+
+        let comment = sqlx::query!(
+            r#"
+                select
+                    comment.user_id,
+                    comment.pending,
+                    article.comments_mode "comments_mode: CommentsMode",
+                    exists(
+                        select 1
+                        from article_author
+                        where article_author.article_id = article.article_id
+                          and article_author.user_id = $3
+                          and accepted_at is not null
+                    ) "can_moderate!"
+                from article_comment comment
+                inner join article using (article_id)
+                where comment_id = $1 and slug = $2 and article.deleted_at is null
+            "#,
+            comment_id,
+            slug,
+            maybe_auth_user
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        // Same as in `get_article_comments()`.
+        if comment.pending && maybe_auth_user != Some(comment.user_id) && !comment.can_moderate {
+            return Err(Error::NotFound);
+        }
+
+        if comment.comments_mode == CommentsMode::Disabled {
+            return Ok(RepliesPage {
+                comments: vec![],
+                more_replies: 0,
+            });
+        }
+
+        let (comments, more_replies) = self
+            .fetch_threads(
+                maybe_auth_user,
+                comment.can_moderate,
+                &[comment_id],
+                false,
+                offset,
+                limit,
+            )
+            .await?;
+
+        let comments = prune_removed(comments);
+
+        Ok(RepliesPage {
+            comments: match query.format {
+                CommentFormat::Flat => comments,
+                CommentFormat::Tree => into_tree(comments),
+            },
+            more_replies: more_replies.get(&comment_id).copied().unwrap_or_default(),
+        })
+    }
+
+    /// Comment on an article, or reply to another comment on it if `parent_id` is set.
+    ///
+    /// The comment goes through the content filters first, which may hold it for moderation.
+    /// If the article's `comments_mode` is `approval_required`, it's also pending until one of
+    /// the article's authors approves it; either way, `Filtered::held` is set.
+    pub async fn create_comment(
+        &self,
+        user_id: Uuid,
+        slug: &str,
+        parent_id: Option<i64>,
+        body: &str,
+    ) -> Result<Filtered<Comment>> {
+        let mut tx = self.pool.begin().await?;
+
+        // Looked up first so a missing article is a `404` rather than whatever the filters say.
+        let article = sqlx::query!(
+            r#"
+                select
+                    article_id,
+                    comments_mode "comments_mode: CommentsMode",
+                    exists(
+                        select 1
+                        from article_author
+                        where article_author.article_id = article.article_id
+                          and user_id = $2
+                          and accepted_at is not null
+                    ) "is_author!"
+                from article
+                where slug = $1 and deleted_at is null
+            "#,
+            slug,
+            user_id
+        )
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        article.comments_mode.require_writable()?;
+
+        let pending = article.comments_mode == CommentsMode::ApprovalRequired && !article.is_author;
+
+        let depth = match parent_id {
+            Some(parent_id) => {
+                let parent = sqlx::query!(
+                    r#"
+                        select
+                            depth,
+                            deleted_at is not null
+                                or hidden_at is not null
+                                or hidden_by_author_at is not null "removed!",
+                            pending
+                        from article_comment
+                        where comment_id = $1 and article_id = $2
+                    "#,
+                    parent_id,
+                    article.article_id
+                )
+                .fetch_optional(&mut tx)
+                .await?
+                .ok_or(Error::NotFound)?;
+
+                if parent.removed {
+                    return Err(Error::unprocessable_entity([(
+                        "parentId",
+                        "can't reply to a deleted comment",
+                    )]));
+                }
+
+                if parent.pending {
+                    return Err(Error::unprocessable_entity([(
+                        "parentId",
+                        "can't reply to a comment that's waiting for approval",
+                    )]));
+                }
+
+                if parent.depth >= self.config.max_comment_depth {
+                    return Err(Error::unprocessable_entity([(
+                        "parentId",
+                        format!(
+                            "replies can't be nested more than {} deep",
+                            self.config.max_comment_depth
+                        ),
+                    )]));
+                }
+
+                parent.depth + 1
+            }
+            None => 0,
+        };
+
+        let hold = self
+            .filters
+            .check(
+                &mut tx,
+                &Submission {
+                    kind: SubmissionKind::Comment,
+                    author_id: user_id,
+                    article_id: None,
+                    comment_id: None,
+                    fields: vec![("body", body)],
+                },
+            )
+            .await?;
+
         let comment = sqlx::query_as!(
             CommentFromQuery,
             r#"
                 with inserted_comment as (
-                    insert into article_comment(article_id, user_id, body)
-                    select article_id, $1, $2
-                    from article
-                    where slug = $3
-                    returning comment_id, created_at, updated_at, body
+                    insert into article_comment(article_id, user_id, body, parent_comment_id, depth, pending)
+                    values ($3, $1, $2, $4, $5, $6)
+                    returning comment_id, parent_comment_id, created_at, updated_at, body, pending
                 )
                 select
                     comment_id,
+                    parent_comment_id,
                     comment.created_at,
                     comment.updated_at,
                     body,
+                    false "removed!",
+                    false "edited!",
+                    pending,
                     author.username author_username,
                     author.bio author_bio,
                     author.image author_image,
@@ -122,50 +638,870 @@ impl CommentController {
             "#,
             user_id,
             body,
+            article.article_id,
+            parent_id,
+            depth,
+            pending
+        )
+        .fetch_one(&mut tx)
+        .await?;
+
+        record_mentions(
+            &mut tx,
+            user_id,
+            article.article_id,
+            Some(comment.comment_id),
+            body,
+        )
+        .await?;
+
+        if let Some(reason) = &hold {
+            hold_content(
+                &mut tx,
+                HeldContent::Comment(comment.comment_id),
+                user_id,
+                reason,
+            )
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(Filtered {
+            value: comment.into_comment(),
+            held: hold.is_some() || pending,
+        })
+    }
+
+    /// Edit one of your own comments, keeping the old version for moderators.
+    ///
+    /// Like `create_comment()`, the new body goes through the content filters, and it's not
+    /// allowed if comments are locked or turned off on the article.
+    pub async fn update_comment(
+        &self,
+        user_id: Uuid,
+        slug: &str,
+        comment_id: i64,
+        body: &str,
+    ) -> Result<Filtered<Comment>> {
+        let mut tx = self.pool.begin().await?;
+
+        // Deleted and hidden comments can't be edited as they're `[deleted]` to everyone else.
+        let comment = sqlx::query!(
+            r#"
+                select
+                    comment.user_id,
+                    comment.article_id,
+                    comment.body,
+                    article.comments_mode "comments_mode: CommentsMode",
+                    coalesce(comment.edited_at, comment.created_at) "written_at!"
+                from article_comment comment
+                inner join article using (article_id)
+                where comment_id = $1
+                  and slug = $2
+                  and article.deleted_at is null
+                  and comment.deleted_at is null
+                  and comment.hidden_at is null
+                  and comment.hidden_by_author_at is null
+                for update of comment
+            "#,
+            comment_id,
             slug
         )
-        .fetch_optional(&self.pool)
+        .fetch_optional(&mut tx)
         .await?
-        // In this case, we know a comment should have been inserted unless the article slug
-        // was not found.
-        .ok_or(Error::NotFound)?
-        .into_comment();
+        .ok_or(Error::NotFound)?;
+
+        if comment.user_id != user_id {
+            return Err(Error::Forbidden);
+        }
+
+        // Same as posting a new one, otherwise locking an article wouldn't stop anyone.
+        comment.comments_mode.require_writable()?;
+
+        // Saving without changing anything isn't an edit, and shouldn't trip the filters if
+        // they've changed since the comment was posted.
+        let changed = comment.body != body;
+
+        let hold = if changed {
+            let hold = self
+                .filters
+                .check(
+                    &mut tx,
+                    &Submission {
+                        kind: SubmissionKind::Comment,
+                        author_id: user_id,
+                        article_id: None,
+                        comment_id: Some(comment_id),
+                        fields: vec![("body", body)],
+                    },
+                )
+                .await?;
+
+            sqlx::query!(
+                r#"
+                    insert into article_comment_edit (comment_id, body, written_at)
+                    values ($1, $2, $3)
+                "#,
+                comment_id,
+                comment.body,
+                comment.written_at
+            )
+            .execute(&mut tx)
+            .await?;
+
+            record_mentions(&mut tx, user_id, comment.article_id, Some(comment_id), body).await?;
+
+            hold
+        } else {
+            None
+        };
 
-        Ok(comment)
+        let updated = sqlx::query_as!(
+            CommentFromQuery,
+            r#"
+                with updated_comment as (
+                    update article_comment
+                    set
+                        body = $2,
+                        edited_at = case when $3 then now() else edited_at end
+                    where comment_id = $1
+                    returning comment_id, parent_comment_id, created_at, updated_at, body, edited_at, pending
+                )
+                select
+                    comment_id,
+                    parent_comment_id,
+                    comment.created_at,
+                    comment.updated_at,
+                    body,
+                    false "removed!",
+                    comment.edited_at is not null "edited!",
+                    pending,
+                    author.username author_username,
+                    author.bio author_bio,
+                    author.image author_image,
+                    false "following_author!"
+                from updated_comment comment
+                inner join "user" author on user_id = $4
+            "#,
+            comment_id,
+            body,
+            changed,
+            user_id
+        )
+        .fetch_one(&mut tx)
+        .await?;
+
+        if let Some(reason) = &hold {
+            hold_content(&mut tx, HeldContent::Comment(comment_id), user_id, reason).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(Filtered {
+            value: updated.into_comment(),
+            held: hold.is_some(),
+        })
     }
 
+    /// Delete one of your own comments, or any comment on an article you're an author of.
+    ///
+    /// If anyone's replied to it, it's left in place as `[deleted]` until they've all gone.
     pub async fn delete_comment(&self, user_id: Uuid, slug: &str, comment_id: i64) -> Result<()> {
-        let result = sqlx::query!(
+        let mut tx = self.pool.begin().await?;
+
+        let comment = sqlx::query!(
             r#"
-                with deleted_comment as (
-                    delete from article_comment
-                    where 
-                        comment_id = $1
-                        and article_id in (select article_id from article where slug = $2)
-                        and user_id = $3
-                    returning 1 
-                )
-                select 
+                select
+                    comment.user_id,
+                    comment.parent_comment_id,
                     exists(
-                        select 1 from article_comment
-                        inner join article using (article_id)
-                        where comment_id = $1 and slug = $2
-                    ) "existed!",
-                    exists(select 1 from deleted_comment) "deleted!"
+                        select 1 from article_comment reply where reply.parent_comment_id = $1
+                    ) "has_replies!",
+                    exists(
+                        select 1
+                        from article_author
+                        where article_author.article_id = article.article_id
+                          and article_author.user_id = $3
+                          and accepted_at is not null
+                    ) "can_moderate!"
+                from article_comment comment
+                inner join article using (article_id)
+                where comment_id = $1
+                  and slug = $2
+                  and article.deleted_at is null
+                  and comment.deleted_at is null
+                for update of comment
             "#,
             comment_id,
             slug,
             user_id
         )
-        .fetch_one(&self.pool)
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        if comment.user_id != user_id && !comment.can_moderate {
+            return Err(Error::Forbidden);
+        }
+
+        if comment.has_replies {
+            // Blank the body as it's meant to be gone, even if the comment itself isn't,
+            // and the same goes for its edit history and who it mentioned.
+            sqlx::query!(
+                "update article_comment set deleted_at = now(), body = '' where comment_id = $1",
+                comment_id
+            )
+            .execute(&mut tx)
+            .await?;
+
+            sqlx::query!(
+                "delete from article_comment_edit where comment_id = $1",
+                comment_id
+            )
+            .execute(&mut tx)
+            .await?;
+
+            sqlx::query!("delete from mention where comment_id = $1", comment_id)
+                .execute(&mut tx)
+                .await?;
+        } else {
+            sqlx::query!(
+                "delete from article_comment where comment_id = $1",
+                comment_id
+            )
+            .execute(&mut tx)
+            .await?;
+
+            // That may have been the last reply to a deleted comment, which can go too now,
+            // and so on up the thread.
+            let mut parent_id = comment.parent_comment_id;
+
+            while let Some(comment_id) = parent_id {
+                parent_id = sqlx::query_scalar!(
+                    r#"
+                        delete from article_comment
+                        where comment_id = $1
+                          and deleted_at is not null
+                          and not exists(
+                              select 1 from article_comment reply where reply.parent_comment_id = $1
+                          )
+                        returning parent_comment_id
+                    "#,
+                    comment_id
+                )
+                .fetch_optional(&mut tx)
+                .await?
+                .flatten();
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Let a pending comment through. Only for the article's authors.
+    pub async fn approve_comment(&self, user_id: Uuid, slug: &str, comment_id: i64) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        self.require_article_author(&mut tx, user_id, slug, comment_id)
+            .await?;
+
+        sqlx::query!(
+            "update article_comment set pending = false where comment_id = $1",
+            comment_id
+        )
+        .execute(&mut tx)
         .await?;
 
-        if result.deleted {
-            Ok(())
-        } else if result.existed {
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Hide or unhide a comment, which shows it as `[deleted]`. Only for the article's authors.
+    ///
+    /// This is separate from moderators hiding comments; neither can undo the other.
+    pub async fn set_comment_hidden(
+        &self,
+        user_id: Uuid,
+        slug: &str,
+        comment_id: i64,
+        hidden: bool,
+    ) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        self.require_article_author(&mut tx, user_id, slug, comment_id)
+            .await?;
+
+        sqlx::query!(
+            r#"
+                update article_comment
+                set hidden_by_author_at = case when $2 then coalesce(hidden_by_author_at, now()) end
+                where comment_id = $1
+            "#,
+            comment_id,
+            hidden
+        )
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// The comments in each of `thread_ids` and their replies, however deep, in the same order
+    /// as `thread_ids`, with each thread oldest first.
+    ///
+    /// Only the `offset`th to `offset + limit`th reply in each thread is included, along with
+    /// the comment it starts with if `include_roots` is set. Also returns how many more replies
+    /// each thread has after those, for threads with any rows at all.
+    async fn fetch_threads(
+        &self,
+        maybe_auth_user: Option<Uuid>,
+        can_moderate: bool,
+        thread_ids: &[i64],
+        include_roots: bool,
+        offset: i64,
+        limit: i64,
+    ) -> Result<(Vec<Comment>, HashMap<i64, i64>)> {
+        let mut more_replies = HashMap::new();
+
+        // Replies always come after what they're replying to, so taking the oldest ones never
+        // leaves one without its parent.
+        let comments = sqlx::query!(
+            r#"
+                with recursive thread as (
+                    select comment_id, comment_id thread_id
+                    from article_comment
+                    where comment_id = any($2)
+
+                    union all
+
+                    select reply.comment_id, thread.thread_id
+                    from article_comment reply
+                    inner join thread on reply.parent_comment_id = thread.comment_id
+                    where not reply.pending or reply.user_id = $1 or $3
+                ),
+                numbered as (
+                    select
+                        thread.thread_id,
+                        comment.*,
+                        -- The comment the thread starts with is always `0`.
+                        row_number() over (
+                            partition by thread.thread_id
+                            order by comment.created_at, comment.comment_id
+                        ) - 1 "position",
+                        count(*) over (partition by thread.thread_id) - 1 replies_count
+                    from thread
+                    inner join article_comment comment using (comment_id)
+                    -- Deleted and hidden replies that nothing's replying to would only be dropped
+                    -- by `prune_removed()`, so they shouldn't count or take up room either.
+                    where comment.comment_id = thread.thread_id
+                       or (
+                           comment.deleted_at is null
+                           and comment.hidden_at is null
+                           and comment.hidden_by_author_at is null
+                       )
+                       or exists(
+                           select 1 from article_comment reply where reply.parent_comment_id = comment.comment_id
+                       )
+                )
+                select
+                    comment.thread_id "thread_id!",
+                    comment.comment_id "comment_id!",
+                    comment.parent_comment_id,
+                    comment.created_at "created_at!",
+                    comment.updated_at "updated_at!",
+                    comment.body "body!",
+                    comment.deleted_at is not null
+                        or comment.hidden_at is not null
+                        or comment.hidden_by_author_at is not null "removed!",
+                    comment.edited_at is not null "edited!",
+                    comment.pending "pending!",
+                    greatest(comment.replies_count - $5 - $6, 0) "more_replies!",
+                    author.username author_username,
+                    author.bio author_bio,
+                    author.image author_image,
+                    exists(select 1 from follow where followed_user_id = author.user_id and following_user_id = $1) "following_author!"
+                from numbered comment
+                inner join "user" author on author.user_id = comment.user_id
+                where (comment.position = 0 and $4)
+                   or (comment.position > $5 and comment.position <= $5 + $6)
+                order by array_position($2, comment.thread_id), comment.position
+            "#,
+            maybe_auth_user,
+            thread_ids,
+            can_moderate,
+            include_roots,
+            offset,
+            limit
+        )
+        .fetch(&self.pool)
+        .map_ok(|row| {
+            more_replies.insert(row.thread_id, row.more_replies);
+
+            CommentFromQuery {
+                comment_id: row.comment_id,
+                parent_comment_id: row.parent_comment_id,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+                body: row.body,
+                removed: row.removed,
+                edited: row.edited,
+                pending: row.pending,
+                author_username: row.author_username,
+                author_bio: row.author_bio,
+                author_image: row.author_image,
+                following_author: row.following_author,
+            }
+            .into_comment()
+        })
+        .try_collect()
+        .await?;
+
+        Ok((comments, more_replies))
+    }
+
+    /// Fail unless the comment exists, and the user is one of the authors of the article it's on.
+    ///
+    /// Locks the comment until the end of the transaction.
+    async fn require_article_author(
+        &self,
+        conn: &mut PgConnection,
+        user_id: Uuid,
+        slug: &str,
+        comment_id: i64,
+    ) -> Result<()> {
+        let can_moderate = sqlx::query_scalar!(
+            r#"
+                select exists(
+                    select 1
+                    from article_author
+                    where article_author.article_id = article.article_id
+                      and article_author.user_id = $3
+                      and accepted_at is not null
+                ) "can_moderate!"
+                from article_comment comment
+                inner join article using (article_id)
+                where comment_id = $1
+                  and slug = $2
+                  and article.deleted_at is null
+                  and comment.deleted_at is null
+                for update of comment
+            "#,
+            comment_id,
+            slug,
+            user_id
+        )
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        if !can_moderate {
+            return Err(Error::Forbidden);
+        }
+
+        Ok(())
+    }
+}
+
+/// Leave out deleted and hidden comments, unless they have replies that aren't.
+///
+/// Every reply has to come after the comment it's replying to.
+fn prune_removed(comments: Vec<Comment>) -> Vec<Comment> {
+    // Replies always come after what they're replying to, so going backwards, we know whether
+    // a comment has any replies left by the time we get to it.
+    let mut has_replies = HashSet::new();
+
+    let mut kept: Vec<Comment> = comments
+        .into_iter()
+        .rev()
+        .filter(|comment| {
+            let keep = !comment.deleted || has_replies.contains(&comment.id);
+
+            if keep {
+                has_replies.extend(comment.parent_id);
+            }
+
+            keep
+        })
+        .collect();
+
+    kept.reverse();
+    kept
+}
+
+/// Nest replies under the comments they're replying to.
+///
+/// Every reply has to come after the comment it's replying to. Replies to comments that aren't
+/// in `comments` are left at the top level.
+fn into_tree(comments: Vec<Comment>) -> Vec<Comment> {
+    let ids: HashSet<i64> = comments.iter().map(|comment| comment.id).collect();
+
+    // Same trick as `prune_removed()`: going backwards, a comment's replies have all been
+    // collected by the time we get to it.
+    let mut replies: HashMap<i64, Vec<Comment>> = HashMap::new();
+    let mut top_level = Vec::new();
+
+    for mut comment in comments.into_iter().rev() {
+        let mut own_replies = replies.remove(&comment.id).unwrap_or_default();
+        own_replies.reverse();
+        comment.replies = Some(own_replies);
+
+        match comment
+            .parent_id
+            .filter(|parent_id| ids.contains(parent_id))
+        {
+            Some(parent_id) => replies.entry(parent_id).or_default().push(comment),
+            None => top_level.push(comment),
+        }
+    }
+
+    top_level.reverse();
+    top_level
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::article::UpdateArticle;
+    use crate::models::testing::{config, create_article, create_moderator, create_user, store};
+    use crate::models::{Store, StoreTrait};
+
+    fn comment(id: i64, parent_id: Option<i64>, deleted: bool) -> Comment {
+        Comment {
+            id,
+            parent_id,
+            created_at: Timestamptz(OffsetDateTime::UNIX_EPOCH),
+            updated_at: Timestamptz(OffsetDateTime::UNIX_EPOCH),
+            body: String::new(),
+            body_html: String::new(),
+            author: None,
+            deleted,
+            edited: false,
+            pending: false,
+            replies: None,
+            more_replies: None,
+        }
+    }
+
+    fn ids(comments: &[Comment]) -> Vec<i64> {
+        comments.iter().map(|comment| comment.id).collect()
+    }
+
+    #[test]
+    fn threads() {
+        // 1
+        //   2 (deleted)
+        //     3
+        //   4 (deleted)
+        // 5 (deleted)
+        //   6 (deleted)
+        // 7
+        //   8
+        let comments = vec![
+            comment(1, None, false),
+            comment(2, Some(1), true),
+            comment(3, Some(2), false),
+            comment(4, Some(1), true),
+            comment(5, None, true),
+            comment(6, Some(5), true),
+            comment(7, None, false),
+            comment(8, Some(7), false),
+        ];
+
+        let comments = prune_removed(comments);
+        assert_eq!(ids(&comments), [1, 2, 3, 7, 8]);
+
+        let tree = into_tree(comments);
+        assert_eq!(ids(&tree), [1, 7]);
+
+        let replies = tree[0].replies.as_deref().unwrap();
+        assert_eq!(ids(replies), [2]);
+        assert_eq!(ids(replies[0].replies.as_deref().unwrap()), [3]);
+        assert_eq!(ids(tree[1].replies.as_deref().unwrap()), [8]);
+
+        // A later page of replies, where 2 was on the page before.
+        let tree = into_tree(vec![comment(3, Some(2), false), comment(9, Some(3), false)]);
+        assert_eq!(ids(&tree), [3]);
+        assert_eq!(ids(tree[0].replies.as_deref().unwrap()), [9]);
+    }
+
+    #[test]
+    fn writable_comments_modes() {
+        assert!(CommentsMode::Open.require_writable().is_ok());
+        assert!(CommentsMode::ApprovalRequired.require_writable().is_ok());
+
+        for mode in [CommentsMode::Locked, CommentsMode::Disabled] {
+            assert!(matches!(
+                mode.require_writable(),
+                Err(Error::UnprocessableEntity { errors }) if errors.contains_key("comments")
+            ));
+        }
+    }
+
+    #[test]
+    fn cursor_round_trip() {
+        let cursor = CommentCursor {
+            created_at: OffsetDateTime::from_unix_timestamp_nanos(1_700_000_000_123_456_000)
+                .unwrap(),
+            comment_id: 42,
+        };
+
+        assert_eq!(CommentCursor::decode(&cursor.encode()).unwrap(), cursor);
+
+        assert!(CommentCursor::decode("").is_err());
+        assert!(CommentCursor::decode(&URL_SAFE_NO_PAD.encode("123")).is_err());
+    }
+
+    async fn comments(store: &Store, slug: &str) -> Vec<Comment> {
+        store
+            .comment()
+            .get_article_comments(None, slug, CommentsQuery::default())
+            .await
+            .unwrap()
+            .comments
+    }
+
+    #[sqlx::test]
+    async fn replies_outlive_deleted_users(pool: PgPool) {
+        let store = store(pool.clone(), config());
+        let alice = create_user(&pool, "alice").await;
+        let bob = create_user(&pool, "bob").await;
+        let carol = create_user(&pool, "carol").await;
+        create_article(&store, carol, "Threads").await;
+
+        let comment = |user_id, parent_id, body| {
+            let store = store.clone();
+            async move {
+                store
+                    .comment()
+                    .create_comment(user_id, "threads", parent_id, body)
+                    .await
+                    .unwrap()
+                    .value
+                    .id
+            }
+        };
+
+        let question = comment(alice, None, "A question.").await;
+        let answer = comment(bob, Some(question), "An answer.").await;
+
+        sqlx::query!(r#"delete from "user" where user_id = $1"#, alice)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let comments = comments(&store, "threads").await;
+        assert_eq!(ids(&comments), [answer]);
+        assert_eq!(comments[0].parent_id, None);
+    }
+
+    #[sqlx::test]
+    async fn edits_keep_history(pool: PgPool) {
+        let store = store(pool.clone(), config());
+        let alice = create_user(&pool, "alice").await;
+        let bob = create_user(&pool, "bob").await;
+        let moderator = create_moderator(&pool, "moderator").await;
+        create_article(&store, alice, "Edits").await;
+
+        let comment_id = store
+            .comment()
+            .create_comment(bob, "edits", None, "Frist.")
+            .await
+            .unwrap()
+            .value
+            .id;
+
+        let update = |user_id, body| {
+            let store = store.clone();
+            async move {
+                store
+                    .comment()
+                    .update_comment(user_id, "edits", comment_id, body)
+                    .await
+            }
+        };
+
+        assert!(matches!(
+            update(alice, "First.").await,
             Err(Error::Forbidden)
-        } else {
-            Err(Error::NotFound)
+        ));
+        // Saving it as it was isn't an edit.
+        assert!(!update(bob, "Frist.").await.unwrap().value.edited);
+
+        let updated = update(bob, "First.").await.unwrap().value;
+        assert!(updated.edited);
+        assert_eq!(updated.body, "First.");
+        assert!(comments(&store, "edits").await[0].edited);
+
+        let history = store
+            .moderation()
+            .comment_history(moderator, comment_id)
+            .await
+            .unwrap();
+        let bodies: Vec<_> = history.iter().map(|version| &version.body[..]).collect();
+        assert_eq!(bodies, ["Frist.", "First."]);
+        assert!(history[0].replaced_at.is_some());
+        assert!(history[1].replaced_at.is_none());
+
+        assert!(matches!(
+            store.moderation().comment_history(bob, comment_id).await,
+            Err(Error::Forbidden)
+        ));
+    }
+
+    #[sqlx::test]
+    async fn replies_per_thread(pool: PgPool) {
+        let store = store(pool.clone(), config());
+        let alice = create_user(&pool, "alice").await;
+        create_article(&store, alice, "Replies").await;
+
+        let comment = |parent_id, body| {
+            let store = store.clone();
+            async move {
+                store
+                    .comment()
+                    .create_comment(alice, "replies", parent_id, body)
+                    .await
+                    .unwrap()
+                    .value
+                    .id
+            }
+        };
+
+        let top = comment(None, "Top.").await;
+        let first = comment(Some(top), "First.").await;
+        let second = comment(Some(first), "Second.").await;
+        let third = comment(Some(top), "Third.").await;
+
+        let page = store
+            .comment()
+            .get_article_comments(
+                None,
+                "replies",
+                CommentsQuery {
+                    replies: Some(2),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(ids(&page.comments), [top, first, second]);
+        assert_eq!(page.comments[0].more_replies, Some(1));
+        assert_eq!(page.total_count, 4);
+
+        let replies = |limit, offset| {
+            let store = store.clone();
+            async move {
+                store
+                    .comment()
+                    .get_replies(
+                        None,
+                        "replies",
+                        top,
+                        RepliesQuery {
+                            limit,
+                            offset,
+                            ..Default::default()
+                        },
+                    )
+                    .await
+                    .unwrap()
+            }
+        };
+
+        let rest = replies(None, Some(2)).await;
+        assert_eq!(ids(&rest.comments), [third]);
+        assert_eq!(rest.more_replies, 0);
+
+        let first_page = replies(Some(1), None).await;
+        assert_eq!(ids(&first_page.comments), [first]);
+        assert_eq!(first_page.more_replies, 2);
+
+        let all = comments(&store, "replies").await;
+        assert_eq!(ids(&all), [top, first, second, third]);
+        assert_eq!(all[0].more_replies, None);
+
+        assert!(matches!(
+            store
+                .comment()
+                .get_article_comments(
+                    None,
+                    "replies",
+                    CommentsQuery {
+                        replies: Some(-1),
+                        ..Default::default()
+                    },
+                )
+                .await,
+            Err(Error::UnprocessableEntity { errors }) if errors.contains_key("replies")
+        ));
+    }
+
+    #[sqlx::test]
+    async fn closed_comments(pool: PgPool) {
+        let store = store(pool.clone(), config());
+        let alice = create_user(&pool, "alice").await;
+        let bob = create_user(&pool, "bob").await;
+        create_article(&store, alice, "Closed").await;
+
+        let comment_id = store
+            .comment()
+            .create_comment(bob, "closed", None, "Before.")
+            .await
+            .unwrap()
+            .value
+            .id;
+
+        let set_mode = |comments_mode| {
+            let store = store.clone();
+            let article = UpdateArticle {
+                title: None,
+                description: None,
+                body: None,
+                comments_mode: Some(comments_mode),
+            };
+
+            async move {
+                store
+                    .article()
+                    .update_article(alice, "closed", article)
+                    .await
+                    .unwrap()
+                    .value
+                    .comments_count
+            }
+        };
+
+        let is_closed = |result: Result<Filtered<Comment>>| {
+            matches!(
+                result,
+                Err(Error::UnprocessableEntity { errors }) if errors.contains_key("comments")
+            )
+        };
+
+        for (mode, comments_count) in [(CommentsMode::Locked, 1), (CommentsMode::Disabled, 0)] {
+            assert_eq!(set_mode(mode).await, comments_count);
+
+            assert!(is_closed(
+                store
+                    .comment()
+                    .create_comment(bob, "closed", None, "After.")
+                    .await
+            ));
+            assert!(is_closed(
+                store
+                    .comment()
+                    .update_comment(bob, "closed", comment_id, "Edited.")
+                    .await
+            ));
         }
+
+        assert_eq!(set_mode(CommentsMode::Open).await, 1);
+        assert_eq!(ids(&comments(&store, "closed").await), [comment_id]);
     }
 }
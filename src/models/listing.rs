@@ -1,18 +1,56 @@
+use crate::config::Config;
 use crate::http::types::Timestamptz;
-use crate::http::Result;
-use crate::models::article::{Article, ArticleFromQuery};
-use futures::TryStreamExt;
+use crate::http::{Error, Result};
+use crate::markdown::MarkdownCache;
+use crate::models::article::{Article, ArticleFromQuery, ArticleReactions};
+use crate::models::coauthor::ArticleAuthor;
+use crate::models::comment::CommentsMode;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use futures::{TryFutureExt, TryStreamExt};
+use sqlx::types::Json;
 use sqlx::PgPool;
+use std::sync::Arc;
+use time::OffsetDateTime;
 use uuid::Uuid;
 
+/// The largest `limit` any listing will accept.
+pub const MAX_LIMIT: i64 = 100;
+
+// This needs to be deserialized with `MultiQuery` rather than `Query` for the `Vec`s to work.
 #[derive(serde::Deserialize, Default)]
 #[serde(default)]
 pub struct ListArticlesQuery {
-    // Theoretically we could allow filtering by multiple tags, e.g. `/api/articles?tag=Rust&tag=SQL`
-    // But the Realworld spec doesn't mention that so we're not doing it.
-    pub tag: Option<String>,
-    pub author: Option<String>,
-    pub favorited: Option<String>,
+    // The Realworld spec only mentions filtering by a single value of each of these, but
+    // the `Vec`s accept repeated keys (`?tag=rust&tag=sql`), comma-separated values
+    // (`?tag=rust,sql`) or both. A value prefixed with `-` excludes it instead (`?tag=-beginner`).
+    //
+    // See `split_filter()` below.
+    pub tag: Vec<String>,
+    /// If any authors are included, their pinned articles come first, whatever the `sort`.
+    /// Those pages have no `nextCursor` or `prevCursor`, since pinned articles would be out
+    /// of order for them; paginate with `offset` instead.
+    pub author: Vec<String>,
+    pub favorited: Vec<String>,
+
+    /// Whether articles must match `any` (the default) or `all` of the given tags and favoriting users.
+    ///
+    /// An article only has one author, so that's always `any`.
+    #[serde(rename = "match")]
+    pub match_mode: Option<MatchMode>,
+
+    /// Only include articles in the series with this slug.
+    ///
+    /// These are still sorted by `sort`, not by their position in the series;
+    /// `GET /api/series/:slug` lists them in reading order.
+    pub series: Option<String>,
+
+    /// Only include articles created at or after this time.
+    pub since: Option<Timestamptz>,
+    /// Only include articles created before this time.
+    pub until: Option<Timestamptz>,
+
+    pub sort: Option<ArticleSort>,
 
     // `limit` and `offset` are not the optimal way to paginate SQL queries, because the query
     // planner essentially has to fetch the whole dataset first and then cull it afterwards.
@@ -25,12 +63,18 @@ pub struct ListArticlesQuery {
     // field after `articles` that is the URL that the frontend should fetch to get the next page in
     // the ordering, so the frontend doesn't even need to care what column you're using to paginate.
     //
-    // However, this is what the Realworld spec calls for.
+    // However, this is what the Realworld spec calls for, so we support both: `offset` for the
+    // spec, and `cursor` (taken from `nextCursor`/`prevCursor` in the response) for everyone else.
+    // `offset` is ignored if `cursor` is set.
     pub limit: Option<i64>,
     pub offset: Option<i64>,
+    pub cursor: Option<String>,
+
+    /// Overrides `Config::articles_count_mode` for this request.
+    pub count: Option<CountMode>,
 }
 //
-// This is technically a subset of `ListArticlesQuery` so we could do some composition
+// This overlaps a fair bit with `ListArticlesQuery` so we could do some composition
 // but it doesn't really save any lines of code and would make these fields slightly less intuitive
 // to access in `list_articles()`.
 #[derive(serde::Deserialize, Default)]
@@ -39,16 +83,611 @@ pub struct FeedArticlesQuery {
     // See comment on these fields in `ListArticlesQuery` above.
     pub limit: Option<i64>,
     pub offset: Option<i64>,
+    pub cursor: Option<String>,
+    pub count: Option<CountMode>,
+
+    pub rank: Option<FeedRank>,
+
+    /// Leave out articles the user has already opened, i.e. fetched with `get_article()`.
+    ///
+    /// Those are written along with views (see `ViewBuffer`), so it takes a few seconds
+    /// for an article to drop out.
+    pub unseen: bool,
+}
+
+#[derive(serde::Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedRank {
+    /// Newest first.
+    #[default]
+    Recent,
+    /// Recency weighted by favorites and comments. Doesn't support cursors.
+    Engagement,
+}
+
+// Trending articles are ranked by a score rather than `(created_at, article_id)`,
+// so only offset pagination is supported.
+#[derive(serde::Deserialize, Default)]
+#[serde(default)]
+pub struct TrendingArticlesQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    pub count: Option<CountMode>,
+}
+
+// Bookmarks are ordered by when they were bookmarked, which our cursors don't encode,
+// so this is offset pagination only, like `TrendingArticlesQuery`.
+#[derive(serde::Deserialize, Default)]
+#[serde(default)]
+pub struct BookmarkedArticlesQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    pub count: Option<CountMode>,
+}
+
+// Same deal as `BookmarkedArticlesQuery`, except it's ordered by when they were deleted. The count
+// is always exact since nobody should have enough articles in the trash for it to matter.
+#[derive(serde::Deserialize, Default)]
+#[serde(default)]
+pub struct TrashQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(serde::Deserialize, Default)]
+#[serde(default)]
+pub struct RelatedArticlesQuery {
+    // Defaults to `DEFAULT_RELATED_LIMIT` rather than 20 since this is meant for
+    // a "you might also like" box, not a full listing.
+    pub limit: Option<i64>,
+}
+
+const DEFAULT_RELATED_LIMIT: i64 = 5;
+
+#[derive(serde::Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    #[default]
+    Any,
+    All,
+}
+
+#[derive(serde::Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ArticleSort {
+    #[default]
+    Newest,
+    Oldest,
+    MostFavorited,
+    MostCommented,
+    RecentlyUpdated,
+}
+
+impl ArticleSort {
+    /// What the listing query expects in its `sort` parameter.
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Newest => "newest",
+            Self::Oldest => "oldest",
+            Self::MostFavorited => "most_favorited",
+            Self::MostCommented => "most_commented",
+            Self::RecentlyUpdated => "recently_updated",
+        }
+    }
+
+    /// Cursors only encode `(created_at, article_id)`, so they only work for these.
+    fn supports_cursor(&self) -> bool {
+        matches!(self, Self::Newest | Self::Oldest)
+    }
+}
+
+/// Split the values of a filter on commas, separating out the ones prefixed with `-`.
+///
+/// Returns `(included, excluded)`.
+fn split_filter(values: &[String]) -> (Vec<String>, Vec<String>) {
+    let mut included = vec![];
+    let mut excluded = vec![];
+
+    for value in values.iter().flat_map(|value| value.split(',')) {
+        let value = value.trim();
+
+        match value.strip_prefix('-') {
+            Some(value) if !value.is_empty() => excluded.push(value.to_string()),
+            // Ignore empty values and lone hyphens.
+            Some(_) => (),
+            None if !value.is_empty() => included.push(value.to_string()),
+            None => (),
+        }
+    }
+
+    (included, excluded)
+}
+
+/// The filters of a `ListArticlesQuery` or `SearchArticlesQuery`, ready to be passed to the
+/// listing, count and search queries.
+struct ArticleFilters {
+    tags: Vec<String>,
+    excluded_tags: Vec<String>,
+    authors: Vec<String>,
+    excluded_authors: Vec<String>,
+    favorited: Vec<String>,
+    excluded_favorited: Vec<String>,
+    match_all: bool,
+    series: Option<String>,
+    since: Option<OffsetDateTime>,
+    until: Option<OffsetDateTime>,
+}
+
+impl ArticleFilters {
+    fn new(query: &ListArticlesQuery) -> Self {
+        Self::from_parts(
+            &query.tag,
+            &query.author,
+            &query.favorited,
+            query.match_mode,
+            &query.series,
+            &query.since,
+            &query.until,
+        )
+    }
+
+    fn for_search(query: &SearchArticlesQuery) -> Self {
+        Self::from_parts(
+            &query.tag,
+            &query.author,
+            &query.favorited,
+            query.match_mode,
+            &query.series,
+            &query.since,
+            &query.until,
+        )
+    }
+
+    fn from_parts(
+        tag: &[String],
+        author: &[String],
+        favorited: &[String],
+        match_mode: Option<MatchMode>,
+        series: &Option<String>,
+        since: &Option<Timestamptz>,
+        until: &Option<Timestamptz>,
+    ) -> Self {
+        let (tags, excluded_tags) = split_filter(tag);
+        let (authors, excluded_authors) = split_filter(author);
+        let (favorited, excluded_favorited) = split_filter(favorited);
+
+        Self {
+            tags,
+            excluded_tags,
+            authors,
+            excluded_authors,
+            favorited,
+            excluded_favorited,
+            match_all: match_mode == Some(MatchMode::All),
+            series: series.clone(),
+            since: since.as_ref().map(|since| since.0),
+            until: until.as_ref().map(|until| until.0),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+            && self.excluded_tags.is_empty()
+            && self.authors.is_empty()
+            && self.excluded_authors.is_empty()
+            && self.favorited.is_empty()
+            && self.excluded_favorited.is_empty()
+            && self.series.is_none()
+            && self.since.is_none()
+            && self.until.is_none()
+    }
+}
+
+/// How `articlesCount` is calculated for a listing.
+#[derive(clap::ValueEnum, serde::Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CountMode {
+    /// Count every article matching the filters. This has to touch all of them.
+    #[default]
+    Exact,
+    /// Use the planner's estimate of how many articles aren't in the trash for unfiltered
+    /// listings, which is basically free but only as fresh as the last `ANALYZE`.
+    ///
+    /// Filtered listings and the feed fall back to `Exact`, as they're typically much smaller
+    /// and there's no cheap way to estimate them.
+    Estimated,
+    /// Just the number of articles in the page, which is what this API did originally.
+    Page,
+}
+
+/// A page of articles, along with cursors to fetch the pages either side of it.
+pub struct ArticlePage {
+    pub articles: Vec<Article>,
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+    /// `None` if `CountMode::Page` was requested.
+    pub total_count: Option<i64>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CursorDirection {
+    /// Articles after the cursor in the listing's order, i.e. the next page.
+    After,
+    /// Articles before the cursor in the listing's order, i.e. the previous page.
+    Before,
+}
+
+/// A position in the `(created_at, article_id)` ordering of a listing.
+///
+/// This is serialized to an opaque string so clients don't start depending on what's inside;
+/// we'd like to be free to paginate on other columns later.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ArticleCursor {
+    pub direction: CursorDirection,
+    pub created_at: OffsetDateTime,
+    pub article_id: Uuid,
+}
+
+impl ArticleCursor {
+    pub fn encode(&self) -> String {
+        let direction = match self.direction {
+            CursorDirection::After => 'a',
+            CursorDirection::Before => 'b',
+        };
+
+        URL_SAFE_NO_PAD.encode(format!(
+            "{direction}:{}:{}",
+            self.created_at.unix_timestamp_nanos(),
+            self.article_id.simple()
+        ))
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self> {
+        let invalid = || Error::unprocessable_entity([("cursor", "invalid cursor")]);
+
+        let decoded = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?;
+        let decoded = std::str::from_utf8(&decoded).map_err(|_| invalid())?;
+
+        let mut parts = decoded.split(':');
+
+        let direction = match parts.next() {
+            Some("a") => CursorDirection::After,
+            Some("b") => CursorDirection::Before,
+            _ => return Err(invalid()),
+        };
+
+        let created_at = parts
+            .next()
+            .and_then(|nanos| nanos.parse().ok())
+            .and_then(|nanos| OffsetDateTime::from_unix_timestamp_nanos(nanos).ok())
+            .ok_or_else(invalid)?;
+
+        let article_id = parts
+            .next()
+            .and_then(|id| id.parse().ok())
+            .ok_or_else(invalid)?;
+
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            direction,
+            created_at,
+            article_id,
+        })
+    }
+}
+
+/// Check `limit` and `offset`, applying the defaults from the spec.
+pub(crate) fn limit_and_offset(limit: Option<i64>, offset: Option<i64>) -> Result<(i64, i64)> {
+    let limit = limit.unwrap_or(20);
+    let offset = offset.unwrap_or(0);
+
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(Error::unprocessable_entity([(
+            "limit",
+            format!("must be between 1 and {MAX_LIMIT}"),
+        )]));
+    }
+
+    if offset < 0 {
+        return Err(Error::unprocessable_entity([(
+            "offset",
+            "can't be negative",
+        )]));
+    }
+
+    Ok((limit, offset))
+}
+
+/// Fix up the rows of a listing query into an `ArticlePage`.
+///
+/// The query is expected to have fetched up to `limit + 1` rows, in whichever direction `cursor`
+/// points, so we can tell whether there's anything past the end without a second query.
+///
+/// If `with_cursors` is `false`, `next_cursor` and `prev_cursor` are left empty.
+fn into_page(
+    mut rows: Vec<ArticleFromQuery>,
+    limit: i64,
+    offset: i64,
+    cursor: Option<ArticleCursor>,
+    with_cursors: bool,
+    markdown: &MarkdownCache,
+) -> ArticlePage {
+    let limit = limit as usize;
+    let has_more = rows.len() > limit;
+    rows.truncate(limit);
+
+    let backwards = cursor.map(|c| c.direction) == Some(CursorDirection::Before);
+
+    if backwards {
+        // The query walked backwards from the cursor, so put the page back in order.
+        rows.reverse();
+    }
+
+    // Fall back to the cursor itself if this page came back empty, so a client that
+    // overshoots can still turn back around.
+    let first = rows
+        .first()
+        .map(|row| (row.created_at.0, row.article_id))
+        .or(cursor.map(|c| (c.created_at, c.article_id)));
+    let last = rows
+        .last()
+        .map(|row| (row.created_at.0, row.article_id))
+        .or(cursor.map(|c| (c.created_at, c.article_id)));
+
+    let (has_next, has_prev) = if !with_cursors {
+        (false, false)
+    } else if backwards {
+        // We came from the next page, so it's definitely there.
+        (true, has_more)
+    } else {
+        (has_more, cursor.is_some() || offset > 0)
+    };
+
+    let to_cursor = |direction, (created_at, article_id)| {
+        ArticleCursor {
+            direction,
+            created_at,
+            article_id,
+        }
+        .encode()
+    };
+
+    ArticlePage {
+        next_cursor: last
+            .filter(|_| has_next)
+            .map(|key| to_cursor(CursorDirection::After, key)),
+        prev_cursor: first
+            .filter(|_| has_prev)
+            .map(|key| to_cursor(CursorDirection::Before, key)),
+        articles: rows
+            .into_iter()
+            .map(|row| row.into_article(markdown))
+            .collect(),
+        total_count: None,
+    }
+}
+
+// Not in the Realworld spec.
+//
+// Same story as `FeedArticlesQuery`: this is the filters of `ListArticlesQuery` plus `q`, but
+// `#[serde(flatten)]` doesn't play nicely with `Query` since everything gets deserialized
+// as a string, so `limit` and `offset` would fail to parse.
+//
+// Like `ListArticlesQuery`, this needs to be deserialized with `MultiQuery`.
+#[derive(serde::Deserialize, Default)]
+#[serde(default)]
+pub struct SearchArticlesQuery {
+    /// Parsed with `websearch_to_tsquery()`, so it supports `"quoted phrases"`, `or` and `-exclusions`.
+    pub q: String,
+    // See the comments on these fields in `ListArticlesQuery`.
+    pub tag: Vec<String>,
+    pub author: Vec<String>,
+    pub favorited: Vec<String>,
+    #[serde(rename = "match")]
+    pub match_mode: Option<MatchMode>,
+    pub series: Option<String>,
+    pub since: Option<Timestamptz>,
+    pub until: Option<Timestamptz>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArticleSearchHit {
+    #[serde(flatten)]
+    pub article: Article,
+    pub rank: f32,
+    /// A few fragments of the body around the matched terms, HTML-escaped, with the matches
+    /// themselves wrapped in `<mark>`.
+    pub snippet: String,
+}
+
+/// An article in `GET /api/user/trash`.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashedArticle {
+    #[serde(flatten)]
+    pub article: Article,
+    pub deleted_at: Timestamptz,
+    /// When it'll be deleted for good, unless it's restored first.
+    pub purge_at: Timestamptz,
+}
+
+pub struct TrashPage {
+    pub articles: Vec<TrashedArticle>,
+    pub total_count: i64,
+}
+
+// `ArticleFromQuery` with the trash columns tacked on, like `ArticleSearchHitFromQuery`.
+struct TrashedArticleFromQuery {
+    article_id: Uuid,
+    slug: String,
+    title: String,
+    description: String,
+    body: String,
+    tag_list: Vec<String>,
+    word_count: i32,
+    reading_time_minutes: i32,
+    created_at: Timestamptz,
+    updated_at: Timestamptz,
+    favorited: bool,
+    bookmarked: bool,
+    pinned: bool,
+    favorites_count: i64,
+    views_count: i64,
+    comments_count: i64,
+    comments_mode: CommentsMode,
+    author_username: String,
+    author_bio: String,
+    author_image: Option<String>,
+    following_author: bool,
+    authors: Json<Vec<ArticleAuthor>>,
+    reactions: Json<ArticleReactions>,
+    series_slug: Option<String>,
+    series_title: Option<String>,
+    series_position: Option<i32>,
+    series_length: Option<i64>,
+    series_previous: Option<String>,
+    series_next: Option<String>,
+    deleted_at: Timestamptz,
+    purge_at: Timestamptz,
+}
+
+impl TrashedArticleFromQuery {
+    fn into_trashed(self, markdown: &MarkdownCache) -> TrashedArticle {
+        TrashedArticle {
+            deleted_at: self.deleted_at,
+            purge_at: self.purge_at,
+            article: ArticleFromQuery {
+                article_id: self.article_id,
+                slug: self.slug,
+                title: self.title,
+                description: self.description,
+                body: self.body,
+                tag_list: self.tag_list,
+                created_at: self.created_at,
+                updated_at: self.updated_at,
+                favorited: self.favorited,
+                bookmarked: self.bookmarked,
+                pinned: self.pinned,
+                favorites_count: self.favorites_count,
+                views_count: self.views_count,
+                comments_count: self.comments_count,
+                comments_mode: self.comments_mode,
+                word_count: self.word_count,
+                reading_time_minutes: self.reading_time_minutes,
+                author_username: self.author_username,
+                author_bio: self.author_bio,
+                author_image: self.author_image,
+                following_author: self.following_author,
+                authors: self.authors,
+                reactions: self.reactions,
+                series_slug: self.series_slug,
+                series_title: self.series_title,
+                series_position: self.series_position,
+                series_length: self.series_length,
+                series_previous: self.series_previous,
+                series_next: self.series_next,
+            }
+            .into_article(markdown),
+        }
+    }
+}
+
+// `ArticleFromQuery` with the search columns tacked on.
+struct ArticleSearchHitFromQuery {
+    article_id: Uuid,
+    slug: String,
+    title: String,
+    description: String,
+    body: String,
+    tag_list: Vec<String>,
+    created_at: Timestamptz,
+    updated_at: Timestamptz,
+    favorited: bool,
+    bookmarked: bool,
+    pinned: bool,
+    favorites_count: i64,
+    views_count: i64,
+    comments_count: i64,
+    comments_mode: CommentsMode,
+    word_count: i32,
+    reading_time_minutes: i32,
+    author_username: String,
+    author_bio: String,
+    author_image: Option<String>,
+    following_author: bool,
+    authors: Json<Vec<ArticleAuthor>>,
+    reactions: Json<ArticleReactions>,
+    series_slug: Option<String>,
+    series_title: Option<String>,
+    series_position: Option<i32>,
+    series_length: Option<i64>,
+    series_previous: Option<String>,
+    series_next: Option<String>,
+    rank: f32,
+    snippet: String,
+}
+
+impl ArticleSearchHitFromQuery {
+    fn into_hit(self, markdown: &MarkdownCache) -> ArticleSearchHit {
+        ArticleSearchHit {
+            rank: self.rank,
+            snippet: highlight_to_html(&self.snippet),
+            article: ArticleFromQuery {
+                article_id: self.article_id,
+                slug: self.slug,
+                title: self.title,
+                description: self.description,
+                body: self.body,
+                tag_list: self.tag_list,
+                created_at: self.created_at,
+                updated_at: self.updated_at,
+                favorited: self.favorited,
+                bookmarked: self.bookmarked,
+                pinned: self.pinned,
+                favorites_count: self.favorites_count,
+                views_count: self.views_count,
+                comments_count: self.comments_count,
+                comments_mode: self.comments_mode,
+                word_count: self.word_count,
+                reading_time_minutes: self.reading_time_minutes,
+                author_username: self.author_username,
+                author_bio: self.author_bio,
+                author_image: self.author_image,
+                following_author: self.following_author,
+                authors: self.authors,
+                reactions: self.reactions,
+                series_slug: self.series_slug,
+                series_title: self.series_title,
+                series_position: self.series_position,
+                series_length: self.series_length,
+                series_previous: self.series_previous,
+                series_next: self.series_next,
+            }
+            .into_article(markdown),
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct ListingController {
     pool: PgPool,
+    config: Arc<Config>,
+    markdown: Arc<MarkdownCache>,
 }
 
 impl ListingController {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+    pub fn new(pool: PgPool, config: Arc<Config>, markdown: Arc<MarkdownCache>) -> Self {
+        Self {
+            pool,
+            config,
+            markdown,
+        }
     }
 }
 
@@ -57,83 +696,397 @@ impl ListingController {
         &self,
         user_id: Option<Uuid>,
         query: ListArticlesQuery,
-    ) -> Result<Vec<Article>> {
-        let articles: Vec<_> = sqlx::query_as!(
+    ) -> Result<ArticlePage> {
+        let (limit, offset) = limit_and_offset(query.limit, query.offset)?;
+        let cursor = query
+            .cursor
+            .as_deref()
+            .map(ArticleCursor::decode)
+            .transpose()?;
+        let offset = if cursor.is_some() { 0 } else { offset };
+
+        let sort = query.sort.unwrap_or_default();
+
+        if cursor.is_some() && !sort.supports_cursor() {
+            return Err(Error::unprocessable_entity([(
+                "cursor",
+                "only supported when sorting by newest or oldest",
+            )]));
+        }
+
+        // Walking backwards from a `prevCursor` means we want the closest rows on the other side,
+        // so the order flips. `into_page()` puts them back the right way around.
+        let ascending = (sort == ArticleSort::Oldest)
+            != (cursor.map(|c| c.direction) == Some(CursorDirection::Before));
+
+        let filters = ArticleFilters::new(&query);
+        let count_mode = query.count.unwrap_or(self.config.articles_count_mode);
+
+        // Pinned articles go on top of an author's profile, which is this listing filtered by
+        // `author=`. They'd be in the way of walking a cursor by `created_at` though, so those
+        // pages only support `offset`; see `ListArticlesQuery.author`.
+        let pinned_first = !filters.authors.is_empty() && cursor.is_none();
+
+        let estimate = match count_mode {
+            CountMode::Estimated if filters.is_empty() => self.estimate_article_count().await?,
+            _ => None,
+        };
+
+        // The count can't use `count(*) over ()` in the page query as it would be thrown off by
+        // the cursor. This way it at least runs concurrently with the page.
+        let count = async {
+            match (count_mode, estimate) {
+                (CountMode::Page, _) => Ok(None),
+                (_, Some(estimate)) => Ok(Some(estimate)),
+                _ => self.count_articles(&filters).await.map(Some),
+            }
+        };
+
+        let rows = sqlx::query_as!(
         ArticleFromQuery,
         // language=PostgreSQL
         r#"
             select
+                article.article_id,
                 slug,
                 title,
                 description,
                 body,
                 tag_list,
+                word_count,
+                reading_time_minutes,
                 article.created_at "created_at: Timestamptz",
                 article.updated_at "updated_at: Timestamptz",
-                exists(select 1 from article_favorite where user_id = $1) "favorited!",
+                exists(select 1 from article_favorite fav where fav.article_id = article.article_id and fav.user_id = $1) "favorited!",
+                exists(select 1 from article_bookmark bm where bm.article_id = article.article_id and bm.user_id = $1) "bookmarked!",
+                exists(select 1 from article_pin pin where pin.article_id = article.article_id) "pinned!",
+                article_authors(article.article_id, $1) "authors!: Json<Vec<ArticleAuthor>>",
+                article_reactions(article.article_id, $1) "reactions!: Json<ArticleReactions>",
+                article_series.series_slug,
+                article_series.series_title,
+                article_series.position series_position,
+                article_series.series_length,
+                article_series.previous_slug series_previous,
+                article_series.next_slug series_next,
                 coalesce(
                     -- `count(*)` returns `NULL` if the query returned zero columns
                     -- not exactly a fan of that design choice but whatever
                     (select count(*) from article_favorite fav where fav.article_id = article.article_id),
                     0
                 ) "favorites_count!",
+                coalesce(
+                    (select sum(daily.views) from article_view_daily daily where daily.article_id = article.article_id),
+                    0
+                )::int8 "views_count!",
+                article_comments_count(article.article_id) "comments_count!",
+                article.comments_mode "comments_mode: CommentsMode",
                 author.username author_username,
                 author.bio author_bio,
                 author.image author_image,
                 exists(select 1 from follow where followed_user_id = author.user_id and following_user_id = $1) "following_author!"
             from article
             inner join "user" author using (user_id)
-            -- the current way to do conditional filtering in SQLx
-            where (
-                -- check if `query.tag` is null or contains the given tag
-                -- PostgresSQL doesn't have an "array contains element" operator
-                -- so instead we check if the tag_list contains an array of just the given tag
-                $2::text is null or tag_list @> array[$2]
+            left join article_series_position article_series on article_series.article_id = article.article_id
+            -- Articles in the trash are hidden everywhere but `GET /api/user/trash`.
+            where article.deleted_at is null
+              -- the current way to do conditional filtering in SQLx
+              and (
+                -- `@>` is "contains", i.e. the article has all of the given tags,
+                -- and `&&` is "overlaps", i.e. it has at least one of them.
+                cardinality($2::text[]) = 0
+                or ($8 and tag_list @> $2)
+                or (not $8 and tag_list && $2)
             )
+              and not tag_list && $3::text[]
               and
             (
-                $3::text is null or author.username = $3
+                cardinality($4::text[]) = 0 or author.username = any($4)
             )
+              and not author.username = any($5::text[])
               and
             (
-                $4::text is null or exists(
+                cardinality($6::text[]) = 0
+                or (
+                    -- "There's no one in the list who hasn't favorited it."
+                    -- SQL doesn't have a "for all" so this is the double negative version.
+                    $8 and not exists(
+                        select 1
+                        from unnest($6) favoriter(username)
+                        where not exists(
+                            select 1
+                            from article_favorite af
+                            inner join "user" using (user_id)
+                            where af.article_id = article.article_id
+                              and "user".username = favoriter.username
+                        )
+                    )
+                )
+                or (
+                    not $8 and exists(
+                        select 1
+                        from article_favorite af
+                        inner join "user" using (user_id)
+                        where af.article_id = article.article_id and "user".username = any($6)
+                    )
+                )
+            )
+              and not exists(
+                select 1
+                from article_favorite af
+                inner join "user" using (user_id)
+                where af.article_id = article.article_id and "user".username = any($7::text[])
+            )
+              and ($9::timestamptz is null or article.created_at >= $9)
+              and ($10::timestamptz is null or article.created_at < $10)
+              and (
+                $17::text is null or exists(
                     select 1
-                    from "user"
-                    inner join article_favorite af using (user_id)
-                    where username = $4
+                    from series_article
+                    inner join series using (series_id)
+                    where series_article.article_id = article.article_id and series.slug = $17
                 )
             )
-            order by article.created_at desc
-            limit $5
-            offset $6
+              and
+            (
+                -- Row-value comparisons make the tiebreak on `article_id` a lot less verbose.
+                $12::timestamptz is null
+                or ($14 and (article.created_at, article.article_id) > ($12, $13))
+                or (not $14 and (article.created_at, article.article_id) < ($12, $13))
+            )
+            order by
+                -- Most recently pinned first, same as `pinnedArticles` on the profile.
+                case when $18 then (
+                    select pin.created_at from article_pin pin where pin.article_id = article.article_id
+                ) end desc nulls last,
+                -- At most one of these is non-null for a given `sort`;
+                -- ties and the other sorts fall through to `created_at`.
+                case when $11 = 'most_favorited' then (
+                    select count(*) from article_favorite fav where fav.article_id = article.article_id
+                ) end desc,
+                case when $11 = 'most_commented' then article_comments_count(article.article_id) end desc,
+                case when $11 = 'recently_updated' then article.updated_at end desc,
+                case when $14 then article.created_at end,
+                case when $14 then article.article_id end,
+                article.created_at desc,
+                article.article_id desc
+            limit $15
+            offset $16
         "#,
         user_id,
-        query.tag,
-        query.author,
-        query.favorited,
-        query.limit.unwrap_or(20),
-        query.offset.unwrap_or(0)
+        &filters.tags[..],
+        &filters.excluded_tags[..],
+        &filters.authors[..],
+        &filters.excluded_authors[..],
+        &filters.favorited[..],
+        &filters.excluded_favorited[..],
+        filters.match_all,
+        filters.since,
+        filters.until,
+        sort.as_str(),
+        cursor.map(|c| c.created_at),
+        cursor.map(|c| c.article_id),
+        ascending,
+        limit + 1,
+        offset,
+        filters.series,
+        pinned_first,
     )
-    .fetch(&self.pool)
-    .map_ok(ArticleFromQuery::into_article)
-    .try_collect()
-    .await?;
-        Ok(articles)
+    .fetch_all(&self.pool)
+    .map_err(Error::from);
+
+        let (rows, total_count) = futures::try_join!(rows, count)?;
+
+        Ok(ArticlePage {
+            total_count,
+            ..into_page(
+                rows,
+                limit,
+                offset,
+                cursor,
+                sort.supports_cursor() && !pinned_first,
+                &self.markdown,
+            )
+        })
+    }
+
+    /// The number of articles `article_list()` would return with no pagination.
+    async fn count_articles(&self, filters: &ArticleFilters) -> Result<i64> {
+        let count = sqlx::query_scalar!(
+            // This has to be kept in sync with the filters in `article_list()`.
+            //
+            // language=PostgreSQL
+            r#"
+                select count(*) "count!"
+                from article
+                inner join "user" author using (user_id)
+                where article.deleted_at is null
+                  and (
+                    cardinality($1::text[]) = 0
+                    or ($7 and tag_list @> $1)
+                    or (not $7 and tag_list && $1)
+                )
+                  and not tag_list && $2::text[]
+                  and (cardinality($3::text[]) = 0 or author.username = any($3))
+                  and not author.username = any($4::text[])
+                  and (
+                    cardinality($5::text[]) = 0
+                    or (
+                        $7 and not exists(
+                            select 1
+                            from unnest($5) favoriter(username)
+                            where not exists(
+                                select 1
+                                from article_favorite af
+                                inner join "user" using (user_id)
+                                where af.article_id = article.article_id
+                                  and "user".username = favoriter.username
+                            )
+                        )
+                    )
+                    or (
+                        not $7 and exists(
+                            select 1
+                            from article_favorite af
+                            inner join "user" using (user_id)
+                            where af.article_id = article.article_id and "user".username = any($5)
+                        )
+                    )
+                  )
+                  and not exists(
+                    select 1
+                    from article_favorite af
+                    inner join "user" using (user_id)
+                    where af.article_id = article.article_id and "user".username = any($6::text[])
+                  )
+                  and ($8::timestamptz is null or article.created_at >= $8)
+                  and ($9::timestamptz is null or article.created_at < $9)
+                  and (
+                    $10::text is null or exists(
+                        select 1
+                        from series_article
+                        inner join series using (series_id)
+                        where series_article.article_id = article.article_id and series.slug = $10
+                    )
+                  )
+            "#,
+            &filters.tags[..],
+            &filters.excluded_tags[..],
+            &filters.authors[..],
+            &filters.excluded_authors[..],
+            &filters.favorited[..],
+            &filters.excluded_favorited[..],
+            filters.match_all,
+            filters.since,
+            filters.until,
+            filters.series,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(count)
     }
 
+    /// The planner's estimate of the number of articles that aren't in the trash or hidden,
+    /// if it has one.
+    async fn estimate_article_count(&self) -> Result<Option<i64>> {
+        // `reltuples` counts every row, so it's scaled down by the fraction of them with no
+        // `deleted_at`, which hidden articles have too. It's -1 if the table has never been
+        // vacuumed or analyzed, in which case there are no column statistics either.
+        let estimate = sqlx::query_scalar!(
+            r#"
+                select
+                    case when reltuples >= 0 then
+                        (reltuples * coalesce(
+                            (
+                                select null_frac
+                                from pg_stats
+                                where schemaname = current_schema()
+                                  and tablename = 'article'
+                                  and attname = 'deleted_at'
+                            ),
+                            1
+                        ))::int8
+                    end "estimate"
+                from pg_class
+                where oid = 'article'::regclass
+            "#
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(estimate)
+    }
+
+    /// Articles by authors the user follows, or tagged with tags they follow.
+    ///
+    /// By default this is newest first, same as `article_list()`. With `?rank=engagement` it's
+    /// a Hacker News-style score instead: favorites and comments push an article up, and age
+    /// pulls it back down.
     pub async fn get_feed_articles(
         &self,
         user_id: Uuid,
         query: FeedArticlesQuery,
-    ) -> Result<Vec<Article>> {
-        let articles: Vec<_> = sqlx::query_as!(
+    ) -> Result<ArticlePage> {
+        let (limit, offset) = limit_and_offset(query.limit, query.offset)?;
+        let cursor = query
+            .cursor
+            .as_deref()
+            .map(ArticleCursor::decode)
+            .transpose()?;
+        let offset = if cursor.is_some() { 0 } else { offset };
+
+        let rank = query.rank.unwrap_or_default();
+
+        if cursor.is_some() && rank != FeedRank::Recent {
+            return Err(Error::unprocessable_entity([(
+                "cursor",
+                "only supported when ranking by recent",
+            )]));
+        }
+
+        // See `CountMode::Estimated` for why that's treated as `Exact` here.
+        let count = async {
+            match query.count.unwrap_or(self.config.articles_count_mode) {
+                CountMode::Page => Ok(None),
+                CountMode::Exact | CountMode::Estimated => sqlx::query_scalar!(
+                    // This has to be kept in sync with the filters in the query below.
+                    //
+                    // language=PostgreSQL
+                    r#"
+                        select count(*) "count!"
+                        from article
+                        where article.user_id <> $1
+                          and article.deleted_at is null
+                          and (
+                            exists(
+                                select 1
+                                from follow
+                                where following_user_id = $1 and followed_user_id = article.user_id
+                            )
+                            or tag_list && array(select tag from tag_follow where user_id = $1)
+                          )
+                          and not (
+                            $2 and exists(
+                                select 1
+                                from article_seen seen
+                                where seen.user_id = $1 and seen.article_id = article.article_id
+                            )
+                          )
+                    "#,
+                    user_id,
+                    query.unseen,
+                )
+                .fetch_one(&self.pool)
+                .await
+                .map(Some),
+            }
+        };
+
+        let rows = sqlx::query_as!(
         ArticleFromQuery,
-        // As a rule of thumb, you always want the most specific dataset to be your outermost
-        // `SELECT` so the query planner does as little extraneous work as possible, and then
-        // your joins are just fetching data related to rows you already know you're returning.
-        // 
-        // In this case, our primary table is the `follow` table so we select from that first
-        // and join the `article` and `user` tables from there.
+        // This used to select from `follow` and join `article` from there, as that was the most
+        // specific dataset. Now that followed tags feed in as well there's no single table
+        // to start from, so we start from `article` and filter it instead.
         //
         // The structure is otherwise very similar to other queries returning `Article`s, so you'd
         // think that SQLx should provide some way to deduplicate them. However, I think that
@@ -142,39 +1095,1048 @@ impl ListingController {
         // language=PostgreSQL
         r#"
             select
+                article.article_id,
                 slug,
                 title,
                 description,
                 body,
                 tag_list,
+                word_count,
+                reading_time_minutes,
                 article.created_at "created_at: Timestamptz",
                 article.updated_at "updated_at: Timestamptz",
-                exists(select 1 from article_favorite where user_id = $1) "favorited!",
+                exists(
+                    select 1
+                    from article_favorite fav
+                    where fav.article_id = article.article_id and fav.user_id = $1
+                ) "favorited!",
+                exists(select 1 from article_bookmark bm where bm.article_id = article.article_id and bm.user_id = $1) "bookmarked!",
+                exists(select 1 from article_pin pin where pin.article_id = article.article_id) "pinned!",
+                article_authors(article.article_id, $1) "authors!: Json<Vec<ArticleAuthor>>",
+                article_reactions(article.article_id, $1) "reactions!: Json<ArticleReactions>",
+                article_series.series_slug,
+                article_series.series_title,
+                article_series.position series_position,
+                article_series.series_length,
+                article_series.previous_slug series_previous,
+                article_series.next_slug series_next,
                 coalesce(
                     (select count(*) from article_favorite fav where fav.article_id = article.article_id),
                     0
                 ) "favorites_count!",
+                coalesce(
+                    (select sum(daily.views) from article_view_daily daily where daily.article_id = article.article_id),
+                    0
+                )::int8 "views_count!",
+                article_comments_count(article.article_id) "comments_count!",
+                article.comments_mode "comments_mode: CommentsMode",
                 author.username author_username,
                 author.bio author_bio,
                 author.image author_image,
-                -- we wouldn't be returning this otherwise
-                true "following_author!"
-            from follow
-            inner join article on followed_user_id = article.user_id
+                -- Not necessarily true anymore; the article could be here for its tags.
+                exists(select 1 from follow where followed_user_id = author.user_id and following_user_id = $1) "following_author!"
+            from article
             inner join "user" author using (user_id)
-            where following_user_id = $1
-            limit $2
-            offset $3
+            left join article_series_position article_series on article_series.article_id = article.article_id
+            -- Followed tags could match the user's own articles, which they don't need to see in their feed.
+            where article.user_id <> $1
+              and article.deleted_at is null
+              and (
+                exists(
+                    select 1
+                    from follow
+                    where following_user_id = $1 and followed_user_id = article.user_id
+                )
+                -- `&&` is "overlaps", and can use the GIN index on `tag_list`.
+                or tag_list && array(select tag from tag_follow where user_id = $1)
+              )
+              and not (
+                $2 and exists(
+                    select 1
+                    from article_seen seen
+                    where seen.user_id = $1 and seen.article_id = article.article_id
+                )
+              )
+              and (
+                -- See `article_list()` above.
+                $5::timestamptz is null
+                or ($7 and (article.created_at, article.article_id) > ($5, $6))
+                or (not $7 and (article.created_at, article.article_id) < ($5, $6))
+              )
+            order by
+                -- Engagement over age, where age is in hours, offset by 2 so brand-new articles
+                -- don't shoot straight to the top. The `+ 1` keeps articles without any
+                -- engagement in the order of their age rather than all tied at 0.
+                case when $3 then (
+                    1 + (select count(*) from article_favorite fav where fav.article_id = article.article_id)
+                      + article_comments_count(article.article_id)
+                ) / power(extract(epoch from now() - article.created_at) / 3600 + 2, 1.5) end desc,
+                case when $7 then article.created_at end,
+                case when $7 then article.article_id end,
+                article.created_at desc,
+                article.article_id desc
+            limit $4
+            offset $8
         "#,
         user_id,
-        query.limit.unwrap_or(20),
-        query.offset.unwrap_or(0)
+        query.unseen,
+        rank == FeedRank::Engagement,
+        limit + 1,
+        cursor.map(|c| c.created_at),
+        cursor.map(|c| c.article_id),
+        cursor.map(|c| c.direction) == Some(CursorDirection::Before),
+        offset,
     )
+        .fetch_all(&self.pool);
+
+        let (rows, total_count) = futures::try_join!(rows, count)?;
+
+        Ok(ArticlePage {
+            total_count,
+            ..into_page(
+                rows,
+                limit,
+                offset,
+                cursor,
+                rank == FeedRank::Recent,
+                &self.markdown,
+            )
+        })
+    }
+
+    /// Articles ranked by their recent favorites and comments.
+    ///
+    /// The scores come from the `article_trending` materialized view, so they're only as fresh
+    /// as the last `refresh_trending()`. Articles with no activity in the past week aren't listed.
+    pub async fn trending_articles(
+        &self,
+        user_id: Option<Uuid>,
+        query: TrendingArticlesQuery,
+    ) -> Result<ArticlePage> {
+        let (limit, offset) = limit_and_offset(query.limit, query.offset)?;
+
+        // Counting the rows of the view is cheap enough that `Estimated` isn't worth it.
+        let count = async {
+            match query.count.unwrap_or(self.config.articles_count_mode) {
+                CountMode::Page => Ok(None),
+                CountMode::Exact | CountMode::Estimated => sqlx::query_scalar!(
+                    r#"
+                            select count(*) "count!"
+                            from article_trending
+                            inner join article using (article_id)
+                            where deleted_at is null
+                        "#
+                )
+                .fetch_one(&self.pool)
+                .await
+                .map(Some),
+            }
+        };
+
+        let rows = sqlx::query_as!(
+            ArticleFromQuery,
+            // Same idea as `get_feed_articles()`: the view is the most specific dataset here.
+            //
+            // language=PostgreSQL
+            r#"
+                select
+                    -- SQLx infers every column of a view as nullable.
+                    article.article_id "article_id!",
+                    slug,
+                    title,
+                    description,
+                    body,
+                    tag_list,
+                    word_count,
+                    reading_time_minutes,
+                    article.created_at "created_at: Timestamptz",
+                    article.updated_at "updated_at: Timestamptz",
+                    exists(
+                        select 1
+                        from article_favorite fav
+                        where fav.article_id = article.article_id and fav.user_id = $1
+                    ) "favorited!",
+                    exists(select 1 from article_bookmark bm where bm.article_id = article.article_id and bm.user_id = $1) "bookmarked!",
+                    exists(select 1 from article_pin pin where pin.article_id = article.article_id) "pinned!",
+                    article_authors(article.article_id, $1) "authors!: Json<Vec<ArticleAuthor>>",
+                    article_reactions(article.article_id, $1) "reactions!: Json<ArticleReactions>",
+                    article_series.series_slug,
+                    article_series.series_title,
+                    article_series.position series_position,
+                    article_series.series_length,
+                    article_series.previous_slug series_previous,
+                    article_series.next_slug series_next,
+                    coalesce(
+                        (select count(*) from article_favorite fav where fav.article_id = article.article_id),
+                        0
+                    ) "favorites_count!",
+                    coalesce(
+                        (select sum(daily.views) from article_view_daily daily where daily.article_id = article.article_id),
+                        0
+                    )::int8 "views_count!",
+                    article_comments_count(article.article_id) "comments_count!",
+                    article.comments_mode "comments_mode: CommentsMode",
+                    author.username author_username,
+                    author.bio author_bio,
+                    author.image author_image,
+                    exists(select 1 from follow where followed_user_id = author.user_id and following_user_id = $1) "following_author!"
+                from article_trending
+                inner join article using (article_id)
+                inner join "user" author using (user_id)
+                left join article_series_position article_series on article_series.article_id = article.article_id
+                -- The view is only refreshed every so often, so it can still have articles that have been deleted since.
+                where article.deleted_at is null
+                order by article_trending.score desc, article.created_at desc, article.article_id desc
+                limit $2
+                offset $3
+            "#,
+            user_id,
+            limit + 1,
+            offset,
+        )
+        .fetch_all(&self.pool);
+
+        let (rows, total_count) = futures::try_join!(rows, count)?;
+
+        Ok(ArticlePage {
+            total_count,
+            ..into_page(rows, limit, offset, None, false, &self.markdown)
+        })
+    }
+
+    /// The user's reading list, most recently bookmarked first.
+    pub async fn bookmarked_articles(
+        &self,
+        user_id: Uuid,
+        query: BookmarkedArticlesQuery,
+    ) -> Result<ArticlePage> {
+        let (limit, offset) = limit_and_offset(query.limit, query.offset)?;
+
+        // This only has to look at the user's own bookmarks, so `Estimated` isn't worth it.
+        let count = async {
+            match query.count.unwrap_or(self.config.articles_count_mode) {
+                CountMode::Page => Ok(None),
+                CountMode::Exact | CountMode::Estimated => sqlx::query_scalar!(
+                    r#"
+                        select count(*) "count!"
+                        from article_bookmark bookmark
+                        inner join article using (article_id)
+                        where bookmark.user_id = $1 and article.deleted_at is null
+                    "#,
+                    user_id
+                )
+                .fetch_one(&self.pool)
+                .await
+                .map(Some),
+            }
+        };
+
+        let rows = sqlx::query_as!(
+            ArticleFromQuery,
+            // Same idea as `get_feed_articles()` used to be: `article_bookmark` is the most
+            // specific dataset so it's the outermost `select`.
+            //
+            // language=PostgreSQL
+            r#"
+                select
+                    article.article_id,
+                    slug,
+                    title,
+                    description,
+                    body,
+                    tag_list,
+                    word_count,
+                    reading_time_minutes,
+                    article.created_at "created_at: Timestamptz",
+                    article.updated_at "updated_at: Timestamptz",
+                    exists(
+                        select 1
+                        from article_favorite fav
+                        where fav.article_id = article.article_id and fav.user_id = $1
+                    ) "favorited!",
+                    -- we wouldn't be returning this otherwise
+                    true "bookmarked!",
+                    exists(select 1 from article_pin pin where pin.article_id = article.article_id) "pinned!",
+                    article_authors(article.article_id, $1) "authors!: Json<Vec<ArticleAuthor>>",
+                    article_reactions(article.article_id, $1) "reactions!: Json<ArticleReactions>",
+                    article_series.series_slug,
+                    article_series.series_title,
+                    article_series.position series_position,
+                    article_series.series_length,
+                    article_series.previous_slug series_previous,
+                    article_series.next_slug series_next,
+                    coalesce(
+                        (select count(*) from article_favorite fav where fav.article_id = article.article_id),
+                        0
+                    ) "favorites_count!",
+                    coalesce(
+                        (select sum(daily.views) from article_view_daily daily where daily.article_id = article.article_id),
+                        0
+                    )::int8 "views_count!",
+                    article_comments_count(article.article_id) "comments_count!",
+                    article.comments_mode "comments_mode: CommentsMode",
+                    author.username author_username,
+                    author.bio author_bio,
+                    author.image author_image,
+                    exists(select 1 from follow where followed_user_id = author.user_id and following_user_id = $1) "following_author!"
+                from article_bookmark bookmark
+                inner join article using (article_id)
+                inner join "user" author on author.user_id = article.user_id
+                left join article_series_position article_series on article_series.article_id = article.article_id
+                where bookmark.user_id = $1 and article.deleted_at is null
+                order by bookmark.created_at desc, article.article_id desc
+                limit $2
+                offset $3
+            "#,
+            user_id,
+            limit + 1,
+            offset,
+        )
+        .fetch_all(&self.pool);
+
+        let (rows, total_count) = futures::try_join!(rows, count)?;
+
+        Ok(ArticlePage {
+            total_count,
+            ..into_page(rows, limit, offset, None, false, &self.markdown)
+        })
+    }
+
+    /// The user's deleted articles that haven't been purged yet, most recently deleted first.
+    ///
+    /// Only the owner of an article can delete it, so this doesn't include articles the user
+    /// co-authored.
+    pub async fn trashed_articles(&self, user_id: Uuid, query: TrashQuery) -> Result<TrashPage> {
+        let (limit, offset) = limit_and_offset(query.limit, query.offset)?;
+
+        let count = sqlx::query_scalar!(
+            r#"
+                select count(*) "count!"
+                from article
+                where user_id = $1 and deleted_at is not null and hidden_at is null
+            "#,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .map_err(Error::from);
+
+        let articles = sqlx::query_as!(
+            TrashedArticleFromQuery,
+            // language=PostgreSQL
+            r#"
+                select
+                    article.article_id,
+                    slug,
+                    title,
+                    description,
+                    body,
+                    tag_list,
+                    word_count,
+                    reading_time_minutes,
+                    article.created_at "created_at: Timestamptz",
+                    article.updated_at "updated_at: Timestamptz",
+                    exists(
+                        select 1
+                        from article_favorite fav
+                        where fav.article_id = article.article_id and fav.user_id = $1
+                    ) "favorited!",
+                    exists(select 1 from article_bookmark bm where bm.article_id = article.article_id and bm.user_id = $1) "bookmarked!",
+                    exists(select 1 from article_pin pin where pin.article_id = article.article_id) "pinned!",
+                    article_authors(article.article_id, $1) "authors!: Json<Vec<ArticleAuthor>>",
+                    article_reactions(article.article_id, $1) "reactions!: Json<ArticleReactions>",
+                    article_series.series_slug,
+                    article_series.series_title,
+                    article_series.position series_position,
+                    article_series.series_length,
+                    article_series.previous_slug series_previous,
+                    article_series.next_slug series_next,
+                    coalesce(
+                        (select count(*) from article_favorite fav where fav.article_id = article.article_id),
+                        0
+                    ) "favorites_count!",
+                    coalesce(
+                        (select sum(daily.views) from article_view_daily daily where daily.article_id = article.article_id),
+                        0
+                    )::int8 "views_count!",
+                    article_comments_count(article.article_id) "comments_count!",
+                    article.comments_mode "comments_mode: CommentsMode",
+                    author.username author_username,
+                    author.bio author_bio,
+                    author.image author_image,
+                    -- you can't follow yourself
+                    false "following_author!",
+                    article.deleted_at "deleted_at!: Timestamptz",
+                    article.deleted_at + make_interval(days => $4) "purge_at!: Timestamptz"
+                from article
+                inner join "user" author using (user_id)
+                left join article_series_position article_series on article_series.article_id = article.article_id
+                -- Hidden articles are technically in the trash, but they can't be restored or purged.
+                where article.user_id = $1 and article.deleted_at is not null and article.hidden_at is null
+                order by article.deleted_at desc, article.article_id desc
+                limit $2
+                offset $3
+            "#,
+            user_id,
+            limit,
+            offset,
+            self.config.trash_retention_days as i32,
+        )
         .fetch(&self.pool)
-        .map_ok(ArticleFromQuery::into_article)
+        .map_ok(|article| article.into_trashed(&self.markdown))
+        .try_collect::<Vec<_>>()
+        .map_err(Error::from);
+
+        let (articles, total_count) = futures::try_join!(articles, count)?;
+
+        Ok(TrashPage {
+            articles,
+            total_count,
+        })
+    }
+
+    /// Articles similar to the one with the given slug, most similar first.
+    ///
+    /// Each candidate scores a point per tag it shares with the article, and half a point per user
+    /// who favorited both. Full-text matches on the article's title only count for a fraction of
+    /// a point, so they mostly fill in when there aren't enough tag or favorite matches.
+    ///
+    /// If the user is logged in, their own articles and ones they've already favorited are left out.
+    pub async fn related_articles(
+        &self,
+        user_id: Option<Uuid>,
+        slug: &str,
+        query: RelatedArticlesQuery,
+    ) -> Result<Vec<Article>> {
+        let (limit, _) = limit_and_offset(query.limit.or(Some(DEFAULT_RELATED_LIMIT)), None)?;
+
+        let article_id = sqlx::query_scalar!(
+            "select article_id from article where slug = $1 and deleted_at is null",
+            slug
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        let articles = sqlx::query_as!(
+            ArticleFromQuery,
+            // language=PostgreSQL
+            r#"
+                with source as (
+                    select
+                        article_id,
+                        tag_list,
+                        -- OR together the (already stemmed) words of the title, so any of them can match.
+                        -- `simple` since stemming them again could change them.
+                        to_tsquery(
+                            'simple',
+                            coalesce(
+                                (
+                                    select string_agg(quote_literal(lexeme), ' | ')
+                                    from unnest(to_tsvector(search_language, title))
+                                ),
+                                ''
+                            )
+                        ) title_query
+                    from article
+                    where article_id = $1
+                ),
+                candidate as (
+                    select article.article_id, cardinality(
+                        array(select unnest(article.tag_list) intersect select unnest(source.tag_list))
+                    )::float8 score
+                    from source
+                    -- `&&` can use the GIN index on `tag_list`.
+                    inner join article on article.tag_list && source.tag_list
+
+                    union all
+
+                    select other.article_id, count(*) * 0.5
+                    from article_favorite this
+                    inner join article_favorite other using (user_id)
+                    where this.article_id = $1
+                    group by other.article_id
+
+                    union all
+
+                    -- `ts_rank()` is well under 1 in practice, so this ends up a tiebreaker or filler.
+                    select article.article_id, ts_rank(article.search_vector, source.title_query) * 0.1
+                    from source
+                    inner join article on article.search_vector @@ source.title_query
+                ),
+                related as (
+                    select article_id, sum(score) score
+                    from candidate
+                    where article_id <> $1
+                    group by article_id
+                )
+                select
+                    -- SQLx can't see through the CTEs to tell this is non-null.
+                    article.article_id "article_id!",
+                    slug,
+                    title,
+                    description,
+                    body,
+                    tag_list,
+                    word_count,
+                    reading_time_minutes,
+                    article.created_at "created_at: Timestamptz",
+                    article.updated_at "updated_at: Timestamptz",
+                    exists(
+                        select 1
+                        from article_favorite fav
+                        where fav.article_id = article.article_id and fav.user_id = $2
+                    ) "favorited!",
+                    exists(select 1 from article_bookmark bm where bm.article_id = article.article_id and bm.user_id = $2) "bookmarked!",
+                    exists(select 1 from article_pin pin where pin.article_id = article.article_id) "pinned!",
+                    article_authors(article.article_id, $2) "authors!: Json<Vec<ArticleAuthor>>",
+                    article_reactions(article.article_id, $2) "reactions!: Json<ArticleReactions>",
+                    article_series.series_slug,
+                    article_series.series_title,
+                    article_series.position series_position,
+                    article_series.series_length,
+                    article_series.previous_slug series_previous,
+                    article_series.next_slug series_next,
+                    coalesce(
+                        (select count(*) from article_favorite fav where fav.article_id = article.article_id),
+                        0
+                    ) "favorites_count!",
+                    coalesce(
+                        (select sum(daily.views) from article_view_daily daily where daily.article_id = article.article_id),
+                        0
+                    )::int8 "views_count!",
+                    article_comments_count(article.article_id) "comments_count!",
+                    article.comments_mode "comments_mode: CommentsMode",
+                    author.username author_username,
+                    author.bio author_bio,
+                    author.image author_image,
+                    exists(select 1 from follow where followed_user_id = author.user_id and following_user_id = $2) "following_author!"
+                from related
+                inner join article using (article_id)
+                inner join "user" author using (user_id)
+                left join article_series_position article_series on article_series.article_id = article.article_id
+                where article.deleted_at is null
+                  and (
+                    $2::uuid is null
+                    or (
+                        article.user_id <> $2
+                        and not exists(
+                            select 1 from article_favorite fav where fav.article_id = article.article_id and fav.user_id = $2
+                        )
+                    )
+                  )
+                order by related.score desc, article.created_at desc, article.article_id desc
+                limit $3
+            "#,
+            article_id,
+            user_id,
+            limit,
+        )
+        .fetch(&self.pool)
+        .map_ok(|article| article.into_article(&self.markdown))
         .try_collect()
         .await?;
 
         Ok(articles)
     }
+
+    pub async fn search_articles(
+        &self,
+        user_id: Option<Uuid>,
+        query: SearchArticlesQuery,
+    ) -> Result<Vec<ArticleSearchHit>> {
+        if query.q.trim().is_empty() {
+            return Err(Error::unprocessable_entity([("q", "can't be blank")]));
+        }
+
+        let (limit, offset) = limit_and_offset(query.limit, query.offset)?;
+        let filters = ArticleFilters::for_search(&query);
+
+        let hits: Vec<_> = sqlx::query_as!(
+            ArticleSearchHitFromQuery,
+            // Unlike `ts_rank_cd()`, `ts_headline()` works on the original document rather than
+            // the `tsvector`, so it's comparatively expensive. It's only evaluated for the rows
+            // that survive `limit` though, since it's only referenced in the select list.
+            //
+            // The highlight markers are control characters that can't meaningfully appear in an
+            // article, so we can find them again after escaping the rest of the snippet.
+            // See `highlight_to_html()` below.
+            //
+            // The filters have to be kept in sync with `article_list()`, same as `count_articles()`.
+            //
+            // language=PostgreSQL
+            r#"
+                with search as (
+                    select websearch_to_tsquery($2::text::regconfig, $3) query
+                )
+                select
+                    article.article_id,
+                    slug,
+                    title,
+                    description,
+                    body,
+                    tag_list,
+                    word_count,
+                    reading_time_minutes,
+                    article.created_at "created_at: Timestamptz",
+                    article.updated_at "updated_at: Timestamptz",
+                    exists(
+                        select 1 from article_favorite fav
+                        where fav.article_id = article.article_id and fav.user_id = $1
+                    ) "favorited!",
+                    exists(select 1 from article_bookmark bm where bm.article_id = article.article_id and bm.user_id = $1) "bookmarked!",
+                    exists(select 1 from article_pin pin where pin.article_id = article.article_id) "pinned!",
+                    article_authors(article.article_id, $1) "authors!: Json<Vec<ArticleAuthor>>",
+                    article_reactions(article.article_id, $1) "reactions!: Json<ArticleReactions>",
+                    article_series.series_slug,
+                    article_series.series_title,
+                    article_series.position series_position,
+                    article_series.series_length,
+                    article_series.previous_slug series_previous,
+                    article_series.next_slug series_next,
+                    coalesce(
+                        (select count(*) from article_favorite fav where fav.article_id = article.article_id),
+                        0
+                    ) "favorites_count!",
+                    coalesce(
+                        (select sum(daily.views) from article_view_daily daily where daily.article_id = article.article_id),
+                        0
+                    )::int8 "views_count!",
+                    article_comments_count(article.article_id) "comments_count!",
+                    article.comments_mode "comments_mode: CommentsMode",
+                    author.username author_username,
+                    author.bio author_bio,
+                    author.image author_image,
+                    exists(select 1 from follow where followed_user_id = author.user_id and following_user_id = $1) "following_author!",
+                    ts_rank_cd(search_vector, search.query) "rank!",
+                    ts_headline(
+                        search_language,
+                        body,
+                        search.query,
+                        'MaxFragments=2, MaxWords=30, MinWords=10, '
+                            || 'StartSel=' || chr(2) || ', StopSel=' || chr(3)
+                    ) "snippet!"
+                from article
+                inner join "user" author using (user_id)
+                left join article_series_position article_series on article_series.article_id = article.article_id
+                cross join search
+                where search_vector @@ search.query
+                  and article.deleted_at is null
+                  and (
+                    cardinality($4::text[]) = 0
+                    or ($10 and tag_list @> $4)
+                    or (not $10 and tag_list && $4)
+                  )
+                  and not tag_list && $5::text[]
+                  and (cardinality($6::text[]) = 0 or author.username = any($6))
+                  and not author.username = any($7::text[])
+                  and (
+                    cardinality($8::text[]) = 0
+                    or (
+                        $10 and not exists(
+                            select 1
+                            from unnest($8) favoriter(username)
+                            where not exists(
+                                select 1
+                                from article_favorite af
+                                inner join "user" using (user_id)
+                                where af.article_id = article.article_id
+                                  and "user".username = favoriter.username
+                            )
+                        )
+                    )
+                    or (
+                        not $10 and exists(
+                            select 1
+                            from article_favorite af
+                            inner join "user" using (user_id)
+                            where af.article_id = article.article_id and "user".username = any($8)
+                        )
+                    )
+                  )
+                  and not exists(
+                    select 1
+                    from article_favorite af
+                    inner join "user" using (user_id)
+                    where af.article_id = article.article_id and "user".username = any($9::text[])
+                  )
+                  and ($11::timestamptz is null or article.created_at >= $11)
+                  and ($12::timestamptz is null or article.created_at < $12)
+                  and (
+                    $13::text is null or exists(
+                        select 1
+                        from series_article
+                        inner join series using (series_id)
+                        where series_article.article_id = article.article_id and series.slug = $13
+                    )
+                  )
+                order by "rank!" desc, article.created_at desc
+                limit $14
+                offset $15
+            "#,
+            user_id,
+            self.config.search_language,
+            query.q,
+            &filters.tags[..],
+            &filters.excluded_tags[..],
+            &filters.authors[..],
+            &filters.excluded_authors[..],
+            &filters.favorited[..],
+            &filters.excluded_favorited[..],
+            filters.match_all,
+            filters.since,
+            filters.until,
+            filters.series,
+            limit,
+            offset
+        )
+        .fetch(&self.pool)
+        .map_ok(|hit| hit.into_hit(&self.markdown))
+        .try_collect()
+        .await?;
+
+        Ok(hits)
+    }
+}
+
+/// Recompute the scores in the `article_trending` materialized view every `interval`, forever.
+///
+/// This is spawned as a background task by `http::serve()`. In a deployment with multiple
+/// replicas of the API, each one refreshes the view on its own schedule, which is wasteful but
+/// harmless. If that ever matters, this could move to a cron job or `pg_cron` instead.
+pub async fn refresh_trending(pool: PgPool, interval: std::time::Duration) {
+    let mut interval = tokio::time::interval(interval);
+    // If a refresh takes longer than the interval, don't try to make up for lost time.
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        // `concurrently` means reads of the view don't block on the refresh.
+        if let Err(e) = sqlx::query!("refresh materialized view concurrently article_trending")
+            .execute(&pool)
+            .await
+        {
+            // Not fatal; we'll just serve slightly stale scores until the next tick.
+            log::error!("failed to refresh article_trending: {e}");
+        }
+    }
+}
+
+/// HTML-escape a `ts_headline()` snippet, then swap its highlight markers for `<mark>` tags.
+fn highlight_to_html(snippet: &str) -> String {
+    let mut html = String::with_capacity(snippet.len() + 32);
+
+    for c in snippet.chars() {
+        match c {
+            '\u{2}' => html.push_str("<mark>"),
+            '\u{3}' => html.push_str("</mark>"),
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+
+    html
+}
+
+#[test]
+fn test_highlight_to_html() {
+    assert_eq!(
+        highlight_to_html("use \u{2}unsafe\u{3} <script> & \"stuff\""),
+        "use <mark>unsafe</mark> &lt;script&gt; &amp; &quot;stuff&quot;"
+    );
+}
+
+#[test]
+fn test_article_cursor_round_trip() {
+    let cursor = ArticleCursor {
+        direction: CursorDirection::Before,
+        created_at: OffsetDateTime::from_unix_timestamp_nanos(1_700_000_000_123_456_000).unwrap(),
+        article_id: Uuid::new_v4(),
+    };
+
+    assert_eq!(ArticleCursor::decode(&cursor.encode()).unwrap(), cursor);
+
+    assert!(ArticleCursor::decode("").is_err());
+    assert!(ArticleCursor::decode("not a cursor").is_err());
+    assert!(ArticleCursor::decode(&URL_SAFE_NO_PAD.encode("a:123")).is_err());
+}
+
+#[test]
+fn test_split_filter() {
+    let values = vec![
+        "rust,sql".to_string(),
+        "-beginner".into(),
+        " go , ,-,".into(),
+    ];
+
+    assert_eq!(
+        split_filter(&values),
+        (
+            vec!["rust".to_string(), "sql".into(), "go".into()],
+            vec!["beginner".to_string()]
+        )
+    );
+}
+
+#[test]
+fn test_count_mode_parsing() {
+    let query: ListArticlesQuery = serde_html_form::from_str("count=estimated").unwrap();
+    assert_eq!(query.count, Some(CountMode::Estimated));
+
+    let query: ListArticlesQuery = serde_html_form::from_str("").unwrap();
+    assert_eq!(query.count, None);
+
+    assert!(serde_html_form::from_str::<ListArticlesQuery>("count=approximate").is_err());
+
+    // `Config::articles_count_mode` goes through clap instead.
+    assert_eq!(
+        <CountMode as clap::ValueEnum>::from_str("page", false),
+        Ok(CountMode::Page)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::article::CreateArticle;
+    use crate::models::testing::{
+        config, create_article, create_tagged_article, create_user, store,
+    };
+    use crate::models::StoreTrait;
+
+    #[sqlx::test]
+    async fn estimate_leaves_out_trash(pool: PgPool) {
+        let store = store(pool.clone(), config());
+        let alice = create_user(&pool, "alice").await;
+
+        for title in ["One", "Two", "Three"] {
+            create_article(&store, alice, title).await;
+        }
+        store.article().delete_article(alice, "two").await.unwrap();
+
+        assert_eq!(
+            store.listing().estimate_article_count().await.unwrap(),
+            None
+        );
+
+        // Small enough that `analyze` looks at every row, so the estimate is exact.
+        sqlx::query!("analyze article")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(
+            store.listing().estimate_article_count().await.unwrap(),
+            Some(2)
+        );
+    }
+
+    #[sqlx::test]
+    async fn search_filters_like_listing(pool: PgPool) {
+        let store = store(pool.clone(), config());
+        let alice = create_user(&pool, "alice").await;
+
+        for (title, tags) in [
+            ("Rust Ownership", vec!["rust"]),
+            ("Rust and SQL", vec!["rust", "sql"]),
+            ("Rust for Beginners", vec!["rust", "beginner"]),
+            ("Rust in Go", vec!["go"]),
+        ] {
+            store
+                .article()
+                .create_article(
+                    alice,
+                    CreateArticle {
+                        title: title.into(),
+                        description: None,
+                        body: "Some thoughts on Rust.".into(),
+                        tag_list: tags.into_iter().map(Into::into).collect(),
+                        comments_mode: Default::default(),
+                    },
+                )
+                .await
+                .unwrap();
+        }
+
+        let search = |tag: &[&str], match_mode| {
+            let store = store.clone();
+            let query = SearchArticlesQuery {
+                q: "rust".into(),
+                tag: tag.iter().map(|tag| tag.to_string()).collect(),
+                match_mode,
+                ..Default::default()
+            };
+
+            async move {
+                let mut slugs: Vec<String> = store
+                    .listing()
+                    .search_articles(None, query)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|hit| hit.article.slug)
+                    .collect();
+                slugs.sort();
+                slugs
+            }
+        };
+
+        assert_eq!(
+            search(&["sql,go"], None).await,
+            ["rust-and-sql", "rust-in-go"]
+        );
+        assert_eq!(
+            search(&["rust", "sql"], Some(MatchMode::All)).await,
+            ["rust-and-sql"]
+        );
+        assert_eq!(
+            search(&["rust", "-beginner"], None).await,
+            ["rust-and-sql", "rust-ownership"]
+        );
+    }
+
+    #[sqlx::test]
+    async fn estimated_count_falls_back_to_exact(pool: PgPool) {
+        let store = store(pool.clone(), config());
+        let alice = create_user(&pool, "alice").await;
+        let bob = create_user(&pool, "bob").await;
+
+        for title in ["One", "Two", "Three"] {
+            create_article(&store, alice, title).await;
+        }
+        create_article(&store, bob, "Four").await;
+
+        let count = |author: Vec<String>| {
+            let store = store.clone();
+            let query = ListArticlesQuery {
+                author,
+                count: Some(CountMode::Estimated),
+                limit: Some(1),
+                ..Default::default()
+            };
+
+            async move {
+                store
+                    .listing()
+                    .article_list(None, query)
+                    .await
+                    .unwrap()
+                    .total_count
+            }
+        };
+
+        // There's no estimate until the table's been analyzed...
+        assert_eq!(count(vec![]).await, Some(4));
+        // ...and never one for a filtered listing.
+        assert_eq!(count(vec!["alice".into()]).await, Some(3));
+
+        sqlx::query!("analyze article")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query!("delete from article where slug = 'four'")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // Now there is, which is stale until the next `analyze`.
+        assert_eq!(count(vec![]).await, Some(4));
+        assert_eq!(count(vec!["alice".into()]).await, Some(3));
+
+        let page = store
+            .listing()
+            .article_list(
+                None,
+                ListArticlesQuery {
+                    count: Some(CountMode::Page),
+                    limit: Some(1),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(page.total_count, None);
+    }
+
+    #[sqlx::test]
+    async fn trending_ranks_by_recent_activity(pool: PgPool) {
+        let store = store(pool.clone(), config());
+        let alice = create_user(&pool, "alice").await;
+        let bob = create_user(&pool, "bob").await;
+
+        for title in ["Quiet", "Popular", "Liked"] {
+            create_article(&store, alice, title).await;
+        }
+        for (user_id, slug) in [(alice, "popular"), (bob, "popular"), (bob, "liked")] {
+            store
+                .article()
+                .favorite_article(user_id, slug)
+                .await
+                .unwrap();
+        }
+
+        // Nothing's trending until the view is refreshed.
+        let trending = || async {
+            store
+                .listing()
+                .trending_articles(None, TrendingArticlesQuery::default())
+                .await
+                .unwrap()
+                .articles
+                .into_iter()
+                .map(|article| article.slug)
+                .collect::<Vec<_>>()
+        };
+        assert!(trending().await.is_empty());
+
+        sqlx::query!("refresh materialized view article_trending")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(trending().await, ["popular", "liked"]);
+    }
+
+    #[sqlx::test]
+    async fn related_by_shared_tags(pool: PgPool) {
+        let store = store(pool.clone(), config());
+        let alice = create_user(&pool, "alice").await;
+
+        for (title, tags) in [
+            ("Source", &["rust", "sql"][..]),
+            ("Lifetimes", &["rust"]),
+            ("Migrations", &["rust", "sql"]),
+            ("Goroutines", &["go"]),
+            ("Trashed", &["rust", "sql"]),
+        ] {
+            create_tagged_article(&store, alice, title, tags).await;
+        }
+        store
+            .article()
+            .delete_article(alice, "trashed")
+            .await
+            .unwrap();
+
+        let related: Vec<_> = store
+            .listing()
+            .related_articles(None, "source", RelatedArticlesQuery::default())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|article| article.slug)
+            .collect();
+
+        // More tags in common first, and never the article itself.
+        assert_eq!(related, ["migrations", "lifetimes"]);
+
+        // Their own articles aren't recommended to the author.
+        let related = store
+            .listing()
+            .related_articles(Some(alice), "source", RelatedArticlesQuery::default())
+            .await
+            .unwrap();
+        assert!(related.is_empty());
+    }
 }
@@ -1,20 +1,39 @@
+use crate::blob::{DynBlobStore, LocalBlobStore};
+use crate::config::Config;
+use crate::filter::ContentFilters;
+use crate::markdown::MarkdownCache;
+use crate::models::view::ViewBuffer;
 use sqlx::PgPool;
 use std::sync::Arc;
+use std::time::Duration;
 
 #[cfg(test)]
 use mockall::automock;
 
 pub mod article;
+pub mod coauthor;
 pub mod comment;
 pub mod listing;
+pub mod moderation;
+pub mod notification;
 pub mod profile;
+pub mod series;
+#[cfg(test)]
+pub mod testing;
+pub mod upload;
 pub mod user;
+pub mod view;
 
 pub type DynStore = Arc<dyn StoreTrait + Send + Sync>;
 
 #[derive(Clone)]
 pub struct Store {
     pub pool: PgPool,
+    pub config: Arc<Config>,
+    pub markdown: Arc<MarkdownCache>,
+    pub blobs: DynBlobStore,
+    pub views: Arc<ViewBuffer>,
+    pub filters: Arc<ContentFilters>,
 }
 #[cfg_attr(test, automock)]
 pub trait StoreTrait {
@@ -22,12 +41,30 @@ pub trait StoreTrait {
     fn profile(&self) -> profile::DynProfileCtrl;
     fn comment(&self) -> comment::CommentController;
     fn article(&self) -> article::ArticleController;
+    fn coauthor(&self) -> coauthor::CoauthorController;
     fn listing(&self) -> listing::ListingController;
+    fn moderation(&self) -> moderation::ModerationController;
+    fn notification(&self) -> notification::NotificationController;
+    fn series(&self) -> series::SeriesController;
+    fn upload(&self) -> upload::UploadController;
+    fn view(&self) -> view::ViewController;
 }
 
 impl Store {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+    pub fn new(pool: PgPool, config: Arc<Config>, filters: Arc<ContentFilters>) -> Self {
+        Self {
+            pool,
+            markdown: Arc::new(MarkdownCache::new(config.markdown_cache_capacity)),
+            blobs: Arc::new(LocalBlobStore::new(
+                &config.upload_dir,
+                &config.upload_base_url,
+            )),
+            views: Arc::new(ViewBuffer::new(Duration::from_secs(
+                config.view_dedup_window,
+            ))),
+            config,
+            filters,
+        }
     }
 }
 
@@ -41,14 +78,52 @@ impl StoreTrait for Store {
     }
 
     fn comment(&self) -> comment::CommentController {
-        comment::CommentController::new(self.pool.clone())
+        comment::CommentController::new(
+            self.pool.clone(),
+            self.config.clone(),
+            self.filters.clone(),
+        )
     }
 
     fn article(&self) -> article::ArticleController {
-        article::ArticleController::new(self.pool.clone())
+        article::ArticleController::new(
+            self.pool.clone(),
+            self.config.clone(),
+            self.markdown.clone(),
+            self.views.clone(),
+            self.filters.clone(),
+        )
+    }
+
+    fn coauthor(&self) -> coauthor::CoauthorController {
+        coauthor::CoauthorController::new(self.pool.clone())
     }
 
     fn listing(&self) -> listing::ListingController {
-        listing::ListingController::new(self.pool.clone())
+        listing::ListingController::new(
+            self.pool.clone(),
+            self.config.clone(),
+            self.markdown.clone(),
+        )
+    }
+
+    fn moderation(&self) -> moderation::ModerationController {
+        moderation::ModerationController::new(self.pool.clone())
+    }
+
+    fn notification(&self) -> notification::NotificationController {
+        notification::NotificationController::new(self.pool.clone())
+    }
+
+    fn series(&self) -> series::SeriesController {
+        series::SeriesController::new(self.pool.clone(), self.config.clone())
+    }
+
+    fn upload(&self) -> upload::UploadController {
+        upload::UploadController::new(self.pool.clone(), self.blobs.clone())
+    }
+
+    fn view(&self) -> view::ViewController {
+        view::ViewController::new(self.pool.clone())
     }
 }
@@ -0,0 +1,1210 @@
+use crate::http::types::Timestamptz;
+use crate::http::{Error, Result, ResultExt};
+use crate::models::listing::limit_and_offset;
+use serde_json::json;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+// Anyone can report an article, comment or profile, which puts it in a queue for moderators.
+// A moderator triages a report to claim it, then resolves it by hiding the content, suspending
+// whoever's responsible, or dismissing it. If they got it wrong, reopening the report undoes that.
+//
+// Everything that happens, including the report itself, goes in `moderation_log`, in the same
+// transaction as the change it's about.
+//
+// The content filters (see `filter.rs`) can also put things in the queue, by holding new content
+// with `hold_content()`. Held content starts out hidden, so for those reports the question is
+// whether to let it through, which is what dismissing one does.
+
+/// The longest `reason` a report can have, in characters.
+const MAX_REASON_CHARS: usize = 1000;
+
+#[derive(Clone)]
+pub struct ModerationController {
+    pool: PgPool,
+}
+
+impl ModerationController {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+/// What a report is about, as `"targetType"` and `"targetId"` side by side.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "targetType", content = "targetId", rename_all = "lowercase")]
+pub enum ReportTarget {
+    /// By slug.
+    Article(String),
+    /// By `Comment.id`.
+    Comment(i64),
+    /// By username.
+    Profile(String),
+}
+
+#[derive(serde::Serialize, serde::Deserialize, sqlx::Type, Copy, Clone, Debug, PartialEq, Eq)]
+// Stored as `text` like `AuthorRole`.
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ReportStatus {
+    Open,
+    /// A moderator has picked it up.
+    Triaged,
+    Resolved,
+    Dismissed,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, sqlx::Type, Copy, Clone, Debug, PartialEq, Eq)]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ModerationAction {
+    /// Hide the article or comment from everyone. Profiles can't be hidden.
+    Hide,
+    /// Stop whoever wrote the content (or the reported user) from doing anything
+    /// that needs them to be logged in.
+    Suspend,
+    /// Do nothing.
+    Dismiss,
+}
+
+impl ModerationAction {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Hide => "hide",
+            Self::Suspend => "suspend",
+            Self::Dismiss => "dismiss",
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct NewReport {
+    #[serde(flatten)]
+    pub target: ReportTarget,
+    pub reason: String,
+}
+
+/// A report as seen by whoever made it.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Report {
+    pub id: Uuid,
+    #[serde(flatten)]
+    pub target: ReportTarget,
+    pub reason: String,
+    pub status: ReportStatus,
+    pub created_at: Timestamptz,
+}
+
+/// A report as seen by moderators.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModerationReport {
+    pub id: Uuid,
+    #[serde(flatten)]
+    pub target: ReportTarget,
+    /// The article the comment is on, for `comment` reports.
+    pub article_slug: Option<String>,
+    /// The article's title, the comment's body or the user's bio, so the queue can be
+    /// skimmed without fetching every target.
+    pub preview: String,
+    /// Who wrote the content, or the reported user.
+    pub author: String,
+    pub author_suspended: bool,
+    /// `null` if they've since deleted their account, or if the report is `held`.
+    pub reporter: Option<String>,
+    /// Whether a content filter held this for moderation, rather than someone reporting it.
+    ///
+    /// Held content is hidden until the report is dismissed.
+    pub held: bool,
+    pub reason: String,
+    pub status: ReportStatus,
+    pub action: Option<ModerationAction>,
+    pub note: Option<String>,
+    pub triaged_by: Option<String>,
+    pub triaged_at: Option<Timestamptz>,
+    pub resolved_by: Option<String>,
+    pub resolved_at: Option<Timestamptz>,
+    pub created_at: Timestamptz,
+}
+
+struct ModerationReportFromQuery {
+    report_id: Uuid,
+    target_type: String,
+    article_slug: Option<String>,
+    comment_id: Option<i64>,
+    preview: String,
+    author: String,
+    author_suspended: bool,
+    reporter: Option<String>,
+    held: bool,
+    reason: String,
+    status: ReportStatus,
+    action: Option<ModerationAction>,
+    note: Option<String>,
+    triaged_by: Option<String>,
+    triaged_at: Option<Timestamptz>,
+    resolved_by: Option<String>,
+    resolved_at: Option<Timestamptz>,
+    created_at: Timestamptz,
+}
+
+impl ModerationReportFromQuery {
+    fn into_report(self) -> ModerationReport {
+        // The check constraints on `report` make sure the right columns are set for each type.
+        let (target, article_slug) = match &*self.target_type {
+            "article" => (
+                ReportTarget::Article(self.article_slug.unwrap_or_default()),
+                None,
+            ),
+            "comment" => (
+                ReportTarget::Comment(self.comment_id.unwrap_or_default()),
+                self.article_slug,
+            ),
+            _ => (ReportTarget::Profile(self.author.clone()), None),
+        };
+
+        ModerationReport {
+            id: self.report_id,
+            target,
+            article_slug,
+            preview: self.preview,
+            author: self.author,
+            author_suspended: self.author_suspended,
+            reporter: self.reporter,
+            held: self.held,
+            reason: self.reason,
+            status: self.status,
+            action: self.action,
+            note: self.note,
+            triaged_by: self.triaged_by,
+            triaged_at: self.triaged_at,
+            resolved_by: self.resolved_by,
+            resolved_at: self.resolved_at,
+            created_at: self.created_at,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Default)]
+#[serde(default)]
+pub struct ReportsQuery {
+    /// Defaults to the queue, i.e. both `open` and `triaged`.
+    pub status: Option<ReportStatus>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+pub struct ReportsPage {
+    pub reports: Vec<ModerationReport>,
+    pub total_count: i64,
+}
+
+#[derive(serde::Deserialize)]
+pub struct Resolution {
+    pub action: ModerationAction,
+    /// Only for other moderators; never shown to the reporter or the author.
+    pub note: Option<String>,
+}
+
+#[derive(serde::Deserialize, Default)]
+#[serde(default)]
+pub struct LogQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEntry {
+    pub id: Uuid,
+    /// `null` if they've since deleted their account.
+    pub actor: Option<String>,
+    pub action: String,
+    pub report_id: Option<Uuid>,
+    pub details: serde_json::Value,
+    pub created_at: Timestamptz,
+}
+
+/// One version of an edited comment.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommentVersion {
+    pub body: String,
+    pub written_at: Timestamptz,
+    /// When the next version replaced it; `null` for the current one.
+    pub replaced_at: Option<Timestamptz>,
+}
+
+/// The row in `report` that `resolve_report()` and `reopen_report()` work on.
+struct ReportTargetRow {
+    target_type: String,
+    article_id: Option<Uuid>,
+    comment_id: Option<i64>,
+    author_id: Uuid,
+    held: bool,
+    status: ReportStatus,
+    action: Option<ModerationAction>,
+}
+
+/// New content a filter wants a moderator to look at; see `hold_content()`.
+#[derive(Copy, Clone, Debug)]
+pub enum HeldContent {
+    Article(Uuid),
+    Comment(i64),
+}
+
+impl ModerationController {
+    /// Report something to the moderators. Anyone who's logged in can do this.
+    pub async fn create_report(&self, user_id: Uuid, report: NewReport) -> Result<Report> {
+        let reason = report.reason.trim();
+
+        if reason.is_empty() {
+            return Err(Error::unprocessable_entity([("reason", "can't be empty")]));
+        }
+
+        if reason.chars().count() > MAX_REASON_CHARS {
+            return Err(Error::unprocessable_entity([(
+                "reason",
+                format!("can't be longer than {MAX_REASON_CHARS} characters"),
+            )]));
+        }
+
+        let not_found = || Error::unprocessable_entity([("targetId", "not found")]);
+
+        let mut tx = self.pool.begin().await?;
+
+        // Hidden and trashed things can't be reported, since nobody can see them anyway.
+        let (target_type, article_id, comment_id, author_id) = match &report.target {
+            ReportTarget::Article(slug) => {
+                let article = sqlx::query!(
+                    "select article_id, user_id from article where slug = $1 and deleted_at is null",
+                    slug
+                )
+                .fetch_optional(&mut tx)
+                .await?
+                .ok_or_else(not_found)?;
+
+                ("article", Some(article.article_id), None, article.user_id)
+            }
+            ReportTarget::Comment(comment_id) => {
+                let author_id = sqlx::query_scalar!(
+                    r#"
+                        select comment.user_id
+                        from article_comment comment
+                        inner join article using (article_id)
+                        where comment_id = $1
+                          and comment.hidden_at is null
+                          and comment.hidden_by_author_at is null
+                          and comment.deleted_at is null
+                          and article.deleted_at is null
+                    "#,
+                    comment_id
+                )
+                .fetch_optional(&mut tx)
+                .await?
+                .ok_or_else(not_found)?;
+
+                ("comment", None, Some(*comment_id), author_id)
+            }
+            ReportTarget::Profile(username) => {
+                let author_id = sqlx::query_scalar!(
+                    r#"select user_id from "user" where username = $1"#,
+                    username
+                )
+                .fetch_optional(&mut tx)
+                .await?
+                .ok_or_else(not_found)?;
+
+                ("profile", None, None, author_id)
+            }
+        };
+
+        let inserted = sqlx::query!(
+            r#"
+                insert into report (reporter_id, target_type, article_id, comment_id, author_id, reason)
+                values ($1, $2, $3, $4, $5, $6)
+                returning report_id, status "status: ReportStatus", created_at
+            "#,
+            user_id,
+            target_type,
+            article_id,
+            comment_id,
+            author_id,
+            reason
+        )
+        .fetch_one(&mut tx)
+        .await
+        .on_constraint("report_pending_unique", |_| {
+            Error::unprocessable_entity([("targetId", "you've already reported this")])
+        })?;
+
+        log(
+            &mut tx,
+            Some(user_id),
+            "report",
+            Some(inserted.report_id),
+            json!({ "target": report.target, "reason": reason }),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Report {
+            id: inserted.report_id,
+            target: report.target,
+            reason: reason.to_string(),
+            status: inserted.status,
+            created_at: Timestamptz(inserted.created_at),
+        })
+    }
+
+    /// The moderation queue, oldest first.
+    pub async fn list_reports(&self, user_id: Uuid, query: ReportsQuery) -> Result<ReportsPage> {
+        require_moderator(&mut *self.pool.acquire().await?, user_id).await?;
+
+        let (limit, offset) = limit_and_offset(query.limit, query.offset)?;
+
+        let reports = sqlx::query_as!(
+            ModerationReportFromQuery,
+            // language=PostgreSQL
+            r#"
+                select
+                    report.report_id,
+                    report.target_type,
+                    article.slug "article_slug?",
+                    report.comment_id,
+                    coalesce(
+                        case report.target_type
+                            when 'article' then article.title
+                            when 'comment' then left(comment.body, 200)
+                            else author.bio
+                        end,
+                        ''
+                    ) "preview!",
+                    author.username author,
+                    author.suspended_at is not null "author_suspended!",
+                    reporter.username "reporter?",
+                    report.held,
+                    report.reason,
+                    report.status "status: ReportStatus",
+                    report.action "action: ModerationAction",
+                    report.note,
+                    triager.username "triaged_by?",
+                    report.triaged_at "triaged_at: Timestamptz",
+                    resolver.username "resolved_by?",
+                    report.resolved_at "resolved_at: Timestamptz",
+                    report.created_at "created_at: Timestamptz"
+                from report
+                inner join "user" author on author.user_id = report.author_id
+                left join "user" reporter on reporter.user_id = report.reporter_id
+                left join "user" triager on triager.user_id = report.triaged_by
+                left join "user" resolver on resolver.user_id = report.resolved_by
+                left join article_comment comment on comment.comment_id = report.comment_id
+                left join article on article.article_id = coalesce(report.article_id, comment.article_id)
+                where ($1::text is null and report.status in ('open', 'triaged')) or report.status = $1
+                order by report.created_at, report.report_id
+                limit $2
+                offset $3
+            "#,
+            query.status as Option<ReportStatus>,
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let total_count = sqlx::query_scalar!(
+            r#"
+                select count(*) "count!"
+                from report
+                where ($1::text is null and status in ('open', 'triaged')) or status = $1
+            "#,
+            query.status as Option<ReportStatus>
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(ReportsPage {
+            reports: reports
+                .into_iter()
+                .map(ModerationReportFromQuery::into_report)
+                .collect(),
+            total_count,
+        })
+    }
+
+    /// Claim an open report.
+    pub async fn triage_report(&self, user_id: Uuid, report_id: Uuid) -> Result<ModerationReport> {
+        let mut tx = self.pool.begin().await?;
+
+        require_moderator(&mut tx, user_id).await?;
+
+        let report = report_for_update(&mut tx, report_id).await?;
+
+        if report.status != ReportStatus::Open {
+            return Err(Error::unprocessable_entity([(
+                "status",
+                "only open reports can be triaged",
+            )]));
+        }
+
+        sqlx::query!(
+            r#"
+                update report
+                set status = 'triaged', triaged_by = $2, triaged_at = now()
+                where report_id = $1
+            "#,
+            report_id,
+            user_id
+        )
+        .execute(&mut tx)
+        .await?;
+
+        log(&mut tx, Some(user_id), "triage", Some(report_id), json!({})).await?;
+
+        tx.commit().await?;
+
+        self.report_by_id(report_id).await
+    }
+
+    /// Act on a report, which doesn't have to have been triaged first.
+    ///
+    /// Hiding or suspending also resolves every other pending report about the same thing,
+    /// so the queue doesn't fill up with duplicates of something that's been dealt with.
+    pub async fn resolve_report(
+        &self,
+        user_id: Uuid,
+        report_id: Uuid,
+        resolution: Resolution,
+    ) -> Result<ModerationReport> {
+        let mut tx = self.pool.begin().await?;
+
+        require_moderator(&mut tx, user_id).await?;
+
+        let report = report_for_update(&mut tx, report_id).await?;
+
+        if matches!(
+            report.status,
+            ReportStatus::Resolved | ReportStatus::Dismissed
+        ) {
+            return Err(Error::unprocessable_entity([(
+                "status",
+                "this report has already been resolved; reopen it first",
+            )]));
+        }
+
+        match resolution.action {
+            ModerationAction::Hide => set_hidden(&mut tx, &report, true).await?,
+            ModerationAction::Suspend => {
+                let target = sqlx::query!(
+                    r#"select is_moderator from "user" where user_id = $1"#,
+                    report.author_id
+                )
+                .fetch_one(&mut tx)
+                .await?;
+
+                if target.is_moderator {
+                    return Err(Error::unprocessable_entity([(
+                        "action",
+                        "moderators can't be suspended",
+                    )]));
+                }
+
+                set_suspended(&mut tx, report.author_id, true).await?;
+            }
+            // Held content was hidden until someone decided it's fine, which is what this means.
+            ModerationAction::Dismiss if report.held => set_hidden(&mut tx, &report, false).await?,
+            ModerationAction::Dismiss => (),
+        }
+
+        let status = match resolution.action {
+            ModerationAction::Dismiss => ReportStatus::Dismissed,
+            _ => ReportStatus::Resolved,
+        };
+
+        let resolved = sqlx::query_scalar!(
+            r#"
+                update report
+                set status = $2, action = $3, note = $4, resolved_by = $5, resolved_at = now()
+                where report_id = $1
+                   or ($3 <> 'dismiss'
+                       and status in ('open', 'triaged')
+                       and (target_type, author_id) = ($6, $7)
+                       and article_id is not distinct from $8
+                       and comment_id is not distinct from $9)
+                returning report_id
+            "#,
+            report_id,
+            status as ReportStatus,
+            resolution.action as ModerationAction,
+            resolution.note,
+            user_id,
+            report.target_type,
+            report.author_id,
+            report.article_id,
+            report.comment_id
+        )
+        .fetch_all(&mut tx)
+        .await?;
+
+        let target = describe_target(&mut tx, &report).await?;
+
+        log(
+            &mut tx,
+            Some(user_id),
+            resolution.action.as_str(),
+            Some(report_id),
+            json!({
+                "target": target,
+                "note": resolution.note,
+                "resolvedReports": resolved,
+            }),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        self.report_by_id(report_id).await
+    }
+
+    /// Undo whatever was done about a report and put it back in the queue.
+    ///
+    /// Other reports that were resolved along with it are reopened too, since the action
+    /// they record no longer stands. A suspension is only lifted if no other resolved report
+    /// about the same author still calls for it.
+    pub async fn reopen_report(&self, user_id: Uuid, report_id: Uuid) -> Result<ModerationReport> {
+        let mut tx = self.pool.begin().await?;
+
+        require_moderator(&mut tx, user_id).await?;
+
+        let report = report_for_update(&mut tx, report_id).await?;
+
+        if matches!(report.status, ReportStatus::Open | ReportStatus::Triaged) {
+            return Err(Error::unprocessable_entity([(
+                "status",
+                "only resolved or dismissed reports can be reopened",
+            )]));
+        }
+
+        match report.action {
+            Some(ModerationAction::Hide) if !report.held => {
+                set_hidden(&mut tx, &report, false).await?
+            }
+            // Not until the reports are reopened below; see there.
+            Some(_) | None => (),
+        }
+
+        let reopened = sqlx::query!(
+            r#"
+                update report
+                set status = 'open', action = null, note = null,
+                    triaged_by = null, triaged_at = null, resolved_by = null, resolved_at = null
+                where report_id = $1
+                   or ($2 <> 'dismiss'
+                       and status = 'resolved'
+                       and action = $2
+                       and (target_type, author_id) = ($3, $4)
+                       and article_id is not distinct from $5
+                       and comment_id is not distinct from $6)
+                returning report_id, held
+            "#,
+            report_id,
+            report.action as Option<ModerationAction>,
+            report.target_type,
+            report.author_id,
+            report.article_id,
+            report.comment_id
+        )
+        .fetch_all(&mut tx)
+        .await?;
+
+        // Held content goes back to how the filter left it: hidden until a moderator lets it
+        // through. That goes for this report or any reopened with it, since hiding something
+        // also resolves the filter's report about it, even if this one wasn't that.
+        if reopened.iter().any(|reopened| reopened.held) {
+            set_hidden(&mut tx, &report, true).await?;
+        }
+
+        let reopened: Vec<Uuid> = reopened
+            .into_iter()
+            .map(|reopened| reopened.report_id)
+            .collect();
+
+        // The author may have been suspended over something else too, in which case that still
+        // stands until those reports are reopened as well.
+        if matches!(report.action, Some(ModerationAction::Suspend)) {
+            let still_suspended = sqlx::query_scalar!(
+                r#"
+                    select exists(
+                        select 1
+                        from report
+                        where status = 'resolved' and action = 'suspend' and author_id = $1
+                    ) "exists!"
+                "#,
+                report.author_id
+            )
+            .fetch_one(&mut tx)
+            .await?;
+
+            if !still_suspended {
+                set_suspended(&mut tx, report.author_id, false).await?;
+            }
+        }
+
+        let target = describe_target(&mut tx, &report).await?;
+
+        log(
+            &mut tx,
+            Some(user_id),
+            "reopen",
+            Some(report_id),
+            json!({
+                "target": target,
+                "undid": report.action,
+                "reopenedReports": reopened,
+            }),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        self.report_by_id(report_id).await
+    }
+
+    /// The audit log, newest first.
+    pub async fn moderation_log(&self, user_id: Uuid, query: LogQuery) -> Result<Vec<LogEntry>> {
+        require_moderator(&mut *self.pool.acquire().await?, user_id).await?;
+
+        let (limit, offset) = limit_and_offset(query.limit, query.offset)?;
+
+        let entries = sqlx::query_as!(
+            LogEntry,
+            r#"
+                select
+                    log_id id,
+                    actor.username "actor?",
+                    action,
+                    report_id,
+                    details,
+                    moderation_log.created_at "created_at: Timestamptz"
+                from moderation_log
+                left join "user" actor on actor.user_id = moderation_log.actor_id
+                order by moderation_log.created_at desc, log_id desc
+                limit $1
+                offset $2
+            "#,
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(entries)
+    }
+
+    /// Every version of a comment, oldest first, including the current one.
+    ///
+    /// This works for hidden comments too, but not deleted ones, as their history
+    /// goes with them.
+    pub async fn comment_history(
+        &self,
+        user_id: Uuid,
+        comment_id: i64,
+    ) -> Result<Vec<CommentVersion>> {
+        require_moderator(&mut *self.pool.acquire().await?, user_id).await?;
+
+        let versions = sqlx::query_as!(
+            CommentVersion,
+            r#"
+                select
+                    body "body!",
+                    written_at "written_at!: Timestamptz",
+                    replaced_at "replaced_at: Timestamptz"
+                from (
+                    select body, written_at, created_at replaced_at
+                    from article_comment_edit
+                    where comment_id = $1
+
+                    union all
+
+                    select body, coalesce(edited_at, created_at), null
+                    from article_comment
+                    where comment_id = $1 and deleted_at is null
+                ) versions
+                order by written_at
+            "#,
+            comment_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        // The current version is always there if the comment is.
+        if versions.is_empty() {
+            return Err(Error::NotFound);
+        }
+
+        Ok(versions)
+    }
+
+    /// Fail unless the user exists and isn't suspended.
+    ///
+    /// Called by the `AuthUser` extractor on every request that requires logging in.
+    pub async fn check_active(&self, user_id: Uuid) -> Result<()> {
+        let suspended = sqlx::query_scalar!(
+            r#"select suspended_at is not null "suspended!" from "user" where user_id = $1"#,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match suspended {
+            // The token is for a user that doesn't exist anymore.
+            None => Err(Error::Unauthorized),
+            Some(true) => Err(Error::Forbidden),
+            Some(false) => Ok(()),
+        }
+    }
+
+    async fn report_by_id(&self, report_id: Uuid) -> Result<ModerationReport> {
+        let report = sqlx::query_as!(
+            ModerationReportFromQuery,
+            // Same as `list_reports()`.
+            //
+            // language=PostgreSQL
+            r#"
+                select
+                    report.report_id,
+                    report.target_type,
+                    article.slug "article_slug?",
+                    report.comment_id,
+                    coalesce(
+                        case report.target_type
+                            when 'article' then article.title
+                            when 'comment' then left(comment.body, 200)
+                            else author.bio
+                        end,
+                        ''
+                    ) "preview!",
+                    author.username author,
+                    author.suspended_at is not null "author_suspended!",
+                    reporter.username "reporter?",
+                    report.held,
+                    report.reason,
+                    report.status "status: ReportStatus",
+                    report.action "action: ModerationAction",
+                    report.note,
+                    triager.username "triaged_by?",
+                    report.triaged_at "triaged_at: Timestamptz",
+                    resolver.username "resolved_by?",
+                    report.resolved_at "resolved_at: Timestamptz",
+                    report.created_at "created_at: Timestamptz"
+                from report
+                inner join "user" author on author.user_id = report.author_id
+                left join "user" reporter on reporter.user_id = report.reporter_id
+                left join "user" triager on triager.user_id = report.triaged_by
+                left join "user" resolver on resolver.user_id = report.resolved_by
+                left join article_comment comment on comment.comment_id = report.comment_id
+                left join article on article.article_id = coalesce(report.article_id, comment.article_id)
+                where report.report_id = $1
+            "#,
+            report_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        Ok(report.into_report())
+    }
+}
+
+/// What the report is about, for `moderation_log.details`, so the entry still makes sense
+/// once the report and the target are gone.
+async fn describe_target(
+    conn: &mut PgConnection,
+    report: &ReportTargetRow,
+) -> Result<serde_json::Value> {
+    let target = sqlx::query!(
+        r#"
+            select
+                author.username author,
+                article.slug "article_slug?",
+                article.title "article_title?"
+            from "user" author
+            left join article_comment comment on comment.comment_id = $2
+            left join article on article.article_id = coalesce($3, comment.article_id)
+            where author.user_id = $1
+        "#,
+        report.author_id,
+        report.comment_id,
+        report.article_id
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(json!({
+        "targetType": report.target_type,
+        "commentId": report.comment_id,
+        "articleSlug": target.article_slug,
+        "articleTitle": target.article_title,
+        "author": target.author,
+    }))
+}
+
+async fn require_moderator(conn: &mut PgConnection, user_id: Uuid) -> Result<()> {
+    let is_moderator = sqlx::query_scalar!(
+        r#"select is_moderator from "user" where user_id = $1"#,
+        user_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    match is_moderator {
+        Some(true) => Ok(()),
+        _ => Err(Error::Forbidden),
+    }
+}
+
+async fn report_for_update(conn: &mut PgConnection, report_id: Uuid) -> Result<ReportTargetRow> {
+    let report = sqlx::query_as!(
+        ReportTargetRow,
+        r#"
+            select
+                target_type,
+                article_id,
+                comment_id,
+                author_id,
+                held,
+                status "status: ReportStatus",
+                action "action: ModerationAction"
+            from report
+            where report_id = $1
+            for update
+        "#,
+        report_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    Ok(report)
+}
+
+async fn set_hidden(conn: &mut PgConnection, report: &ReportTargetRow, hidden: bool) -> Result<()> {
+    match (report.article_id, report.comment_id) {
+        (Some(article_id), _) => {
+            // See the migration for why this goes through `deleted_at`. `now()` is the same
+            // for the whole transaction, so `deleted_at = hidden_at` means the article was
+            // only in the trash because it was hidden.
+            sqlx::query!(
+                r#"
+                    update article
+                    set
+                        hidden_at = case when $2 then coalesce(hidden_at, now()) end,
+                        deleted_at = case
+                            when $2 then coalesce(deleted_at, now())
+                            when deleted_at = hidden_at then null
+                            else deleted_at
+                        end
+                    where article_id = $1
+                "#,
+                article_id,
+                hidden
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            // Same as `delete_article()`, so it doesn't count against the owner's pins.
+            if hidden {
+                sqlx::query!("delete from article_pin where article_id = $1", article_id)
+                    .execute(&mut *conn)
+                    .await?;
+            }
+        }
+        (None, Some(comment_id)) => {
+            sqlx::query!(
+                r#"
+                    update article_comment
+                    set hidden_at = case when $2 then coalesce(hidden_at, now()) end
+                    where comment_id = $1
+                "#,
+                comment_id,
+                hidden
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+        (None, None) => {
+            return Err(Error::unprocessable_entity([(
+                "action",
+                "profiles can't be hidden; suspend the user instead",
+            )]));
+        }
+    }
+
+    Ok(())
+}
+
+async fn set_suspended(conn: &mut PgConnection, user_id: Uuid, suspended: bool) -> Result<()> {
+    sqlx::query!(
+        r#"
+            update "user"
+            set suspended_at = case when $2 then coalesce(suspended_at, now()) end
+            where user_id = $1
+        "#,
+        user_id,
+        suspended
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Hide new content that a filter held for moderation, and put it in the queue.
+///
+/// This is meant to be called in the same transaction that inserted the content, so it's never
+/// visible, even briefly.
+pub async fn hold_content(
+    conn: &mut PgConnection,
+    content: HeldContent,
+    author_id: Uuid,
+    reason: &str,
+) -> Result<()> {
+    let (target_type, article_id, comment_id) = match content {
+        HeldContent::Article(article_id) => ("article", Some(article_id), None),
+        HeldContent::Comment(comment_id) => ("comment", None, Some(comment_id)),
+    };
+
+    let report = ReportTargetRow {
+        target_type: target_type.to_string(),
+        article_id,
+        comment_id,
+        author_id,
+        held: true,
+        status: ReportStatus::Open,
+        action: None,
+    };
+
+    set_hidden(&mut *conn, &report, true).await?;
+
+    // Nobody made this report, so it doesn't conflict with `report_pending_unique`.
+    let report_id = sqlx::query_scalar!(
+        r#"
+            insert into report (target_type, article_id, comment_id, author_id, reason, held)
+            values ($1, $2, $3, $4, $5, true)
+            returning report_id
+        "#,
+        target_type,
+        article_id,
+        comment_id,
+        author_id,
+        reason
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let target = describe_target(&mut *conn, &report).await?;
+
+    log(
+        conn,
+        None,
+        "hold",
+        Some(report_id),
+        json!({ "target": target, "reason": reason }),
+    )
+    .await
+}
+
+/// `actor_id` is `None` for things the server did by itself, like `hold_content()`.
+async fn log(
+    conn: &mut PgConnection,
+    actor_id: Option<Uuid>,
+    action: &str,
+    report_id: Option<Uuid>,
+    details: serde_json::Value,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+            insert into moderation_log (actor_id, action, report_id, details)
+            values ($1, $2, $3, $4)
+        "#,
+        actor_id,
+        action,
+        report_id,
+        details
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::testing::{
+        article_id, config, create_article, create_moderator, create_user, store,
+    };
+    use crate::models::StoreTrait;
+
+    async fn article_hidden(pool: &PgPool, slug: &str) -> bool {
+        sqlx::query_scalar!(
+            r#"select hidden_at is not null "hidden!" from article where slug = $1"#,
+            slug
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    #[test]
+    fn report_target_json() {
+        let report: NewReport = serde_json::from_value(json!({
+            "targetType": "comment",
+            "targetId": 42,
+            "reason": "spam"
+        }))
+        .unwrap();
+
+        assert_eq!(report.target, ReportTarget::Comment(42));
+
+        assert_eq!(
+            serde_json::to_value(ReportTarget::Article("hello".into())).unwrap(),
+            json!({ "targetType": "article", "targetId": "hello" })
+        );
+
+        // The ID has to be the right type for the target.
+        assert!(serde_json::from_value::<NewReport>(json!({
+            "targetType": "comment",
+            "targetId": "hello",
+            "reason": "spam"
+        }))
+        .is_err());
+    }
+
+    #[sqlx::test]
+    async fn reopening_keeps_held_content_hidden(pool: PgPool) {
+        let store = store(pool.clone(), config());
+        let moderation = store.moderation();
+        let alice = create_user(&pool, "alice").await;
+        let bob = create_user(&pool, "bob").await;
+        let moderator = create_moderator(&pool, "moderator").await;
+        let article = create_article(&store, alice, "Spam").await;
+
+        let report = moderation
+            .create_report(
+                bob,
+                NewReport {
+                    target: ReportTarget::Article(article.slug.clone()),
+                    reason: "spam".into(),
+                },
+            )
+            .await
+            .unwrap();
+
+        // Then a filter holds an edit to it, as `update_article()` would.
+        let article_id = article_id(&pool, &article.slug).await;
+        let mut conn = pool.acquire().await.unwrap();
+        hold_content(
+            &mut conn,
+            HeldContent::Article(article_id),
+            alice,
+            "too many links",
+        )
+        .await
+        .unwrap();
+        drop(conn);
+
+        let held = moderation
+            .list_reports(moderator, ReportsQuery::default())
+            .await
+            .unwrap()
+            .reports
+            .into_iter()
+            .find(|report| report.held)
+            .unwrap();
+
+        // Hiding it resolves the held report too...
+        moderation
+            .resolve_report(
+                moderator,
+                report.id,
+                Resolution {
+                    action: ModerationAction::Hide,
+                    note: None,
+                },
+            )
+            .await
+            .unwrap();
+        let held = moderation.report_by_id(held.id).await.unwrap();
+        assert_eq!(held.status, ReportStatus::Resolved);
+
+        // ...so undoing that reopens it, and it's still waiting for a moderator to let it through.
+        moderation
+            .reopen_report(moderator, report.id)
+            .await
+            .unwrap();
+        let held = moderation.report_by_id(held.id).await.unwrap();
+        assert_eq!(held.status, ReportStatus::Open);
+        assert!(article_hidden(&pool, &article.slug).await);
+    }
+
+    #[sqlx::test]
+    async fn reopening_keeps_standing_suspension(pool: PgPool) {
+        let store = store(pool.clone(), config());
+        let moderation = store.moderation();
+        let alice = create_user(&pool, "alice").await;
+        let bob = create_user(&pool, "bob").await;
+        let moderator = create_moderator(&pool, "moderator").await;
+
+        let mut reports = vec![];
+        for title in ["First Offense", "Second Offense"] {
+            let article = create_article(&store, alice, title).await;
+            let report = moderation
+                .create_report(
+                    bob,
+                    NewReport {
+                        target: ReportTarget::Article(article.slug),
+                        reason: "abuse".into(),
+                    },
+                )
+                .await
+                .unwrap();
+            moderation
+                .resolve_report(
+                    moderator,
+                    report.id,
+                    Resolution {
+                        action: ModerationAction::Suspend,
+                        note: None,
+                    },
+                )
+                .await
+                .unwrap();
+            reports.push(report.id);
+        }
+
+        let suspended = || async {
+            moderation
+                .report_by_id(reports[0])
+                .await
+                .unwrap()
+                .author_suspended
+        };
+        assert!(suspended().await);
+
+        // The other suspension still stands.
+        moderation
+            .reopen_report(moderator, reports[0])
+            .await
+            .unwrap();
+        assert!(suspended().await);
+
+        moderation
+            .reopen_report(moderator, reports[1])
+            .await
+            .unwrap();
+        assert!(!suspended().await);
+    }
+}
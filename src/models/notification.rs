@@ -0,0 +1,352 @@
+use crate::http::types::Timestamptz;
+use crate::http::{Error, Result};
+use crate::markdown;
+use crate::models::listing::limit_and_offset;
+use crate::models::profile::Profile;
+use sqlx::{PgConnection, PgPool};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+// None of this is in the Realworld spec.
+//
+// Writing an article or comment that mentions `@someone` notifies them, via `record_mentions()`
+// in the same transaction as the write. Editing it only notifies people who weren't already
+// mentioned, so fixing a typo doesn't ping everyone again.
+//
+// Notifications stay put if what they're about is deleted, hidden or held for approval; the
+// `visible_notification` view just leaves them out until it's back.
+
+/// Only the first this many different users mentioned in something are recorded and notified,
+/// so one comment can't ping the whole site. The rest are still linked when it's rendered.
+const MAX_MENTIONS: usize = 20;
+
+#[derive(Clone)]
+pub struct NotificationController {
+    pool: PgPool,
+}
+
+impl NotificationController {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[derive(serde::Serialize, sqlx::Type, Copy, Clone, Debug, PartialEq, Eq)]
+// Stored as `text` like `CommentsMode`.
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    /// `actor` mentioned you in the article, or in a comment on it if `commentId` is set.
+    Mention,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Notification {
+    pub id: i64,
+    pub kind: NotificationKind,
+    pub actor: Profile,
+    pub article: NotificationArticle,
+    pub comment_id: Option<i64>,
+    pub read: bool,
+    pub created_at: Timestamptz,
+}
+
+/// Just enough to link to it, like `SeriesArticle`.
+#[derive(serde::Serialize)]
+pub struct NotificationArticle {
+    pub slug: String,
+    pub title: String,
+}
+
+#[derive(serde::Deserialize, Default)]
+#[serde(default)]
+pub struct NotificationsQuery {
+    /// Only unread notifications.
+    pub unread: bool,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+pub struct NotificationsPage {
+    pub notifications: Vec<Notification>,
+    /// Of all of them, not just this page or what the query asked for.
+    pub unread_count: i64,
+}
+
+struct NotificationFromQuery {
+    notification_id: i64,
+    kind: NotificationKind,
+    actor_username: String,
+    actor_bio: String,
+    actor_image: Option<String>,
+    following_actor: bool,
+    article_slug: String,
+    article_title: String,
+    comment_id: Option<i64>,
+    read: bool,
+    created_at: Timestamptz,
+}
+
+impl NotificationFromQuery {
+    fn into_notification(self) -> Notification {
+        Notification {
+            id: self.notification_id,
+            kind: self.kind,
+            actor: Profile {
+                username: self.actor_username,
+                bio: self.actor_bio,
+                image: self.actor_image,
+                following: self.following_actor,
+            },
+            article: NotificationArticle {
+                slug: self.article_slug,
+                title: self.article_title,
+            },
+            comment_id: self.comment_id,
+            read: self.read,
+            created_at: self.created_at,
+        }
+    }
+}
+
+/// Record who's mentioned in the latest version of an article, or a comment on it if
+/// `comment_id` is set, and notify anyone who wasn't mentioned in it before.
+///
+/// `actor_id` is whoever wrote this version, who doesn't get notified about mentioning
+/// themselves. Call this whenever the body changes, in the same transaction.
+pub async fn record_mentions(
+    conn: &mut PgConnection,
+    actor_id: Uuid,
+    article_id: Uuid,
+    comment_id: Option<i64>,
+    body: &str,
+) -> Result<()> {
+    // Usernames are case-insensitive, so `@Alice` and `@alice` only take up one slot.
+    let mut seen = HashSet::new();
+    let usernames: Vec<String> = markdown::mentions(body)
+        .into_iter()
+        .filter(|username| seen.insert(username.to_lowercase()))
+        .take(MAX_MENTIONS)
+        .collect();
+
+    // `username` has the `case_insensitive` collation, so `=` takes care of the rest.
+    sqlx::query!(
+        r#"
+            with mentioned as (
+                select user_id
+                from "user"
+                where username = any($4) and user_id <> $1
+            ),
+            removed as (
+                delete from mention
+                where article_id = $2
+                  and comment_id is not distinct from $3
+                  and user_id not in (select user_id from mentioned)
+            ),
+            added as (
+                insert into mention (article_id, comment_id, user_id)
+                select $2, $3, user_id
+                from mentioned
+                -- Without a conflict target, this covers both of the partial unique indexes.
+                on conflict do nothing
+                returning user_id
+            )
+            insert into notification (user_id, kind, actor_id, article_id, comment_id)
+            select user_id, 'mention', $1, $2, $3
+            from added
+        "#,
+        actor_id,
+        article_id,
+        comment_id,
+        &usernames[..]
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+impl NotificationController {
+    /// Newest first.
+    pub async fn list_notifications(
+        &self,
+        user_id: Uuid,
+        query: NotificationsQuery,
+    ) -> Result<NotificationsPage> {
+        let (limit, offset) = limit_and_offset(query.limit, query.offset)?;
+
+        // Postgres can't tell that the view's columns aren't nullable, hence all the `!`s.
+        let notifications = sqlx::query_as!(
+            NotificationFromQuery,
+            // language=PostgreSQL
+            r#"
+                select
+                    notification.notification_id "notification_id!",
+                    notification.kind "kind!: NotificationKind",
+                    actor.username actor_username,
+                    actor.bio actor_bio,
+                    actor.image actor_image,
+                    exists(
+                        select 1
+                        from follow
+                        where followed_user_id = actor.user_id and following_user_id = $1
+                    ) "following_actor!",
+                    article.slug article_slug,
+                    article.title article_title,
+                    notification.comment_id,
+                    notification.read_at is not null "read!",
+                    notification.created_at "created_at!: Timestamptz"
+                from visible_notification notification
+                inner join "user" actor on actor.user_id = notification.actor_id
+                inner join article on article.article_id = notification.article_id
+                where notification.user_id = $1 and (not $2 or notification.read_at is null)
+                order by notification.notification_id desc
+                limit $3
+                offset $4
+            "#,
+            user_id,
+            query.unread,
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let unread_count = sqlx::query_scalar!(
+            r#"
+                select count(*) "count!"
+                from visible_notification
+                where user_id = $1 and read_at is null
+            "#,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(NotificationsPage {
+            notifications: notifications
+                .into_iter()
+                .map(NotificationFromQuery::into_notification)
+                .collect(),
+            unread_count,
+        })
+    }
+
+    /// Mark one of your notifications as read. Reading it again is fine.
+    pub async fn mark_read(&self, user_id: Uuid, notification_id: i64) -> Result<()> {
+        sqlx::query_scalar!(
+            r#"
+                update notification
+                set read_at = coalesce(read_at, now())
+                where notification_id = $1 and user_id = $2
+                returning notification_id
+            "#,
+            notification_id,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        Ok(())
+    }
+
+    /// Mark all of your notifications as read, including ones that aren't visible right now.
+    pub async fn mark_all_read(&self, user_id: Uuid) -> Result<()> {
+        sqlx::query!(
+            "update notification set read_at = now() where user_id = $1 and read_at is null",
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::article::{CreateArticle, UpdateArticle};
+    use crate::models::testing::{config, create_user, store};
+    use crate::models::StoreTrait;
+
+    #[sqlx::test]
+    async fn mentions_notify_once(pool: PgPool) {
+        let store = store(pool.clone(), config());
+        let alice = create_user(&pool, "alice").await;
+        let bob = create_user(&pool, "bob").await;
+        let carol = create_user(&pool, "carol").await;
+
+        store
+            .article()
+            .create_article(
+                alice,
+                CreateArticle {
+                    title: "Mentions".into(),
+                    description: None,
+                    body: "Thanks @bob, @Bob and @alice.".into(),
+                    tag_list: vec![],
+                    comments_mode: Default::default(),
+                },
+            )
+            .await
+            .unwrap();
+
+        let comment_id = store
+            .comment()
+            .create_comment(alice, "mentions", None, "And @bob again.")
+            .await
+            .unwrap()
+            .value
+            .id;
+
+        store
+            .comment()
+            .update_comment(alice, "mentions", comment_id, "And @bob and @carol again.")
+            .await
+            .unwrap();
+        store
+            .article()
+            .update_article(
+                alice,
+                "mentions",
+                UpdateArticle {
+                    title: None,
+                    description: None,
+                    body: Some("Thanks again @bob.".into()),
+                    comments_mode: None,
+                },
+            )
+            .await
+            .unwrap();
+
+        let notifications = |user_id| {
+            let store = store.clone();
+            async move {
+                store
+                    .notification()
+                    .list_notifications(user_id, NotificationsQuery::default())
+                    .await
+                    .unwrap()
+            }
+        };
+
+        // Once for the article and once for the comment, however many times they've been edited.
+        let page = notifications(bob).await;
+        let comment_ids: Vec<_> = page
+            .notifications
+            .iter()
+            .map(|notification| notification.comment_id)
+            .collect();
+        assert_eq!(comment_ids, [Some(comment_id), None]);
+        assert_eq!(page.unread_count, 2);
+
+        let page = notifications(carol).await;
+        assert_eq!(page.notifications.len(), 1);
+        assert_eq!(page.notifications[0].comment_id, Some(comment_id));
+
+        assert!(notifications(alice).await.notifications.is_empty());
+    }
+}
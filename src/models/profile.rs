@@ -1,3 +1,4 @@
+use crate::http::types::Timestamptz;
 use crate::http::{Error, Result, ResultExt};
 use async_trait::async_trait;
 use sqlx::PgPool;
@@ -16,6 +17,19 @@ pub struct Profile {
     pub following: bool,
 }
 
+/// An article pinned to the top of a profile. Not in the Realworld spec.
+///
+/// Just enough to link to it, like `SeriesArticle`; fetch the article itself for the rest.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PinnedArticle {
+    pub slug: String,
+    pub title: String,
+    pub description: String,
+    pub created_at: Timestamptz,
+    pub pinned_at: Timestamptz,
+}
+
 #[derive(Clone)]
 pub struct ProfileController {
     pool: PgPool,
@@ -34,6 +48,8 @@ pub trait ProfileCtrlTrait {
     async fn get_profile_by_id(&self, user_id: Option<Uuid>, username: &str) -> Result<Profile>;
     async fn create_follow(&self, follower: &Uuid, following: &str) -> Result<Profile>;
     async fn unfollow(&self, follower: &Uuid, following: &str) -> Result<Profile>;
+    /// The articles `username` has pinned, most recently pinned first.
+    async fn pinned_articles(&self, username: &str) -> Result<Vec<PinnedArticle>>;
 }
 
 #[async_trait]
@@ -124,4 +140,28 @@ impl ProfileCtrlTrait for ProfileController {
             following: false,
         })
     }
+
+    async fn pinned_articles(&self, username: &str) -> Result<Vec<PinnedArticle>> {
+        let articles = sqlx::query_as!(
+            PinnedArticle,
+            r#"
+                select
+                    article.slug,
+                    article.title,
+                    article.description,
+                    article.created_at "created_at: Timestamptz",
+                    article_pin.created_at "pinned_at: Timestamptz"
+                from article_pin
+                inner join article using (article_id)
+                inner join "user" on "user".user_id = article_pin.user_id
+                where "user".username = $1 and article.deleted_at is null
+                order by article_pin.created_at desc
+            "#,
+            username
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(articles)
+    }
 }
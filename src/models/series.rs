@@ -0,0 +1,462 @@
+use crate::config::Config;
+use crate::http::types::Timestamptz;
+use crate::http::{Error, Result, ResultExt};
+use crate::models::article::{slugify, SlugOptions};
+use crate::models::listing::limit_and_offset;
+use crate::models::profile::Profile;
+use futures::TryStreamExt;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::HashSet;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct SeriesController {
+    pool: PgPool,
+    config: Arc<Config>,
+}
+
+impl SeriesController {
+    pub fn new(pool: PgPool, config: Arc<Config>) -> Self {
+        Self { pool, config }
+    }
+}
+
+/// None of this is in the Realworld spec, but it's modeled on `Article`.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Series {
+    pub slug: String,
+    pub title: String,
+    pub description: String,
+    pub created_at: Timestamptz,
+    pub updated_at: Timestamptz,
+    pub author: Profile,
+    /// In reading order.
+    pub articles: Vec<SeriesArticle>,
+}
+
+/// Just enough of an article to show a table of contents; fetch the article itself for the rest.
+#[derive(serde::Serialize)]
+pub struct SeriesArticle {
+    pub slug: String,
+    pub title: String,
+    pub position: i32,
+}
+
+/// `Article.series`: where an article sits in its series.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArticleSeries {
+    pub slug: String,
+    pub title: String,
+    /// 1-based.
+    pub position: i32,
+    /// The number of articles in the series.
+    pub length: i64,
+    /// The slug of the article before this one, if any.
+    pub previous: Option<String>,
+    /// The slug of the article after this one, if any.
+    pub next: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct CreateSeries {
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    /// Article slugs, in reading order.
+    #[serde(default)]
+    pub articles: Vec<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct UpdateSeries {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// If set, replaces the articles in the series, in this order.
+    pub articles: Option<Vec<String>>,
+}
+
+#[derive(serde::Deserialize, Default)]
+#[serde(default)]
+pub struct ListSeriesQuery {
+    pub author: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+// Same idea as `ArticleFromQuery`.
+struct SeriesFromQuery {
+    series_id: Uuid,
+    slug: String,
+    title: String,
+    description: String,
+    created_at: Timestamptz,
+    updated_at: Timestamptz,
+    author_username: String,
+    author_bio: String,
+    author_image: Option<String>,
+    following_author: bool,
+}
+
+impl SeriesFromQuery {
+    fn into_series(self, articles: Vec<SeriesArticle>) -> Series {
+        Series {
+            slug: self.slug,
+            title: self.title,
+            description: self.description,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            author: Profile {
+                username: self.author_username,
+                bio: self.author_bio,
+                image: self.author_image,
+                following: self.following_author,
+            },
+            articles,
+        }
+    }
+}
+
+impl SeriesController {
+    pub async fn create_series(&self, user_id: Uuid, series: CreateSeries) -> Result<Series> {
+        let slug = slugify(&series.title, &SlugOptions::from(&*self.config));
+
+        let mut tx = self.pool.begin().await?;
+
+        let series_id = sqlx::query_scalar!(
+            r#"
+                insert into series (user_id, slug, title, description)
+                values ($1, $2, $3, $4)
+                returning series_id
+            "#,
+            user_id,
+            slug,
+            series.title,
+            series.description
+        )
+        .fetch_one(&mut tx)
+        .await
+        .on_constraint("series_slug_key", |_| {
+            Error::unprocessable_entity([("slug", format!("duplicate series slug: {slug}"))])
+        })?;
+
+        set_articles(&mut tx, series_id, user_id, &series.articles).await?;
+
+        tx.commit().await?;
+
+        self.series_by_id(Some(user_id), series_id).await
+    }
+
+    pub async fn get_series(&self, user_id: Option<Uuid>, slug: &str) -> Result<Series> {
+        let series_id = sqlx::query_scalar!("select series_id from series where slug = $1", slug)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or(Error::NotFound)?;
+
+        self.series_by_id(user_id, series_id).await
+    }
+
+    /// Series by the given author (or everyone), most recently created first.
+    ///
+    /// The `articles` of each series are left empty; fetch the series itself for those.
+    pub async fn list_series(
+        &self,
+        user_id: Option<Uuid>,
+        query: ListSeriesQuery,
+    ) -> Result<Vec<Series>> {
+        let (limit, offset) = limit_and_offset(query.limit, query.offset)?;
+
+        let series = sqlx::query_as!(
+            SeriesFromQuery,
+            // language=PostgreSQL
+            r#"
+                select
+                    series_id,
+                    slug,
+                    title,
+                    description,
+                    series.created_at "created_at: Timestamptz",
+                    series.updated_at "updated_at: Timestamptz",
+                    author.username author_username,
+                    author.bio author_bio,
+                    author.image author_image,
+                    exists(select 1 from follow where followed_user_id = author.user_id and following_user_id = $1) "following_author!"
+                from series
+                inner join "user" author using (user_id)
+                where $2::text is null or author.username = $2
+                order by series.created_at desc, series_id desc
+                limit $3
+                offset $4
+            "#,
+            user_id,
+            query.author,
+            limit,
+            offset
+        )
+        .fetch(&self.pool)
+        .map_ok(|series| series.into_series(vec![]))
+        .try_collect()
+        .await?;
+
+        Ok(series)
+    }
+
+    pub async fn update_series(
+        &self,
+        user_id: Uuid,
+        slug: &str,
+        series: UpdateSeries,
+    ) -> Result<Series> {
+        let mut tx = self.pool.begin().await?;
+
+        let new_slug = series
+            .title
+            .as_deref()
+            .map(|title| slugify(title, &SlugOptions::from(&*self.config)));
+
+        // Same as `update_article()`: lock the row so the ownership check can't race.
+        let series_meta = sqlx::query!(
+            "select series_id, user_id from series where slug = $1 for update",
+            slug
+        )
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        if series_meta.user_id != user_id {
+            return Err(Error::Forbidden);
+        }
+
+        sqlx::query!(
+            r#"
+                update series
+                set
+                    slug = coalesce($1, slug),
+                    title = coalesce($2, title),
+                    description = coalesce($3, description)
+                where series_id = $4
+            "#,
+            new_slug,
+            series.title,
+            series.description,
+            series_meta.series_id
+        )
+        .execute(&mut tx)
+        .await
+        .on_constraint("series_slug_key", |_| {
+            Error::unprocessable_entity([(
+                "slug",
+                format!(
+                    "duplicate series slug: {}",
+                    new_slug.as_deref().unwrap_or(slug)
+                ),
+            )])
+        })?;
+
+        if let Some(articles) = &series.articles {
+            set_articles(&mut tx, series_meta.series_id, user_id, articles).await?;
+        }
+
+        tx.commit().await?;
+
+        self.series_by_id(Some(user_id), series_meta.series_id)
+            .await
+    }
+
+    /// Deleting a series leaves its articles alone.
+    pub async fn delete_series(&self, user_id: Uuid, slug: &str) -> Result<()> {
+        // Same approach as `delete_article()`.
+        let result = sqlx::query!(
+            r#"
+                with deleted_series as (
+                    delete from series
+                    where slug = $1 and user_id = $2
+                    returning 1
+                )
+                select
+                    exists(select 1 from series where slug = $1) "existed!",
+                    exists(select 1 from deleted_series) "deleted!"
+            "#,
+            slug,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        if result.deleted {
+            Ok(())
+        } else if result.existed {
+            Err(Error::Forbidden)
+        } else {
+            Err(Error::NotFound)
+        }
+    }
+
+    async fn series_by_id(&self, user_id: Option<Uuid>, series_id: Uuid) -> Result<Series> {
+        let series = sqlx::query_as!(
+            SeriesFromQuery,
+            // language=PostgreSQL
+            r#"
+                select
+                    series_id,
+                    slug,
+                    title,
+                    description,
+                    series.created_at "created_at: Timestamptz",
+                    series.updated_at "updated_at: Timestamptz",
+                    author.username author_username,
+                    author.bio author_bio,
+                    author.image author_image,
+                    exists(select 1 from follow where followed_user_id = author.user_id and following_user_id = $1) "following_author!"
+                from series
+                inner join "user" author using (user_id)
+                where series_id = $2
+            "#,
+            user_id,
+            series_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        let articles = sqlx::query_as!(
+            SeriesArticle,
+            r#"
+                select slug, title, position
+                from series_article
+                inner join article using (article_id)
+                where series_id = $1 and article.deleted_at is null
+                order by position
+            "#,
+            series.series_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(series.into_series(articles))
+    }
+}
+
+/// Replace the articles in the series with `slugs`, in that order.
+async fn set_articles(
+    tx: &mut Transaction<'_, Postgres>,
+    series_id: Uuid,
+    user_id: Uuid,
+    slugs: &[String],
+) -> Result<()> {
+    if slugs.iter().collect::<HashSet<_>>().len() != slugs.len() {
+        return Err(Error::unprocessable_entity([(
+            "articles",
+            "can't contain the same article twice",
+        )]));
+    }
+
+    sqlx::query!("delete from series_article where series_id = $1", series_id)
+        .execute(&mut *tx)
+        .await?;
+
+    // `with ordinality` numbers the elements of the array from 1, which is exactly what we want
+    // for `position`.
+    let inserted = sqlx::query!(
+        r#"
+            insert into series_article (series_id, article_id, position)
+            select $1, article.article_id, slugs.position
+            from unnest($2::text[]) with ordinality slugs(slug, position)
+            inner join article using (slug)
+            where article.user_id = $3 and article.deleted_at is null
+        "#,
+        series_id,
+        slugs,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await
+    .on_constraint("series_article_article_id_key", |_| {
+        Error::unprocessable_entity([("articles", "an article can only be in one series")])
+    })?;
+
+    // Either the slug doesn't exist or it's someone else's article. Either way, the positions
+    // would have a gap in them now, so we can't just skip it.
+    if inserted.rows_affected() != slugs.len() as u64 {
+        return Err(Error::unprocessable_entity([(
+            "articles",
+            "must all be articles you wrote",
+        )]));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::testing::{config, create_article, create_user, store};
+    use crate::models::view::Viewer;
+    use crate::models::StoreTrait;
+
+    #[sqlx::test]
+    async fn series_in_reading_order(pool: PgPool) {
+        let store = store(pool.clone(), config());
+        let alice = create_user(&pool, "alice").await;
+        let bob = create_user(&pool, "bob").await;
+
+        for title in ["One", "Two", "Three"] {
+            create_article(&store, alice, title).await;
+        }
+        create_article(&store, bob, "Not Alice's").await;
+
+        let series = store
+            .series()
+            .create_series(
+                alice,
+                CreateSeries {
+                    title: "Counting".into(),
+                    description: String::new(),
+                    articles: vec!["two".into(), "one".into()],
+                },
+            )
+            .await
+            .unwrap();
+        let contents: Vec<_> = series
+            .articles
+            .iter()
+            .map(|article| (&*article.slug, article.position))
+            .collect();
+        assert_eq!(contents, [("two", 1), ("one", 2)]);
+
+        let article = store
+            .article()
+            .get_article(None, "one", Viewer::Anonymous(0))
+            .await
+            .unwrap();
+        let in_series = article.series.unwrap();
+        assert_eq!(in_series.slug, "counting");
+        assert_eq!((in_series.position, in_series.length), (2, 2));
+        assert_eq!(in_series.previous.as_deref(), Some("two"));
+        assert_eq!(in_series.next, None);
+
+        // Only your own articles, and each in one series at most.
+        for articles in [
+            vec!["three".into(), "not-alices".into()],
+            vec!["one".into()],
+        ] {
+            let result = store
+                .series()
+                .create_series(
+                    alice,
+                    CreateSeries {
+                        title: "Another".into(),
+                        description: String::new(),
+                        articles,
+                    },
+                )
+                .await;
+            assert!(matches!(
+                result,
+                Err(Error::UnprocessableEntity { errors }) if errors.contains_key("articles")
+            ));
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Helpers for tests that need a database.
+//!
+//! These are for `#[sqlx::test]`, which gives each test its own database with the migrations
+//! already run, on the server `DATABASE_URL` points at.
+
+use crate::config::Config;
+use crate::filter::ContentFilters;
+use crate::models::article::{Article, CreateArticle};
+use crate::models::{Store, StoreTrait};
+use clap::Parser;
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// The defaults for everything, as opposed to `Config::default()` which zeroes it all.
+pub fn config() -> Config {
+    Config::try_parse_from([
+        "realworld",
+        "--database-url=",
+        "--hmac-key=test",
+        "--port=0",
+    ])
+    .expect("defaults are valid")
+}
+
+/// With no content filters, so tests can post the same thing as often as they like.
+pub fn store(pool: PgPool, config: Config) -> Store {
+    Store::new(
+        pool,
+        Arc::new(config),
+        Arc::new(ContentFilters::new(vec![])),
+    )
+}
+
+/// Skips hashing a password, since nobody's going to log in as them.
+pub async fn create_user(pool: &PgPool, username: &str) -> Uuid {
+    sqlx::query_scalar!(
+        r#"insert into "user" (username, email, password_hash) values ($1, $2, '') returning user_id"#,
+        username,
+        format!("{username}@example.com")
+    )
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+pub async fn create_moderator(pool: &PgPool, username: &str) -> Uuid {
+    let user_id = create_user(pool, username).await;
+
+    sqlx::query!(
+        r#"update "user" set is_moderator = true where user_id = $1"#,
+        user_id
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+
+    user_id
+}
+
+pub async fn create_article(store: &Store, author_id: Uuid, title: &str) -> Article {
+    create_tagged_article(store, author_id, title, &[]).await
+}
+
+pub async fn create_tagged_article(
+    store: &Store,
+    author_id: Uuid,
+    title: &str,
+    tags: &[&str],
+) -> Article {
+    store
+        .article()
+        .create_article(
+            author_id,
+            CreateArticle {
+                title: title.into(),
+                description: None,
+                body: format!("All about {title}."),
+                tag_list: tags.iter().map(|tag| tag.to_string()).collect(),
+                comments_mode: Default::default(),
+            },
+        )
+        .await
+        .unwrap()
+        .value
+}
+
+/// `Article` doesn't have its ID in it, since that's not in the Realworld spec.
+pub async fn article_id(pool: &PgPool, slug: &str) -> Uuid {
+    sqlx::query_scalar!("select article_id from article where slug = $1", slug)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
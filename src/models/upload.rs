@@ -0,0 +1,274 @@
+use crate::blob::DynBlobStore;
+use crate::http::{Error, Result};
+use anyhow::Context;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::io::Cursor;
+use uuid::Uuid;
+
+// We never store what the client sent us as-is. Every upload is decoded, resized if necessary
+// and re-encoded, which:
+//
+// * means we only ever serve files that really are the image type their extension says,
+//   no matter what the client claimed in `Content-Type`,
+// * strips EXIF metadata, which tends to include things like the GPS coordinates of
+//   where a photo was taken,
+// * and keeps someone from using us to host arbitrarily large files.
+//
+// Files are named after the SHA-256 of the re-encoded bytes, so uploading the same image twice
+// (or two users picking the same stock avatar) only stores it once, and the files can be cached
+// forever since they can never change.
+
+/// Anything bigger than this in either dimension is rejected before we try to decode it,
+/// because a tiny PNG can decompress into gigabytes of pixels.
+const MAX_SOURCE_DIMENSION: u32 = 10_000;
+
+const JPEG_QUALITY: u8 = 85;
+
+#[derive(Clone)]
+pub struct UploadController {
+    pool: PgPool,
+    blobs: DynBlobStore,
+}
+
+impl UploadController {
+    pub fn new(pool: PgPool, blobs: DynBlobStore) -> Self {
+        Self { pool, blobs }
+    }
+}
+
+/// What we did with an uploaded image.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Upload {
+    pub url: String,
+    pub thumbnail_url: String,
+    /// What the image was stored as, which isn't necessarily what was uploaded.
+    pub content_type: &'static str,
+    pub width: u32,
+    pub height: u32,
+    /// The size of the stored image in bytes.
+    pub size: usize,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UploadKind {
+    /// Cropped to a square, since that's how every frontend displays them.
+    Avatar,
+    /// Images to link to from article bodies. Only scaled down, never cropped.
+    ArticleImage,
+}
+
+impl UploadKind {
+    /// The largest width and height of the stored image.
+    fn max_size(self) -> u32 {
+        match self {
+            Self::Avatar => 512,
+            Self::ArticleImage => 2048,
+        }
+    }
+
+    fn thumbnail_size(self) -> u32 {
+        match self {
+            Self::Avatar => 96,
+            Self::ArticleImage => 400,
+        }
+    }
+
+    fn resize(self, image: &DynamicImage, size: u32) -> DynamicImage {
+        match self {
+            Self::Avatar => {
+                let side = size.min(image.width()).min(image.height());
+                image.resize_to_fill(side, side, FilterType::Lanczos3)
+            }
+            Self::ArticleImage if image.width() <= size && image.height() <= size => image.clone(),
+            Self::ArticleImage => image.resize(size, size, FilterType::Lanczos3),
+        }
+    }
+}
+
+/// An image that's ready to be handed to the `BlobStore`.
+#[derive(Debug)]
+struct EncodedImage {
+    key: String,
+    content_type: &'static str,
+    data: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+impl UploadController {
+    /// Process and store an uploaded image along with its thumbnail.
+    pub async fn upload_image(&self, kind: UploadKind, data: Vec<u8>) -> Result<Upload> {
+        // Decoding and resizing are CPU-bound, and can take a while for a big photo.
+        let (image, thumbnail) = tokio::task::spawn_blocking(move || process_image(kind, &data))
+            .await
+            .context("panic in processing uploaded image")??;
+
+        let upload = Upload {
+            url: self.blobs.url(&image.key),
+            thumbnail_url: self.blobs.url(&thumbnail.key),
+            content_type: image.content_type,
+            width: image.width,
+            height: image.height,
+            size: image.data.len(),
+        };
+
+        futures::try_join!(
+            self.blobs.put(&image.key, image.content_type, image.data),
+            self.blobs
+                .put(&thumbnail.key, thumbnail.content_type, thumbnail.data),
+        )?;
+
+        Ok(upload)
+    }
+
+    /// Upload a new avatar and set it as the user's `image`.
+    pub async fn set_avatar(&self, user_id: Uuid, data: Vec<u8>) -> Result<Upload> {
+        let upload = self.upload_image(UploadKind::Avatar, data).await?;
+
+        sqlx::query!(
+            r#"update "user" set image = $1 where user_id = $2"#,
+            upload.url,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(upload)
+    }
+}
+
+/// Returns the image to store and its thumbnail.
+fn process_image(kind: UploadKind, data: &[u8]) -> Result<(EncodedImage, EncodedImage)> {
+    // Sniff the format from the first few bytes rather than trusting the client.
+    let format = match image::guess_format(data) {
+        Ok(
+            format @ (ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::Gif | ImageFormat::WebP),
+        ) => format,
+        _ => {
+            return Err(Error::unprocessable_entity([(
+                "image",
+                "must be a JPEG, PNG, GIF or WebP image",
+            )]))
+        }
+    };
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_SOURCE_DIMENSION);
+    limits.max_image_height = Some(MAX_SOURCE_DIMENSION);
+
+    let mut reader = ImageReader::with_format(Cursor::new(data), format);
+    reader.limits(limits);
+
+    let image = reader
+        .into_decoder()
+        .and_then(|mut decoder| {
+            // Phones store photos sideways and set an EXIF flag saying which way is up.
+            // We're about to throw the EXIF data away, so this has to be applied first.
+            let orientation = decoder.orientation()?;
+            let mut image = DynamicImage::from_decoder(decoder)?;
+            image.apply_orientation(orientation);
+            Ok(image)
+        })
+        .map_err(|e| match e {
+            image::ImageError::Limits(_) => Error::unprocessable_entity([(
+                "image",
+                format!("can't be wider or taller than {MAX_SOURCE_DIMENSION} pixels"),
+            )]),
+            _ => Error::unprocessable_entity([("image", "couldn't be decoded")]),
+        })?;
+
+    // Photos stay JPEGs since they'd be several times bigger as PNGs. Everything else becomes a
+    // PNG: it's lossless, and the `image` crate can't encode lossy WebP. Animated GIFs lose
+    // their animation, which is fine for what these are used for.
+    let output_format = match format {
+        ImageFormat::Jpeg => ImageFormat::Jpeg,
+        _ => ImageFormat::Png,
+    };
+
+    Ok((
+        encode(&kind.resize(&image, kind.max_size()), output_format)?,
+        encode(&kind.resize(&image, kind.thumbnail_size()), output_format)?,
+    ))
+}
+
+fn encode(image: &DynamicImage, format: ImageFormat) -> Result<EncodedImage> {
+    let mut data = Vec::new();
+
+    let (content_type, extension) = match format {
+        ImageFormat::Jpeg => {
+            // JPEG has no alpha channel, and the encoder won't drop it for us.
+            DynamicImage::ImageRgb8(image.to_rgb8())
+                .write_with_encoder(JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY))
+                .context("failed to encode JPEG")?;
+            ("image/jpeg", "jpg")
+        }
+        _ => {
+            image
+                .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+                .context("failed to encode PNG")?;
+            ("image/png", "png")
+        }
+    };
+
+    let hash = format!("{:x}", Sha256::digest(&data));
+
+    Ok(EncodedImage {
+        // Splitting on the first two characters keeps any one directory from getting too big.
+        key: format!("{}/{hash}.{extension}", &hash[..2]),
+        content_type,
+        data,
+        width: image.width(),
+        height: image.height(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Rgba};
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = ImageBuffer::from_pixel(width, height, Rgba([255u8, 0, 0, 255]));
+        let mut data = Vec::new();
+        DynamicImage::ImageRgba8(image)
+            .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn rejects_non_images() {
+        // Claiming to be a PNG doesn't help; we only look at the bytes.
+        let err =
+            process_image(UploadKind::ArticleImage, b"<script>alert(1)</script>").unwrap_err();
+        assert!(matches!(err, Error::UnprocessableEntity { .. }), "{err:?}");
+
+        let mut truncated = png(10, 10);
+        truncated.truncate(40);
+        let err = process_image(UploadKind::ArticleImage, &truncated).unwrap_err();
+        assert!(matches!(err, Error::UnprocessableEntity { .. }), "{err:?}");
+    }
+
+    #[test]
+    fn resizes_and_names_by_content() {
+        let (image, thumbnail) = process_image(UploadKind::Avatar, &png(800, 600)).unwrap();
+
+        assert_eq!((image.width, image.height), (512, 512));
+        assert_eq!((thumbnail.width, thumbnail.height), (96, 96));
+        assert_eq!(image.content_type, "image/png");
+        assert!(image.key.ends_with(".png"), "{}", image.key);
+
+        // Small article images are left at their original size.
+        let (image, thumbnail) = process_image(UploadKind::ArticleImage, &png(300, 100)).unwrap();
+        assert_eq!((image.width, image.height), (300, 100));
+        assert_eq!((thumbnail.width, thumbnail.height), (300, 100));
+
+        let (again, _) = process_image(UploadKind::ArticleImage, &png(300, 100)).unwrap();
+        assert_eq!(image.key, again.key);
+    }
+}
@@ -0,0 +1,453 @@
+use crate::http::{Error, Result};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use time::{Date, OffsetDateTime};
+use uuid::Uuid;
+
+// Counting a view on every `GET /api/articles/:slug` would turn the most common read in the
+// API into a write, so views go through `ViewBuffer` first. That deduplicates them and adds them
+// up in memory, and `flush_views()` periodically writes the totals to `article_view_daily`.
+//
+// The tradeoff is that `viewsCount` lags behind by up to `view_flush_interval`, and if the process
+// dies, the views since the last flush are lost. For a view counter, that's fine.
+//
+// Logged-in readers' views also mark the article as seen, for `?unseen=true` on the feed, and
+// those go through the buffer for the same reason. Only the first view in each window marks it,
+// but the feed only cares whether there's a mark at all.
+
+/// Who viewed an article, for deduplication. This is only ever kept in memory.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Viewer {
+    User(Uuid),
+    /// A hash of the client's IP address and `User-Agent`; see the `Viewer` extractor.
+    Anonymous(u64),
+}
+
+/// Buffers article views between flushes to the database.
+///
+/// Shared by every request, so the state is behind a (synchronous) mutex. It's only ever held
+/// for a couple of hash map operations so that's cheaper than an async one.
+pub struct ViewBuffer {
+    /// Repeat views by the same viewer within this long are only counted once.
+    window: Duration,
+    state: Mutex<ViewBufferState>,
+}
+
+#[derive(Default)]
+struct ViewBufferState {
+    /// When each viewer last had a view of each article counted.
+    last_counted: HashMap<(Uuid, Viewer), Instant>,
+    pending: Pending,
+}
+
+/// What's waiting for the next flush.
+#[derive(Default)]
+struct Pending {
+    /// Views not yet written to the database, per article per (UTC) day.
+    views: HashMap<(Uuid, Date), i64>,
+    /// When each user last viewed each article, keyed by `(user_id, article_id)`.
+    seen: HashMap<(Uuid, Uuid), OffsetDateTime>,
+}
+
+impl ViewBuffer {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Count a view, unless `viewer` already viewed the article within the window.
+    ///
+    /// Returns whether the view was counted.
+    pub fn record(&self, article_id: Uuid, viewer: Viewer) -> bool {
+        self.record_at(
+            article_id,
+            viewer,
+            Instant::now(),
+            OffsetDateTime::now_utc(),
+        )
+    }
+
+    fn record_at(
+        &self,
+        article_id: Uuid,
+        viewer: Viewer,
+        now: Instant,
+        now_utc: OffsetDateTime,
+    ) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        match state.last_counted.get(&(article_id, viewer)) {
+            Some(last) if now.duration_since(*last) < self.window => return false,
+            _ => (),
+        }
+
+        state.last_counted.insert((article_id, viewer), now);
+        *state
+            .pending
+            .views
+            .entry((article_id, now_utc.date()))
+            .or_default() += 1;
+
+        if let Viewer::User(user_id) = viewer {
+            state.pending.seen.insert((user_id, article_id), now_utc);
+        }
+
+        true
+    }
+
+    /// Take the pending views, and forget about viewers whose window has passed
+    /// so `last_counted` doesn't grow forever.
+    fn take_pending(&self) -> Pending {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        let now = Instant::now();
+        let window = self.window;
+        state
+            .last_counted
+            .retain(|_, last| now.duration_since(*last) < window);
+
+        std::mem::take(&mut state.pending)
+    }
+
+    /// Put back views that failed to flush, so they get another try next time.
+    fn restore_views(&self, views: HashMap<(Uuid, Date), i64>) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        for (key, count) in views {
+            *state.pending.views.entry(key).or_default() += count;
+        }
+    }
+
+    /// The same for seen marks. Any newer ones since then win.
+    fn restore_seen(&self, seen: HashMap<(Uuid, Uuid), OffsetDateTime>) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        for (key, seen_at) in seen {
+            state.pending.seen.entry(key).or_insert(seen_at);
+        }
+    }
+}
+
+/// Write buffered views to `article_view_daily`, and seen marks to `article_seen`, every
+/// `interval`, forever.
+///
+/// Spawned by `http::serve()`, same as `listing::refresh_trending()`. Each replica of the API
+/// has its own buffer, which is fine since the upsert adds to whatever is already there,
+/// although a reader bouncing between replicas may be counted once on each.
+pub async fn flush_views(pool: PgPool, buffer: Arc<ViewBuffer>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        let Pending { views, seen } = buffer.take_pending();
+
+        if !views.is_empty() {
+            if let Err(e) = write_views(&pool, &views).await {
+                log::error!("failed to flush article views: {e}");
+                buffer.restore_views(views);
+            }
+        }
+
+        if !seen.is_empty() {
+            if let Err(e) = write_seen(&pool, &seen).await {
+                log::error!("failed to flush seen articles: {e}");
+                buffer.restore_seen(seen);
+            }
+        }
+    }
+}
+
+async fn write_views(pool: &PgPool, views: &HashMap<(Uuid, Date), i64>) -> sqlx::Result<()> {
+    let mut article_ids = Vec::with_capacity(views.len());
+    let mut days = Vec::with_capacity(views.len());
+    let mut counts = Vec::with_capacity(views.len());
+
+    for (&(article_id, day), &count) in views {
+        article_ids.push(article_id);
+        days.push(day);
+        counts.push(count);
+    }
+
+    // The join drops views of articles that were deleted since, which would otherwise
+    // fail the foreign key and take the rest of the batch down with them.
+    sqlx::query!(
+        r#"
+            insert into article_view_daily (article_id, day, views)
+            select article_id, views.day, views.count
+            from unnest($1::uuid[], $2::date[], $3::int8[]) views(article_id, day, count)
+            inner join article using (article_id)
+            on conflict (article_id, day) do update
+                set views = article_view_daily.views + excluded.views
+        "#,
+        &article_ids,
+        &days,
+        &counts
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+async fn write_seen(
+    pool: &PgPool,
+    seen: &HashMap<(Uuid, Uuid), OffsetDateTime>,
+) -> sqlx::Result<()> {
+    let mut user_ids = Vec::with_capacity(seen.len());
+    let mut article_ids = Vec::with_capacity(seen.len());
+    let mut seen_ats = Vec::with_capacity(seen.len());
+
+    for (&(user_id, article_id), &seen_at) in seen {
+        user_ids.push(user_id);
+        article_ids.push(article_id);
+        seen_ats.push(seen_at);
+    }
+
+    // Same as above, for users who deleted their accounts since as well.
+    sqlx::query!(
+        r#"
+            insert into article_seen (user_id, article_id, seen_at)
+            select seen.user_id, seen.article_id, seen.seen_at
+            from unnest($1::uuid[], $2::uuid[], $3::timestamptz[]) seen(user_id, article_id, seen_at)
+            inner join article on article.article_id = seen.article_id
+            inner join "user" on "user".user_id = seen.user_id
+            on conflict (user_id, article_id) do update
+                set seen_at = greatest(article_seen.seen_at, excluded.seen_at)
+        "#,
+        &user_ids,
+        &article_ids,
+        &seen_ats
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct ViewController {
+    pool: PgPool,
+}
+
+impl ViewController {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[derive(serde::Deserialize, Default)]
+#[serde(default)]
+pub struct ArticleStatsQuery {
+    /// How many days to return, counting back from today. Defaults to 30.
+    pub days: Option<i64>,
+}
+
+const DEFAULT_STATS_DAYS: i64 = 30;
+const MAX_STATS_DAYS: i64 = 365;
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArticleStats {
+    /// All time, same as `Article.viewsCount`.
+    pub views_count: i64,
+    pub favorites_count: i64,
+    pub comments_count: i64,
+    /// Oldest first, with an entry for every day in the range, even ones without any views.
+    pub days: Vec<DailyViews>,
+}
+
+#[derive(serde::Serialize)]
+pub struct DailyViews {
+    /// `YYYY-MM-DD`, in UTC.
+    pub date: String,
+    pub views: i64,
+}
+
+impl ViewController {
+    /// Readership of an article, for its authors.
+    ///
+    /// Views are only as up-to-date as the last flush of the `ViewBuffer`.
+    pub async fn article_stats(
+        &self,
+        user_id: Uuid,
+        slug: &str,
+        query: ArticleStatsQuery,
+    ) -> Result<ArticleStats> {
+        let days = query.days.unwrap_or(DEFAULT_STATS_DAYS);
+
+        if !(1..=MAX_STATS_DAYS).contains(&days) {
+            return Err(Error::unprocessable_entity([(
+                "days",
+                format!("must be between 1 and {MAX_STATS_DAYS}"),
+            )]));
+        }
+
+        let article = sqlx::query!(
+            r#"
+                select
+                    article.article_id,
+                    exists(
+                        select 1
+                        from article_author
+                        where article_author.article_id = article.article_id
+                          and user_id = $2
+                          and accepted_at is not null
+                    ) "is_author!"
+                from article
+                where slug = $1 and deleted_at is null
+            "#,
+            slug,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        if !article.is_author {
+            return Err(Error::Forbidden);
+        }
+
+        let totals = sqlx::query!(
+            r#"
+                select
+                    coalesce((select sum(views) from article_view_daily where article_id = $1), 0)::int8 "views_count!",
+                    (select count(*) from article_favorite where article_id = $1) "favorites_count!",
+                    article_comments_count($1) "comments_count!"
+            "#,
+            article.article_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        let days = sqlx::query_as!(
+            DailyViews,
+            r#"
+                select
+                    to_char(series.day, 'YYYY-MM-DD') "date!",
+                    coalesce(daily.views, 0) "views!"
+                from generate_series(
+                    (now() at time zone 'utc')::date - ($2::int8 - 1)::int4,
+                    (now() at time zone 'utc')::date,
+                    '1 day'
+                ) series(day)
+                left join article_view_daily daily
+                    on daily.article_id = $1 and daily.day = series.day::date
+                order by series.day
+            "#,
+            article.article_id,
+            days
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(ArticleStats {
+            views_count: totals.views_count,
+            favorites_count: totals.favorites_count,
+            comments_count: totals.comments_count,
+            days,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::testing::{article_id, config, create_article, create_user, store};
+
+    #[test]
+    fn dedupes_within_window() {
+        let buffer = ViewBuffer::new(Duration::from_secs(60));
+        let article_id = Uuid::new_v4();
+        let alice = Viewer::User(Uuid::new_v4());
+        let anon = Viewer::Anonymous(42);
+        let start = Instant::now();
+        let now = OffsetDateTime::now_utc();
+
+        assert!(buffer.record_at(article_id, alice, start, now));
+        assert!(!buffer.record_at(article_id, alice, start + Duration::from_secs(30), now));
+        assert!(buffer.record_at(article_id, anon, start + Duration::from_secs(30), now));
+        assert!(buffer.record_at(article_id, alice, start + Duration::from_secs(61), now));
+
+        assert_eq!(buffer.take_pending().views[&(article_id, now.date())], 3);
+        assert!(buffer.take_pending().views.is_empty());
+    }
+
+    #[test]
+    fn marks_seen_for_users() {
+        let buffer = ViewBuffer::new(Duration::from_secs(60));
+        let article_id = Uuid::new_v4();
+        let alice = Uuid::new_v4();
+        let start = Instant::now();
+        let now = OffsetDateTime::now_utc();
+
+        buffer.record_at(article_id, Viewer::User(alice), start, now);
+        buffer.record_at(article_id, Viewer::Anonymous(42), start, now);
+
+        let seen = buffer.take_pending().seen;
+        assert_eq!(seen.len(), 1);
+        assert_eq!(seen[&(alice, article_id)], now);
+
+        // A failed flush puts it back, unless they've seen it again since.
+        let later = now + time::Duration::minutes(5);
+        buffer.record_at(
+            article_id,
+            Viewer::User(alice),
+            start + Duration::from_secs(300),
+            later,
+        );
+        buffer.restore_seen(seen);
+        assert_eq!(buffer.take_pending().seen[&(alice, article_id)], later);
+    }
+
+    #[sqlx::test]
+    async fn writes_pending(pool: PgPool) {
+        let store = store(pool.clone(), config());
+        let alice = create_user(&pool, "alice").await;
+        create_article(&store, alice, "Kept").await;
+        create_article(&store, alice, "Gone").await;
+
+        let kept = article_id(&pool, "kept").await;
+        let gone = article_id(&pool, "gone").await;
+
+        let buffer = ViewBuffer::new(Duration::from_secs(60));
+        buffer.record(kept, Viewer::User(alice));
+        buffer.record(kept, Viewer::Anonymous(42));
+        buffer.record(gone, Viewer::User(alice));
+
+        // Purged before the flush, which shouldn't stop the rest being written.
+        sqlx::query!("delete from article where article_id = $1", gone)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let Pending { views, seen } = buffer.take_pending();
+        write_views(&pool, &views).await.unwrap();
+        write_seen(&pool, &seen).await.unwrap();
+        // Adds to what's there.
+        write_views(&pool, &views).await.unwrap();
+
+        let views = sqlx::query_scalar!(
+            "select views from article_view_daily where article_id = $1",
+            kept
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(views, [4]);
+
+        let seen = sqlx::query_scalar!(
+            r#"select article_id from article_seen where user_id = $1"#,
+            alice
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(seen, [kept]);
+    }
+}